@@ -0,0 +1,3 @@
+--- Drop Tables ---
+
+DROP TABLE reserved_device_ids;
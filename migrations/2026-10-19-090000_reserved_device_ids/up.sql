@@ -0,0 +1,12 @@
+-- Adds support for reserving device IDs ahead of time, so that labels can be printed before the devices are added.
+
+--- Tables ---
+
+CREATE TABLE reserved_device_ids
+(
+	id          INTEGER PRIMARY KEY NOT NULL,
+	device_id   TEXT                NOT NULL UNIQUE,
+	reserved_by INTEGER             NOT NULL,
+	reserved_at TIMESTAMP           NOT NULL,
+	FOREIGN KEY (reserved_by) REFERENCES user_info (id)
+);
@@ -0,0 +1,8 @@
+--- Drop Indices ---
+
+DROP INDEX device_loans_single_open_loan;
+
+
+--- Drop Tables ---
+
+DROP TABLE device_loans;
@@ -0,0 +1,31 @@
+-- Adds tracking for devices being loaned out to people, and returned.
+
+--- Tables ---
+
+CREATE TABLE device_loans
+(
+	id                 INTEGER PRIMARY KEY NOT NULL,
+	device_key_info_id INTEGER             NOT NULL,
+	borrower_user_id   INTEGER             NULL,
+	borrower_name      TEXT                NULL,
+	loaned_by          INTEGER             NOT NULL,
+	loaned_at          TIMESTAMP           NOT NULL,
+	due_at             TIMESTAMP           NULL,
+	notes              TEXT                NOT NULL DEFAULT '',
+	returned_at        TIMESTAMP           NULL,
+	returned_to        INTEGER             NULL,
+	return_notes       TEXT                NULL,
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id),
+	FOREIGN KEY (borrower_user_id) REFERENCES user_info (id),
+	FOREIGN KEY (loaned_by) REFERENCES user_info (id),
+	FOREIGN KEY (returned_to) REFERENCES user_info (id),
+	CHECK (borrower_user_id IS NOT NULL OR borrower_name IS NOT NULL)
+);
+
+
+--- Indices ---
+
+-- A device can only be out on one loan at a time
+CREATE UNIQUE INDEX device_loans_single_open_loan
+	ON device_loans (device_key_info_id)
+	WHERE returned_at IS NULL;
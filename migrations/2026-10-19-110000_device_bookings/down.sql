@@ -0,0 +1,10 @@
+--- Drop Indices ---
+
+DROP INDEX device_bookings_device_time_range;
+
+
+--- Drop Tables ---
+
+DROP TABLE calendar_feed_tokens;
+
+DROP TABLE device_bookings;
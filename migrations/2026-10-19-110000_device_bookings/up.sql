@@ -0,0 +1,31 @@
+-- Adds bookings for devices over a time range, and the tokens for subscribing to them as calendar feeds.
+
+--- Tables ---
+
+CREATE TABLE device_bookings
+(
+	id                 INTEGER PRIMARY KEY NOT NULL,
+	device_key_info_id INTEGER             NOT NULL,
+	user_id            INTEGER             NOT NULL,
+	starts_at          TIMESTAMP           NOT NULL,
+	ends_at            TIMESTAMP           NOT NULL,
+	purpose            TEXT                NOT NULL DEFAULT '',
+	cancelled          BOOLEAN             NOT NULL DEFAULT 0,
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id),
+	FOREIGN KEY (user_id) REFERENCES user_info (id),
+	CHECK (starts_at < ends_at)
+);
+
+CREATE TABLE calendar_feed_tokens
+(
+	id      INTEGER PRIMARY KEY NOT NULL,
+	user_id INTEGER             NOT NULL UNIQUE,
+	value   TEXT                NOT NULL UNIQUE,
+	FOREIGN KEY (user_id) REFERENCES user_info (id)
+);
+
+
+--- Indices ---
+
+CREATE INDEX device_bookings_device_time_range
+	ON device_bookings (device_key_info_id, starts_at, ends_at);
@@ -0,0 +1,77 @@
+--- Drop Triggers ---
+
+DROP TRIGGER user_locations_new;
+
+DROP TRIGGER user_locations_existing;
+
+
+--- Flatten Locations ---
+
+-- Nested locations keep their full path as their name, so that names stay
+-- unique
+CREATE TEMPORARY TABLE locations_backup AS
+SELECT id,
+       path AS name
+FROM location_paths;
+
+
+--- Drop Views ---
+
+DROP VIEW location_subtrees;
+
+DROP VIEW location_paths;
+
+
+--- Drop Indices ---
+
+DROP INDEX locations_unique_name_per_parent;
+
+
+--- Tables ---
+
+DROP TABLE locations;
+
+CREATE TABLE locations
+(
+	id   INTEGER PRIMARY KEY NOT NULL,
+	name TEXT                NOT NULL UNIQUE
+);
+
+INSERT INTO locations
+	(id, name)
+SELECT id,
+       name
+FROM locations_backup;
+
+DROP TABLE locations_backup;
+
+
+--- Triggers ---
+
+CREATE TRIGGER user_locations_existing
+	AFTER INSERT
+	ON user_info
+	WHEN NEW.associated_location_id IS NULL
+		AND (SELECT ll.id
+		     FROM locations AS ll
+		     WHERE ll.name = NEW.display_name) IS NOT NULL
+BEGIN
+	UPDATE user_info
+	SET associated_location_id = (SELECT ll.id FROM locations AS ll WHERE ll.name = NEW.display_name)
+	WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER user_locations_new
+	AFTER INSERT
+	ON user_info
+	WHEN NEW.associated_location_id IS NULL
+		AND (SELECT ll.id
+		     FROM locations AS ll
+		     WHERE ll.name = NEW.display_name) IS NULL
+BEGIN
+	INSERT INTO locations (name) VALUES (NEW.display_name);
+
+	UPDATE user_info
+	SET associated_location_id = (SELECT ll.id FROM locations AS ll WHERE ll.name = NEW.display_name)
+	WHERE id = NEW.id;
+END;
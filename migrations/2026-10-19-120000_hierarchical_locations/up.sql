@@ -0,0 +1,130 @@
+-- Allows locations to be nested under a parent location.
+
+--- Triggers ---
+
+-- The personal location triggers are recreated below, as they need to ignore
+-- nested locations that happen to share a user's name
+DROP TRIGGER user_locations_existing;
+
+DROP TRIGGER user_locations_new;
+
+
+--- Tables ---
+
+CREATE TEMPORARY TABLE locations_backup AS
+SELECT *
+FROM locations;
+
+DROP TABLE locations;
+
+CREATE TABLE locations
+(
+	id        INTEGER PRIMARY KEY NOT NULL,
+	parent_id INTEGER             NULL DEFAULT NULL,
+	name      TEXT                NOT NULL,
+	FOREIGN KEY (parent_id) REFERENCES locations (id)
+);
+
+INSERT INTO locations
+	(id, parent_id, name)
+SELECT id,
+       NULL AS parent_id,
+       name
+FROM locations_backup;
+
+DROP TABLE locations_backup;
+
+
+--- Indices ---
+
+-- Names only need to be unique amongst siblings (top-level locations are
+-- treated as siblings of each other)
+CREATE UNIQUE INDEX locations_unique_name_per_parent
+	ON locations (IFNULL(parent_id, 0), name);
+
+
+--- Views ---
+
+-- The full path of every location, from the top-level location down
+CREATE VIEW location_paths AS
+WITH RECURSIVE paths (id, parent_id, name, path, depth) AS (
+	SELECT l.id,
+	       l.parent_id,
+	       l.name,
+	       l.name AS path,
+	       0      AS depth
+	FROM locations AS l
+	WHERE l.parent_id IS NULL
+	UNION ALL
+	SELECT l.id,
+	       l.parent_id,
+	       l.name,
+	       p.path || ' / ' || l.name AS path,
+	       p.depth + 1               AS depth
+	FROM locations AS l
+	INNER JOIN paths AS p
+		ON p.id = l.parent_id
+)
+SELECT id,
+       parent_id,
+       name,
+       path,
+       depth
+FROM paths;
+
+-- Every location in the subtree of each location, including the location itself
+CREATE VIEW location_subtrees AS
+WITH RECURSIVE subtrees (ancestor_id, location_id) AS (
+	SELECT l.id AS ancestor_id,
+	       l.id AS location_id
+	FROM locations AS l
+	UNION
+	SELECT s.ancestor_id,
+	       l.id AS location_id
+	FROM locations AS l
+	INNER JOIN subtrees AS s
+		ON s.location_id = l.parent_id
+)
+SELECT ancestor_id,
+       location_id
+FROM subtrees;
+
+
+--- Triggers ---
+
+-- Creates the association to a location for a new user.
+CREATE TRIGGER user_locations_existing
+	AFTER INSERT
+	ON user_info
+	WHEN NEW.associated_location_id IS NULL
+		AND (SELECT ll.id
+		     FROM locations AS ll
+		     WHERE ll.parent_id IS NULL
+			     AND ll.name = NEW.display_name) IS NOT NULL
+BEGIN
+	UPDATE user_info
+	SET associated_location_id = (SELECT ll.id
+	                              FROM locations AS ll
+	                              WHERE ll.parent_id IS NULL
+		                              AND ll.name = NEW.display_name)
+	WHERE id = NEW.id;
+END;
+
+CREATE TRIGGER user_locations_new
+	AFTER INSERT
+	ON user_info
+	WHEN NEW.associated_location_id IS NULL
+		AND (SELECT ll.id
+		     FROM locations AS ll
+		     WHERE ll.parent_id IS NULL
+			     AND ll.name = NEW.display_name) IS NULL
+BEGIN
+	INSERT INTO locations (name) VALUES (NEW.display_name);
+
+	UPDATE user_info
+	SET associated_location_id = (SELECT ll.id
+	                              FROM locations AS ll
+	                              WHERE ll.parent_id IS NULL
+		                              AND ll.name = NEW.display_name)
+	WHERE id = NEW.id;
+END;
@@ -0,0 +1,31 @@
+--- Drop Indices ---
+
+DROP INDEX device_key_info_parent_device;
+
+
+--- Tables ---
+
+CREATE TEMPORARY TABLE device_key_info_backup AS
+SELECT *
+FROM device_key_info;
+
+DROP TABLE device_key_info;
+
+CREATE TABLE device_key_info
+(
+	id          INTEGER PRIMARY KEY NOT NULL,
+	device_id   TEXT                NOT NULL UNIQUE,
+	deleted     BOOLEAN             NOT NULL DEFAULT 0,
+	location_id INTEGER             NOT NULL,
+	FOREIGN KEY (location_id) REFERENCES locations (id)
+);
+
+INSERT INTO device_key_info
+	(id, device_id, deleted, location_id)
+SELECT id,
+       device_id,
+       deleted,
+       location_id
+FROM device_key_info_backup;
+
+DROP TABLE device_key_info_backup;
@@ -0,0 +1,39 @@
+-- Allows devices to be attached to a parent device, such as a monitor or a
+-- docking station that belongs with a workstation.
+
+--- Tables ---
+
+CREATE TEMPORARY TABLE device_key_info_backup AS
+SELECT *
+FROM device_key_info;
+
+DROP TABLE device_key_info;
+
+CREATE TABLE device_key_info
+(
+	id               INTEGER PRIMARY KEY NOT NULL,
+	device_id        TEXT                NOT NULL UNIQUE,
+	deleted          BOOLEAN             NOT NULL DEFAULT 0,
+	location_id      INTEGER             NOT NULL,
+	parent_device_id INTEGER             NULL DEFAULT NULL,
+	FOREIGN KEY (location_id) REFERENCES locations (id),
+	FOREIGN KEY (parent_device_id) REFERENCES device_key_info (id),
+	CHECK (parent_device_id IS NULL OR parent_device_id != id)
+);
+
+INSERT INTO device_key_info
+	(id, device_id, deleted, location_id, parent_device_id)
+SELECT id,
+       device_id,
+       deleted,
+       location_id,
+       NULL AS parent_device_id
+FROM device_key_info_backup;
+
+DROP TABLE device_key_info_backup;
+
+
+--- Indices ---
+
+CREATE INDEX device_key_info_parent_device
+	ON device_key_info (parent_device_id);
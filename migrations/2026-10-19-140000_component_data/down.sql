@@ -0,0 +1,7 @@
+--- Drop Tables ---
+
+DROP TABLE device_component_data;
+
+DROP TABLE component_column_possible_values;
+
+DROP TABLE component_column_definitions;
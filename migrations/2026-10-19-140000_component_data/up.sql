@@ -0,0 +1,50 @@
+-- Adds structured data for device components, with column definitions that
+-- work the same way as the ones for devices.
+
+--- Tables ---
+
+CREATE TABLE component_column_definitions
+(
+	id                          INTEGER PRIMARY KEY NOT NULL,
+	name                        TEXT                NOT NULL UNIQUE,
+	ordering_key                INTEGER             NULL     DEFAULT NULL,
+	not_null                    BOOLEAN             NOT NULL DEFAULT 0,
+	unique_values               BOOLEAN             NOT NULL DEFAULT 0,
+	exclusively_possible_values BOOLEAN             NOT NULL DEFAULT 0,
+	default_value_id            INTEGER             NULL     DEFAULT NULL,
+	FOREIGN KEY (default_value_id) REFERENCES component_column_possible_values (id)
+);
+
+CREATE TABLE component_column_possible_values
+(
+	id                             INTEGER PRIMARY KEY NOT NULL,
+	component_column_definition_id INTEGER             NOT NULL,
+	value                          TEXT                NOT NULL,
+	FOREIGN KEY (component_column_definition_id) REFERENCES component_column_definitions (id)
+);
+
+CREATE TABLE device_component_data
+(
+	id                             INTEGER PRIMARY KEY NOT NULL,
+	device_component_id            INTEGER             NOT NULL,
+	component_column_definition_id INTEGER             NOT NULL,
+	data_value                     TEXT                NOT NULL,
+	FOREIGN KEY (device_component_id) REFERENCES device_components (id),
+	FOREIGN KEY (component_column_definition_id) REFERENCES component_column_definitions (id),
+	UNIQUE (device_component_id, component_column_definition_id)
+);
+
+
+--- Component Column Definitions ---
+
+INSERT INTO component_column_definitions (name, ordering_key, not_null, unique_values,
+                                          exclusively_possible_values)
+VALUES ('Serial Number', 1, 0, 1, 0);
+
+INSERT INTO component_column_definitions (name, ordering_key, not_null, unique_values,
+                                          exclusively_possible_values)
+VALUES ('Part Number', 2, 0, 0, 0);
+
+INSERT INTO component_column_definitions (name, ordering_key, not_null, unique_values,
+                                          exclusively_possible_values)
+VALUES ('Capacity', 3, 0, 0, 0);
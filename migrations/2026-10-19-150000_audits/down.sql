@@ -0,0 +1,10 @@
+--- Drop Indices ---
+
+DROP INDEX audit_sessions_single_open_audit;
+
+
+--- Drop Tables ---
+
+DROP TABLE audit_scans;
+
+DROP TABLE audit_sessions;
@@ -0,0 +1,40 @@
+-- Adds audit sessions, for stocktakes where the devices in a location are
+-- scanned to check that they're actually there.
+
+--- Tables ---
+
+CREATE TABLE audit_sessions
+(
+	id          INTEGER PRIMARY KEY NOT NULL,
+	location_id INTEGER             NOT NULL,
+	started_by  INTEGER             NOT NULL,
+	started_at  TIMESTAMP           NOT NULL,
+	closed_by   INTEGER             NULL,
+	closed_at   TIMESTAMP           NULL,
+	report      TEXT                NULL,
+	FOREIGN KEY (location_id) REFERENCES locations (id),
+	FOREIGN KEY (started_by) REFERENCES user_info (id),
+	FOREIGN KEY (closed_by) REFERENCES user_info (id)
+);
+
+CREATE TABLE audit_scans
+(
+	id                 INTEGER PRIMARY KEY NOT NULL,
+	audit_session_id   INTEGER             NOT NULL,
+	scanned_value      TEXT                NOT NULL,
+	device_key_info_id INTEGER             NULL,
+	scanned_by         INTEGER             NOT NULL,
+	scanned_at         TIMESTAMP           NOT NULL,
+	FOREIGN KEY (audit_session_id) REFERENCES audit_sessions (id),
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id),
+	FOREIGN KEY (scanned_by) REFERENCES user_info (id),
+	UNIQUE (audit_session_id, scanned_value)
+);
+
+
+--- Indices ---
+
+-- A location can only have one audit in progress at a time
+CREATE UNIQUE INDEX audit_sessions_single_open_audit
+	ON audit_sessions (location_id)
+	WHERE closed_at IS NULL;
@@ -0,0 +1,50 @@
+-- Adds change batches, so that the changes made to many devices at once by a
+-- bulk edit can be grouped together.
+
+--- Tables ---
+
+CREATE TABLE device_change_batches
+(
+	id         INTEGER PRIMARY KEY NOT NULL,
+	user_id    INTEGER             NOT NULL,
+	created_at TIMESTAMP           NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id)
+);
+
+CREATE TEMPORARY TABLE device_changes_backup AS
+SELECT *
+FROM device_changes;
+
+DROP TABLE device_changes;
+
+CREATE TABLE device_changes
+(
+	id                 INTEGER PRIMARY KEY NOT NULL,
+	device_key_info_id INTEGER             NOT NULL,
+	timestamp          TIMESTAMP           NOT NULL,
+	done_automatically BOOLEAN             NOT NULL DEFAULT 0,
+	user_id            INTEGER             NULL,
+	change             TEXT                NOT NULL,
+	batch_id           INTEGER             NULL,
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id),
+	FOREIGN KEY (user_id) REFERENCES user_info (id),
+	FOREIGN KEY (batch_id) REFERENCES device_change_batches (id),
+	CHECK (json_valid(change))
+);
+
+INSERT INTO device_changes
+	(id, device_key_info_id, timestamp, done_automatically, user_id, change, batch_id)
+SELECT id,
+       device_key_info_id,
+       timestamp,
+       done_automatically,
+       user_id,
+       change,
+       NULL AS batch_id
+FROM device_changes_backup;
+
+DROP TABLE device_changes_backup;
+
+--- Indices ---
+
+CREATE INDEX device_changes_batch_id ON device_changes (batch_id);
@@ -0,0 +1,8 @@
+--- Drop Indices ---
+
+DROP INDEX device_lifecycles_end_of_life_on;
+DROP INDEX device_lifecycles_warranty_ends_on;
+
+--- Drop Tables ---
+
+DROP TABLE device_lifecycles;
@@ -0,0 +1,20 @@
+-- Adds lifecycle dates for devices, so that warranties and planned replacements can be tracked and reported on.
+
+--- Tables ---
+
+CREATE TABLE device_lifecycles
+(
+	id                        INTEGER PRIMARY KEY NOT NULL,
+	device_key_info_id        INTEGER             NOT NULL UNIQUE,
+	purchased_on              DATE                NULL,
+	warranty_ends_on          DATE                NULL,
+	end_of_life_on            DATE                NULL,
+	-- Whether the automatic status change for an expired warranty has been made already, so that it only happens once
+	warranty_expiry_processed BOOLEAN             NOT NULL DEFAULT 0,
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id)
+);
+
+--- Indices ---
+
+CREATE INDEX device_lifecycles_warranty_ends_on ON device_lifecycles (warranty_ends_on);
+CREATE INDEX device_lifecycles_end_of_life_on ON device_lifecycles (end_of_life_on);
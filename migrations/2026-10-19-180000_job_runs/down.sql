@@ -0,0 +1,7 @@
+--- Drop Indices ---
+
+DROP INDEX job_runs_job_name_started_at;
+
+--- Drop Tables ---
+
+DROP TABLE job_runs;
@@ -0,0 +1,21 @@
+-- Adds a history of scheduled background job runs.
+
+--- Tables ---
+
+CREATE TABLE job_runs
+(
+	id           INTEGER PRIMARY KEY NOT NULL,
+	job_name     TEXT                NOT NULL,
+	started_at   TIMESTAMP           NOT NULL,
+	finished_at  TIMESTAMP           NULL,
+	succeeded    BOOLEAN             NULL,
+	-- The user who triggered the run manually, if it wasn't run on schedule
+	triggered_by INTEGER             NULL,
+	output       TEXT                NULL,
+	FOREIGN KEY (triggered_by) REFERENCES user_info (id),
+	CHECK (output IS NULL OR json_valid(output))
+);
+
+--- Indices ---
+
+CREATE INDEX job_runs_job_name_started_at ON job_runs (job_name, started_at);
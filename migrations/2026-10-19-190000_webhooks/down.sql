@@ -0,0 +1,9 @@
+--- Drop Indices ---
+
+DROP INDEX webhook_deliveries_attempted_at;
+DROP INDEX webhook_outbox_completed_at;
+
+--- Drop Tables ---
+
+DROP TABLE webhook_deliveries;
+DROP TABLE webhook_outbox;
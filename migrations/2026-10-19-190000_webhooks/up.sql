@@ -0,0 +1,34 @@
+-- Adds an outbox of device changes to send to webhooks, and a log of the attempts to deliver them.
+
+--- Tables ---
+
+CREATE TABLE webhook_outbox
+(
+	id               INTEGER PRIMARY KEY NOT NULL,
+	device_change_id INTEGER             NOT NULL UNIQUE,
+	event            TEXT                NOT NULL,
+	created_at       TIMESTAMP           NOT NULL,
+	-- Set once every webhook has either received the change or run out of attempts
+	completed_at     TIMESTAMP           NULL,
+	FOREIGN KEY (device_change_id) REFERENCES device_changes (id)
+);
+
+CREATE TABLE webhook_deliveries
+(
+	id                INTEGER PRIMARY KEY NOT NULL,
+	webhook_outbox_id INTEGER             NOT NULL,
+	webhook_name      TEXT                NOT NULL,
+	attempt           INTEGER             NOT NULL,
+	attempted_at      TIMESTAMP           NOT NULL,
+	-- The HTTP status code of the response, if there was one
+	status_code       INTEGER             NULL,
+	error             TEXT                NULL,
+	succeeded         BOOLEAN             NOT NULL,
+	FOREIGN KEY (webhook_outbox_id) REFERENCES webhook_outbox (id),
+	UNIQUE (webhook_outbox_id, webhook_name, attempt)
+);
+
+--- Indices ---
+
+CREATE INDEX webhook_outbox_completed_at ON webhook_outbox (completed_at);
+CREATE INDEX webhook_deliveries_attempted_at ON webhook_deliveries (attempted_at);
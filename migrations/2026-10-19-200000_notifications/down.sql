@@ -0,0 +1,13 @@
+--- Drop Indices ---
+
+DROP INDEX notifications_status_next_attempt_at;
+
+--- Drop Tables ---
+
+DROP TABLE notifications;
+DROP TABLE notification_preferences;
+
+--- Drop Columns ---
+
+ALTER TABLE user_info
+	DROP COLUMN email;
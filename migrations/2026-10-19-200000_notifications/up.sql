@@ -0,0 +1,42 @@
+-- Adds email addresses for users, per-user notification preferences, and a queue of notifications to
+-- send by email.
+
+--- Columns ---
+
+ALTER TABLE user_info
+	ADD COLUMN email TEXT NULL;
+
+--- Tables ---
+
+CREATE TABLE notification_preferences
+(
+	user_id           INTEGER PRIMARY KEY NOT NULL,
+	loan_overdue      BOOLEAN             NOT NULL,
+	warranty_expiring BOOLEAN             NOT NULL,
+	device_changed    BOOLEAN             NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id)
+);
+
+CREATE TABLE notifications
+(
+	id              INTEGER PRIMARY KEY NOT NULL,
+	user_id         INTEGER             NOT NULL,
+	kind            TEXT                NOT NULL,
+	-- The values that are filled into the message template
+	details         TEXT                NOT NULL,
+	-- Prevents the same notification from being queued more than once
+	dedupe_key      TEXT                NULL UNIQUE,
+	created_at      TIMESTAMP           NOT NULL,
+	-- One of `pending`, `sent`, `failed`, or `skipped`
+	status          TEXT                NOT NULL,
+	attempts        INTEGER             NOT NULL DEFAULT 0,
+	next_attempt_at TIMESTAMP           NOT NULL,
+	finished_at     TIMESTAMP           NULL,
+	last_error      TEXT                NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id),
+	CHECK (json_valid(details))
+);
+
+--- Indices ---
+
+CREATE INDEX notifications_status_next_attempt_at ON notifications (status, next_attempt_at);
@@ -0,0 +1,14 @@
+--- Drop Indices ---
+
+DROP INDEX watches_user_id_saved_search_id;
+DROP INDEX watches_user_id_location_id;
+DROP INDEX watches_user_id_device_key_info_id;
+
+
+--- Drop Tables ---
+
+DROP TABLE watch_feed_visits;
+
+DROP TABLE watches;
+
+DROP TABLE saved_searches;
@@ -0,0 +1,50 @@
+-- Adds saved searches, watches on devices, locations, and saved searches, and
+-- how far each user has read through the changes to what they're watching.
+
+--- Tables ---
+
+CREATE TABLE saved_searches
+(
+	id         INTEGER PRIMARY KEY NOT NULL,
+	user_id    INTEGER             NOT NULL,
+	name       TEXT                NOT NULL,
+	-- The search query, in the same form as it's submitted to the search route
+	query      TEXT                NOT NULL,
+	created_at TIMESTAMP           NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id),
+	UNIQUE (user_id, name),
+	CHECK (json_valid(query))
+);
+
+CREATE TABLE watches
+(
+	id                 INTEGER PRIMARY KEY NOT NULL,
+	user_id            INTEGER             NOT NULL,
+	-- Exactly one of these is set
+	device_key_info_id INTEGER             NULL,
+	location_id        INTEGER             NULL,
+	saved_search_id    INTEGER             NULL,
+	created_at         TIMESTAMP           NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id),
+	FOREIGN KEY (device_key_info_id) REFERENCES device_key_info (id),
+	FOREIGN KEY (location_id) REFERENCES locations (id),
+	FOREIGN KEY (saved_search_id) REFERENCES saved_searches (id),
+	CHECK ((device_key_info_id IS NOT NULL) + (location_id IS NOT NULL) + (saved_search_id IS NOT NULL) = 1)
+);
+
+CREATE TABLE watch_feed_visits
+(
+	user_id             INTEGER PRIMARY KEY NOT NULL,
+	-- The newest change the user has seen in their feed
+	last_seen_change_id INTEGER             NOT NULL,
+	visited_at          TIMESTAMP           NOT NULL,
+	FOREIGN KEY (user_id) REFERENCES user_info (id)
+);
+
+
+--- Indices ---
+
+-- A user can only watch each thing once
+CREATE UNIQUE INDEX watches_user_id_device_key_info_id ON watches (user_id, device_key_info_id);
+CREATE UNIQUE INDEX watches_user_id_location_id ON watches (user_id, location_id);
+CREATE UNIQUE INDEX watches_user_id_saved_search_id ON watches (user_id, saved_search_id);
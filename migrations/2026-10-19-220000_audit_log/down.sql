@@ -0,0 +1,10 @@
+--- Drop Indices ---
+
+DROP INDEX audit_log_target;
+DROP INDEX audit_log_actor_user_id;
+DROP INDEX audit_log_timestamp;
+
+
+--- Drop Tables ---
+
+DROP TABLE audit_log;
@@ -0,0 +1,29 @@
+-- Adds an audit log of administrative changes and authentication events, which
+-- covers everything that isn't a change to a device.
+
+--- Tables ---
+
+CREATE TABLE audit_log
+(
+	id            INTEGER PRIMARY KEY NOT NULL,
+	timestamp     TIMESTAMP           NOT NULL,
+	-- The user who did it, if they're known
+	actor_user_id INTEGER             NULL,
+	action        TEXT                NOT NULL,
+	-- The kind of thing that was acted on, and its ID (if it has one)
+	target_type   TEXT                NOT NULL,
+	target_id     TEXT                NULL,
+	before_state  TEXT                NULL,
+	after_state   TEXT                NULL,
+	client_ip     TEXT                NULL,
+	FOREIGN KEY (actor_user_id) REFERENCES user_info (id),
+	CHECK (before_state IS NULL OR json_valid(before_state)),
+	CHECK (after_state IS NULL OR json_valid(after_state))
+);
+
+
+--- Indices ---
+
+CREATE INDEX audit_log_timestamp ON audit_log (timestamp);
+CREATE INDEX audit_log_actor_user_id ON audit_log (actor_user_id);
+CREATE INDEX audit_log_target ON audit_log (target_type, target_id);
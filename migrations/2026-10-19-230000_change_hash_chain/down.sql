@@ -0,0 +1,6 @@
+--- Drop Columns ---
+
+ALTER TABLE device_changes
+	DROP COLUMN hash;
+ALTER TABLE device_changes
+	DROP COLUMN previous_hash;
@@ -0,0 +1,11 @@
+-- Chains each device change to the one before it with a SHA-256 hash, so that editing or deleting
+-- changes can be detected.
+-- SQLite can't calculate SHA-256 hashes, so the existing changes are hashed by the server on
+-- startup, straight after the migrations are run.
+
+--- Columns ---
+
+ALTER TABLE device_changes
+	ADD COLUMN previous_hash TEXT NULL;
+ALTER TABLE device_changes
+	ADD COLUMN hash TEXT NULL;
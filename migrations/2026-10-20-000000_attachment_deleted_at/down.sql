@@ -0,0 +1,4 @@
+--- Drop Columns ---
+
+ALTER TABLE device_attachments
+	DROP COLUMN deleted_at;
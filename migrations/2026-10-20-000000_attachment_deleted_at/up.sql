@@ -0,0 +1,15 @@
+-- Records when attachments are deleted, so that they can be purged once they've been deleted for
+-- long enough.
+-- Attachments that were already deleted are treated as having been deleted now, since there's no
+-- way to know when they actually were.
+
+--- Columns ---
+
+ALTER TABLE device_attachments
+	ADD COLUMN deleted_at TIMESTAMP NULL;
+
+--- Data ---
+
+UPDATE device_attachments
+SET deleted_at = CURRENT_TIMESTAMP
+WHERE deleted = 1;
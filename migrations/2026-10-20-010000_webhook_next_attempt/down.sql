@@ -0,0 +1,8 @@
+--- Drop Indices ---
+
+DROP INDEX webhook_outbox_next_attempt_at;
+
+--- Drop Columns ---
+
+ALTER TABLE webhook_outbox
+	DROP COLUMN next_attempt_at;
@@ -0,0 +1,13 @@
+-- Records when each outbox entry next has a delivery due, so that the dispatcher can skip the
+-- entries that are waiting to be retried without loading them.
+-- Existing entries are left to be checked on the next tick, which sets this for them.
+
+--- Columns ---
+
+ALTER TABLE webhook_outbox
+	-- Null if a delivery may be due now
+	ADD COLUMN next_attempt_at TIMESTAMP NULL;
+
+--- Indices ---
+
+CREATE INDEX webhook_outbox_next_attempt_at ON webhook_outbox (next_attempt_at);
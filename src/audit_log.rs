@@ -0,0 +1,165 @@
+//! The audit log, which records administrative changes and authentication
+//! events - everything that isn't a change to a device, which are logged in
+//! `device_changes` instead.
+
+// Uses
+use std::{borrow::Cow, net::IpAddr};
+
+use chrono::Utc;
+use diesel::{insert_into, RunQueryDsl, SqliteConnection};
+use rocket::serde::json::Value as JsonValue;
+use serde_json::to_string as to_json_string;
+
+use crate::{
+	db::{models::AuditLogEntryNew, schema},
+	error::{Context, Error},
+};
+
+/// Something that was done.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+	Login,
+	LoginFailed,
+	TokenCreate,
+	TokenRevoke,
+	LocationCreate,
+	LocationUpdate,
+	EmailUpdate,
+	NotificationPreferencesUpdate,
+	JobRun,
+	WarrantyTransitionsApply,
+	DevicePurge,
+	AttachmentPurge,
+	ChangeLogAnonymise,
+}
+
+impl AuditAction {
+	pub const ALL: [Self; 13] = [
+		Self::Login,
+		Self::LoginFailed,
+		Self::TokenCreate,
+		Self::TokenRevoke,
+		Self::LocationCreate,
+		Self::LocationUpdate,
+		Self::EmailUpdate,
+		Self::NotificationPreferencesUpdate,
+		Self::JobRun,
+		Self::WarrantyTransitionsApply,
+		Self::DevicePurge,
+		Self::AttachmentPurge,
+		Self::ChangeLogAnonymise,
+	];
+
+	/// The name the action is stored and filtered by.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Login => "login",
+			Self::LoginFailed => "login_failed",
+			Self::TokenCreate => "token_create",
+			Self::TokenRevoke => "token_revoke",
+			Self::LocationCreate => "location_create",
+			Self::LocationUpdate => "location_update",
+			Self::EmailUpdate => "email_update",
+			Self::NotificationPreferencesUpdate => "notification_preferences_update",
+			Self::JobRun => "job_run",
+			Self::WarrantyTransitionsApply => "warranty_transitions_apply",
+			Self::DevicePurge => "device_purge",
+			Self::AttachmentPurge => "attachment_purge",
+			Self::ChangeLogAnonymise => "change_log_anonymise",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|action| action.name() == name)
+	}
+}
+
+/// The thing an action was done to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum AuditTarget<'a> {
+	/// A user, if they're known. A failed login has no known user.
+	User(Option<i32>),
+	Location(i32),
+	/// A device, by its internal ID, since its device ID may not exist any
+	/// more.
+	Device(i32),
+	/// An attachment, by its internal ID.
+	Attachment(i32),
+	Job(&'a str),
+	/// Every device that an action applies to, such as the warranty
+	/// transitions.
+	Devices,
+}
+
+impl AuditTarget<'_> {
+	/// The name of the kind of target, which is stored and filtered by.
+	pub fn type_name(&self) -> &'static str {
+		match self {
+			Self::User(_) => "user",
+			Self::Location(_) => "location",
+			Self::Device(_) => "device",
+			Self::Attachment(_) => "attachment",
+			Self::Job(_) => "job",
+			Self::Devices => "devices",
+		}
+	}
+
+	fn id(&self) -> Option<String> {
+		match self {
+			Self::User(user) => user.map(|user| user.to_string()),
+			Self::Location(location) => Some(location.to_string()),
+			Self::Device(device) => Some(device.to_string()),
+			Self::Attachment(attachment) => Some(attachment.to_string()),
+			Self::Job(job) => Some((*job).to_owned()),
+			Self::Devices => None,
+		}
+	}
+}
+
+/// An entry to add to the audit log.
+#[derive(Debug)]
+pub struct AuditEvent<'a> {
+	/// The user who did it, if they're known.
+	pub actor:     Option<i32>,
+	pub action:    AuditAction,
+	pub target:    AuditTarget<'a>,
+	/// The state of the target before the action, if it had one.
+	pub before:    Option<JsonValue>,
+	/// The state of the target after the action, or any details of it.
+	pub after:     Option<JsonValue>,
+	pub client_ip: Option<IpAddr>,
+}
+
+/// Adds an entry to the audit log.
+///
+/// Where the action changes the database, this should be called in the same
+/// transaction, so that the log only ever contains actions that were actually
+/// done.
+pub fn record_audit_event(conn: &mut SqliteConnection, event: AuditEvent<'_>) -> Result<(), Error> {
+	// Uses
+	use schema::audit_log::dsl::*;
+
+	let serialise = |state: Option<JsonValue>| {
+		state
+			.map(|state| to_json_string(&state).map(Cow::from))
+			.transpose()
+			.with_context("unable to serialise the audited state to JSON")
+	};
+
+	insert_into(audit_log)
+		.values(AuditLogEntryNew {
+			timestamp:     Utc::now().naive_utc(),
+			actor_user_id: event.actor,
+			action:        Cow::from(event.action.name()),
+			target_type:   Cow::from(event.target.type_name()),
+			target_id:     event.target.id().map(Cow::from),
+			before_state:  serialise(event.before)?,
+			after_state:   serialise(event.after)?,
+			client_ip:     event.client_ip.map(|ip| Cow::from(ip.to_string())),
+		})
+		.execute(conn)
+		.with_context("unable to insert into audit_log")?;
+
+	Ok(())
+}
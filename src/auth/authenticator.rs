@@ -39,6 +39,8 @@ pub struct LdapAuthenticator {
 	pub user_identifier_attribute: String,
 	/// The LDAP attribute that stores the user display name.
 	pub display_name_attribute:    String,
+	/// The LDAP attribute that stores the user's email address, if any.
+	pub email_attribute:           Option<String>,
 }
 
 /// The return value of an LDAP authentication.
@@ -49,6 +51,9 @@ pub struct AuthenticationReturn {
 	unique_identifier:  String,
 	/// The display name for the user. Typically first & last name.
 	display_name:       String,
+	/// The user's email address, if the email attribute is configured and the
+	/// user has one.
+	email:              Option<String>,
 }
 
 impl TryFrom<&LdapSettings> for LdapAuthenticator {
@@ -81,6 +86,7 @@ impl TryFrom<&LdapSettings> for LdapAuthenticator {
 			}
 			.to_owned(),
 			display_name_attribute:    config.user_display_name_attribute.clone(),
+			email_attribute:           config.user_email_attribute.clone(),
 		})
 	}
 }
@@ -191,12 +197,22 @@ impl LdapAuthenticator {
 				}
 			};
 
+			// Fetch the user email address, which is optional
+			let email = self
+				.email_attribute
+				.as_ref()
+				.and_then(|attribute| user_entry.attrs.get(attribute))
+				.and_then(|attribute_vec| attribute_vec.first())
+				.map(|email_value| email_value.trim().to_owned())
+				.filter(|email_value| !email_value.is_empty());
+
 			// Return the result
 			if success {
 				Ok(Some(AuthenticationReturn {
 					distinguished_name: user_entry.dn,
-					unique_identifier:  username.to_owned(),
-					display_name:       display_name.trim().to_owned(),
+					unique_identifier: username.to_owned(),
+					display_name: display_name.trim().to_owned(),
+					email,
 				}))
 			} else {
 				Ok(None)
@@ -213,6 +229,7 @@ impl From<AuthenticationReturn> for UserNew {
 			source:            UserSource::Ldap,
 			unique_identifier: ret.unique_identifier,
 			display_name:      ret.display_name,
+			email:             ret.email,
 		}
 	}
 }
@@ -15,10 +15,13 @@ use rocket::{
 };
 
 use super::COOKIE_NAME;
-use crate::db::{
-	models::{User, USER},
-	schema,
-	DbConn,
+use crate::{
+	config::AppConfig,
+	db::{
+		models::{User, USER},
+		schema,
+		DbConn,
+	},
 };
 
 /// The request guard that verifies the user has a valid login token.
@@ -28,6 +31,10 @@ pub struct AuthedUser(pub User);
 /// Rocket to serve redirects on user pages that require authentication.
 pub struct AuthedUserForwarding(pub User);
 
+/// The request guard that verifies the user has a valid login token, and is
+/// one of the configured admins.
+pub struct AdminUser(pub User);
+
 /// The error type for [`AuthedUser`] failures.
 #[derive(Debug, Copy, Clone)]
 pub enum AuthedUserError {
@@ -36,6 +43,13 @@ pub enum AuthedUserError {
 	DatabaseError,
 }
 
+/// The error type for [`AdminUser`] failures.
+#[derive(Debug, Copy, Clone)]
+pub enum AdminUserError {
+	NotAuthed(AuthedUserError),
+	NotAdmin,
+}
+
 // Implementation
 #[rocket::async_trait]
 impl<'r> FromRequest<'r> for &'r AuthedUser {
@@ -74,6 +88,31 @@ impl<'r> FromRequest<'r> for &'r AuthedUserForwarding {
 	}
 }
 
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for &'r AdminUser {
+	type Error = AdminUserError;
+
+	async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let user = match request.guard::<&AuthedUser>().await {
+			Outcome::Success(user) => user,
+			Outcome::Failure((status, e)) => {
+				return Outcome::Failure((status, AdminUserError::NotAuthed(e)))
+			}
+			Outcome::Forward(f) => return Outcome::Forward(f),
+		};
+
+		let is_admin = request
+			.rocket()
+			.state::<AppConfig>()
+			.is_some_and(|config| config.admins.contains(&user.0.unique_identifier));
+		if !is_admin {
+			return Outcome::Failure((Status::Forbidden, AdminUserError::NotAdmin));
+		}
+
+		Outcome::Success(request.local_cache(|| AdminUser(user.0.clone())))
+	}
+}
+
 /// Does the actual cookie validation.
 async fn validate_cookie<T, F>(
 	request: &Request<'_>,
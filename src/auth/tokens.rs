@@ -1,10 +1,12 @@
 // Uses
-use std::borrow::Cow;
+use std::{borrow::Cow, net::IpAddr};
 
 use chrono::Utc;
 use diesel::{
+	delete,
 	dsl::exists,
 	insert_into,
+	result::OptionalExtension,
 	select,
 	Connection,
 	ExpressionMethods,
@@ -12,12 +14,17 @@ use diesel::{
 	RunQueryDsl,
 	SqliteConnection,
 };
+use rocket::serde::json::json;
 
 use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
 	auth::get_token_valid_duration,
-	db::{models::TokenNew, schema},
+	db::{
+		models::{CalendarFeedTokenNew, TokenNew},
+		schema,
+	},
 	error::{Context, Error},
-	id_gen::{gen_new_id, Base64},
+	id_gen::{gen_new_id, Base64, CrockfordBase32},
 };
 
 /// Generates a new token, inserts it into the database, and returns the new
@@ -68,3 +75,132 @@ fn generate_token_value(conn: &mut SqliteConnection) -> Result<String, Error> {
 			.with_context("unable to query the database for an existing token")
 	})
 }
+
+/// Fetches a user's calendar feed token, generating one if they don't have one
+/// yet.
+///
+/// Calendar clients can't log in, so calendar feeds are authenticated with this
+/// token in the URL instead. It doesn't grant access to anything else.
+pub fn get_calendar_feed_token(
+	conn: &mut SqliteConnection,
+	token_user_id: i32,
+	client_ip: Option<IpAddr>,
+) -> Result<String, Error> {
+	// Uses
+	use schema::calendar_feed_tokens::dsl::*;
+
+	conn.transaction::<_, Error, _>(|tc| {
+		let existing_token = calendar_feed_tokens
+			.filter(user_id.eq(token_user_id))
+			.select(value)
+			.get_result::<String>(tc)
+			.optional()
+			.with_context("unable to query the database for an existing calendar feed token")?;
+		if let Some(existing_token) = existing_token {
+			return Ok(existing_token);
+		}
+
+		create_calendar_feed_token(tc, token_user_id, false, client_ip)
+	})
+}
+
+/// Replaces a user's calendar feed token with a new one, so that any feed URLs
+/// that have been shared stop working, and returns the new token's value.
+pub fn rotate_calendar_feed_token(
+	conn: &mut SqliteConnection,
+	token_user_id: i32,
+	client_ip: Option<IpAddr>,
+) -> Result<String, Error> {
+	// Uses
+	use schema::calendar_feed_tokens::dsl::*;
+
+	conn.transaction::<_, Error, _>(|tc| {
+		let removed_count = delete(calendar_feed_tokens.filter(user_id.eq(token_user_id)))
+			.execute(tc)
+			.with_context("unable to delete the old calendar feed token")?;
+		if removed_count > 0 {
+			record_audit_event(
+				tc,
+				AuditEvent {
+					actor: Some(token_user_id),
+					action: AuditAction::TokenRevoke,
+					target: AuditTarget::User(Some(token_user_id)),
+					before: Some(json!({ "kind": "calendarFeed" })),
+					after: None,
+					client_ip,
+				},
+			)?;
+		}
+
+		create_calendar_feed_token(tc, token_user_id, true, client_ip)
+	})
+}
+
+/// Fetches the user that a calendar feed token belongs to, if the token is
+/// valid.
+pub fn load_calendar_feed_token_user(
+	conn: &mut SqliteConnection,
+	token: &str,
+) -> Result<Option<i32>, Error> {
+	// Uses
+	use schema::calendar_feed_tokens::dsl::*;
+
+	calendar_feed_tokens
+		.filter(value.eq(token))
+		.select(user_id)
+		.get_result::<i32>(conn)
+		.optional()
+		.with_context("unable to query the database for the calendar feed token")
+}
+
+/// Generates a new calendar feed token for a user who doesn't have one, and
+/// returns the new token's value.
+///
+/// This should be called in a transaction.
+fn create_calendar_feed_token(
+	conn: &mut SqliteConnection,
+	token_user_id: i32,
+	replaced: bool,
+	client_ip: Option<IpAddr>,
+) -> Result<String, Error> {
+	// Uses
+	use schema::calendar_feed_tokens::dsl::*;
+
+	let token_value = generate_calendar_feed_token_value(conn)
+		.with_context("unable to generate a new calendar feed token value")?;
+	insert_into(calendar_feed_tokens)
+		.values(CalendarFeedTokenNew {
+			user_id: token_user_id,
+			value:   Cow::from(token_value.as_str()),
+		})
+		.execute(conn)
+		.with_context("unable to insert into calendar_feed_tokens")?;
+	record_audit_event(
+		conn,
+		AuditEvent {
+			actor: Some(token_user_id),
+			action: AuditAction::TokenCreate,
+			target: AuditTarget::User(Some(token_user_id)),
+			before: None,
+			after: Some(json!({ "kind": "calendarFeed", "replaced": replaced })),
+			client_ip,
+		},
+	)?;
+
+	Ok(token_value)
+}
+
+// Calendar feed tokens end up in URLs, so they use a URL-safe character set
+fn generate_calendar_feed_token_value(conn: &mut SqliteConnection) -> Result<String, Error> {
+	const TOKEN_BITS: usize = 256;
+	const BITS_PER_CHARACTER: usize = 5;
+	const LENGTH: usize = TOKEN_BITS.div_ceil(BITS_PER_CHARACTER);
+
+	gen_new_id(CrockfordBase32, LENGTH, |new_id| {
+		use schema::calendar_feed_tokens::dsl::*;
+
+		select(exists(calendar_feed_tokens.filter(value.eq(new_id))))
+			.get_result::<bool>(conn)
+			.with_context("unable to query the database for an existing calendar feed token")
+	})
+}
@@ -1,6 +1,7 @@
 // Uses
 use diesel::{
 	insert_into,
+	update,
 	Connection,
 	ExpressionMethods,
 	OptionalExtension,
@@ -26,8 +27,15 @@ pub fn create_user_if_new(conn: &mut SqliteConnection, user_new: UserNew) -> Res
 			.optional()
 			.with_context("failed to query the database for user existence")?;
 
-		// If they already exist, simply return the found ID
+		// If they already exist, keep their email address in sync and return the found
+		// ID
 		if let Some(id_value) = existing_id {
+			if let Some(email_value) = &user_new.email {
+				update(user_info.filter(id.eq(id_value)))
+					.set(email.eq(email_value))
+					.execute(tc)
+					.with_context("unable to update the user's email address")?;
+			}
 			return Ok(id_value);
 		}
 
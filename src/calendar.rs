@@ -0,0 +1,108 @@
+//! iCalendar (ICS) generation, for feeds that calendar clients can subscribe
+//! to.
+//!
+//! Only the small subset of [RFC 5545](https://www.rfc-editor.org/rfc/rfc5545)
+//! needed for simple events is supported.
+
+// Uses
+use chrono::{NaiveDateTime, Utc};
+
+// Constants
+const MAX_LINE_LENGTH: usize = 75;
+const DATE_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// A single event in a calendar. All times are in UTC.
+#[derive(Debug, Clone)]
+pub struct CalendarEvent {
+	/// A globally-unique ID for the event, which stays the same across updates.
+	pub uid:         String,
+	pub starts_at:   NaiveDateTime,
+	pub ends_at:     NaiveDateTime,
+	pub summary:     String,
+	pub description: String,
+}
+
+/// Renders a complete calendar with the given name and events.
+pub fn render_calendar(name: &str, events: &[CalendarEvent]) -> String {
+	let now = Utc::now().naive_utc();
+
+	let mut calendar = String::new();
+	write_line(&mut calendar, "BEGIN:VCALENDAR");
+	write_line(&mut calendar, "VERSION:2.0");
+	write_line(&mut calendar, "PRODID:-//Pecan//Pecan//EN");
+	write_line(&mut calendar, "CALSCALE:GREGORIAN");
+	write_line(&mut calendar, "METHOD:PUBLISH");
+	write_line(
+		&mut calendar,
+		format!("X-WR-CALNAME:{}", escape_text(name)).as_str(),
+	);
+	for event in events {
+		write_line(&mut calendar, "BEGIN:VEVENT");
+		write_line(
+			&mut calendar,
+			format!("UID:{}", escape_text(event.uid.as_str())).as_str(),
+		);
+		write_line(
+			&mut calendar,
+			format!("DTSTAMP:{}", now.format(DATE_TIME_FORMAT)).as_str(),
+		);
+		write_line(
+			&mut calendar,
+			format!("DTSTART:{}", event.starts_at.format(DATE_TIME_FORMAT)).as_str(),
+		);
+		write_line(
+			&mut calendar,
+			format!("DTEND:{}", event.ends_at.format(DATE_TIME_FORMAT)).as_str(),
+		);
+		write_line(
+			&mut calendar,
+			format!("SUMMARY:{}", escape_text(event.summary.as_str())).as_str(),
+		);
+		if !event.description.is_empty() {
+			write_line(
+				&mut calendar,
+				format!("DESCRIPTION:{}", escape_text(event.description.as_str())).as_str(),
+			);
+		}
+		write_line(&mut calendar, "END:VEVENT");
+	}
+	write_line(&mut calendar, "END:VCALENDAR");
+
+	calendar
+}
+
+/// Writes a content line, folding it onto continuation lines if it's too long.
+///
+/// Lines are limited to 75 octets, and are never split in the middle of a
+/// multi-byte character.
+fn write_line(calendar: &mut String, line: &str) {
+	let mut line_length = 0;
+	for c in line.chars() {
+		if line_length + c.len_utf8() > MAX_LINE_LENGTH {
+			calendar.push_str("\r\n ");
+			// The leading space counts towards the length of the continuation line
+			line_length = 1;
+		}
+		calendar.push(c);
+		line_length += c.len_utf8();
+	}
+	calendar.push_str("\r\n");
+}
+
+/// Escapes a value of the `TEXT` type.
+fn escape_text(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'\\' | ';' | ',' => {
+				escaped.push('\\');
+				escaped.push(c);
+			}
+			'\n' => escaped.push_str("\\n"),
+			'\r' => {}
+			_ => escaped.push(c),
+		}
+	}
+
+	escaped
+}
@@ -1,4 +1,5 @@
 // Uses
+use lettre::message::Mailbox;
 use rocket::{
 	data::{ByteUnit, Limits, ToByteUnit},
 	figment::{
@@ -11,6 +12,11 @@ use rocket::{
 	Rocket,
 };
 
+use crate::{
+	id_gen::{CrockfordBase32, NumericAscii},
+	webhooks::WebhookEvent,
+};
+
 // Constants
 const CONFIG_FILE_NAME: &str = "pecan.toml";
 const CONFIG_FILE_ENV_OVERRIDE: &str = "PECAN_CONFIG";
@@ -18,6 +24,9 @@ const CONFIG_FILE_PROFILE_ENV_NAME: &str = "PECAN_PROFILE";
 const CONFIG_ENV_PREFIX: &str = "PECAN_";
 const RELEASE_DIST_PATH: &str = "dist";
 const DEFAULT_JSON_LIMIT: &'static dyn Fn() -> ByteUnit = &|| 5.mebibytes();
+/// The longest generated part of a device ID that's allowed, which keeps
+/// sequential IDs within the range of a `u128`.
+pub const MAX_DEVICE_ID_LENGTH: usize = 20;
 
 // Config Struct
 
@@ -27,11 +36,34 @@ const DEFAULT_JSON_LIMIT: &'static dyn Fn() -> ByteUnit = &|| 5.mebibytes();
 pub struct AppConfig {
 	/// The path to the directory to serve the front-end Svelte files from.
 	pub serve_path:          String,
+	/// The public URL that the app is served from, such as
+	/// `https://pecan.example.com`.
+	///
+	/// This is used for links that leave the app, such as the ones in label QR
+	/// codes.
+	pub public_url:          Option<String>,
 	/// How many days a login token is valid for, before a user has to log in
 	/// again.
 	pub token_valid_days:    u32,
+	/// The usernames of the users who can use the admin routes, such as running
+	/// jobs and purging deleted data.
+	pub admins:              Vec<String>,
 	/// The maximum attachment size allowed on upload.
 	pub max_attachment_size: ByteUnit,
+	/// The format of new device IDs.
+	pub device_ids:          DeviceIdSettings,
+	/// Settings for server-side label generation.
+	pub labels:              LabelSettings,
+	/// Settings for device lifecycle tracking.
+	pub lifecycle:           LifecycleSettings,
+	/// Settings for how long deleted data is kept before it's purged.
+	pub retention:           RetentionSettings,
+	/// Settings for the background jobs that run on a schedule.
+	pub jobs:                JobSettings,
+	/// Settings for the webhooks that are notified of device changes.
+	pub webhooks:            WebhookSettings,
+	/// Settings for sending notifications by email.
+	pub email:               EmailSettings,
 	/// Settings for LDAP-based authentication.
 	pub ldap:                Option<LdapSettings>,
 }
@@ -45,13 +77,513 @@ impl Default for AppConfig {
 				RELEASE_DIST_PATH
 			}
 			.to_owned(),
+			public_url:          None,
 			token_valid_days:    7,
+			admins:              Vec::new(),
 			max_attachment_size: 3.mebibytes(),
+			device_ids:          DeviceIdSettings::default(),
+			labels:              LabelSettings::default(),
+			lifecycle:           LifecycleSettings::default(),
+			retention:           RetentionSettings::default(),
+			jobs:                JobSettings::default(),
+			webhooks:            WebhookSettings::default(),
+			email:               EmailSettings::default(),
 			ldap:                None,
 		}
 	}
 }
 
+/// The format of new device IDs.
+///
+/// Changing these only affects new devices - existing devices keep their IDs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct DeviceIdSettings {
+	/// The length of the generated part of the ID, not including the prefix or
+	/// the check digit.
+	pub length:      usize,
+	/// The characters that the generated part of the ID is made up of.
+	pub alphabet:    DeviceIdAlphabet,
+	/// A fixed prefix to start every ID with, such as `PC-`.
+	pub prefix:      String,
+	/// Whether to number IDs sequentially, instead of generating them randomly.
+	pub sequential:  bool,
+	/// The check digit to add to the end of the ID, if any.
+	pub check_digit: CheckDigit,
+}
+
+impl Default for DeviceIdSettings {
+	fn default() -> Self {
+		Self {
+			length:      6,
+			alphabet:    DeviceIdAlphabet::Numeric,
+			prefix:      String::new(),
+			sequential:  false,
+			check_digit: CheckDigit::None,
+		}
+	}
+}
+
+impl DeviceIdSettings {
+	/// The total length of an ID, including the prefix and the check digit.
+	pub fn total_length(&self) -> usize {
+		let check_digit_length = match self.check_digit {
+			CheckDigit::None => 0,
+			CheckDigit::Luhn | CheckDigit::Damm => 1,
+		};
+
+		self.prefix.len() + self.length + check_digit_length
+	}
+}
+
+/// The characters that the generated part of a device ID is made up of.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum DeviceIdAlphabet {
+	/// The digits 0-9.
+	#[serde(rename = "numeric")]
+	Numeric,
+	/// Digits and uppercase letters, leaving out I, L, O, and U so they can't
+	/// be mistaken for other characters.
+	#[serde(rename = "crockford-base32")]
+	CrockfordBase32,
+}
+
+impl DeviceIdAlphabet {
+	/// The characters in the alphabet, in ascending order.
+	pub fn charset(self) -> &'static [u8] {
+		match self {
+			Self::Numeric => NumericAscii::CHARSET,
+			Self::CrockfordBase32 => CrockfordBase32::CHARSET,
+		}
+	}
+}
+
+/// The algorithm used to calculate the check digit at the end of a device ID.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum CheckDigit {
+	#[serde(rename = "none")]
+	None,
+	/// The Luhn algorithm, or Luhn mod N for non-numeric alphabets.
+	#[serde(rename = "luhn")]
+	Luhn,
+	/// The Damm algorithm. This only works with the numeric alphabet.
+	#[serde(rename = "damm")]
+	Damm,
+}
+
+/// Settings for server-side label generation.
+///
+/// All measurements are in millimetres unless otherwise noted.
+#[derive(Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LabelSettings {
+	/// The width of a single label.
+	pub width:             f32,
+	/// The height of a single label.
+	pub height:            f32,
+	/// The blank space to leave around the edges of a label.
+	pub margin:            f32,
+	/// The type of code to print on labels.
+	pub code:              LabelCode,
+	/// The height of the barcode. This doesn't apply to QR codes, since they're
+	/// always square.
+	pub barcode_height:    f32,
+	/// The font size of the label text, in points.
+	pub font_size:         f32,
+	/// Whether to prefix each value on the label with its column name.
+	pub show_column_names: bool,
+	/// The print resolution of the Zebra printer, in dots per inch. This only
+	/// affects ZPL output.
+	///
+	/// Zebra printers typically use 203, 300, or 600 DPI.
+	pub zpl_dpi:           u32,
+	/// The sheet formats that labels can be laid out on when printing.
+	///
+	/// The first one is used if none is specified.
+	pub sheets:            Vec<LabelSheetSettings>,
+}
+
+impl Default for LabelSettings {
+	fn default() -> Self {
+		// A 2" x 1" label, which is the most common size for Zebra printers
+		Self {
+			width:             50.8,
+			height:            25.4,
+			margin:            2.0,
+			code:              LabelCode::Barcode,
+			barcode_height:    8.0,
+			font_size:         6.0,
+			show_column_names: true,
+			zpl_dpi:           203,
+			sheets:            vec![LabelSheetSettings::default()],
+		}
+	}
+}
+
+impl LabelSettings {
+	/// Finds a sheet format by name, or the default one if `name` is `None`.
+	pub fn find_sheet(&self, name: Option<&str>) -> Option<&LabelSheetSettings> {
+		match name {
+			Some(name) => self.sheets.iter().find(|sheet| sheet.name == name),
+			None => self.sheets.first(),
+		}
+	}
+}
+
+/// The type of code to print on labels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum LabelCode {
+	/// A Code 128 barcode of the device ID.
+	#[serde(rename = "barcode")]
+	Barcode,
+	/// A QR code linking to the device's page in the app, if `public_url` is
+	/// set. Otherwise, it contains just the device ID.
+	#[serde(rename = "qr")]
+	QrCode,
+}
+
+/// A sheet format that labels are laid out on, such as a grid of labels on a
+/// page or a continuous roll.
+///
+/// All measurements are in millimetres.
+#[derive(Debug, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LabelSheetSettings {
+	/// The name used to select the sheet format.
+	pub name:           String,
+	/// The number of labels across.
+	pub columns:        u32,
+	/// The number of labels down. Continuous rolls should use 1.
+	///
+	/// This is ignored for ZPL output, since the printer feeds labels one row
+	/// at a time.
+	pub rows:           u32,
+	/// The width of the page. If unset, the page is sized to fit the labels
+	/// exactly.
+	#[serde(default)]
+	pub page_width:     Option<f32>,
+	/// The height of the page. If unset, the page is sized to fit the labels
+	/// exactly.
+	#[serde(default)]
+	pub page_height:    Option<f32>,
+	/// The distance from the left edge of the page to the first column.
+	#[serde(default)]
+	pub left_offset:    f32,
+	/// The distance from the top edge of the page to the first row.
+	#[serde(default)]
+	pub top_offset:     f32,
+	/// The space between each column.
+	#[serde(default)]
+	pub horizontal_gap: f32,
+	/// The space between each row.
+	#[serde(default)]
+	pub vertical_gap:   f32,
+}
+
+impl Default for LabelSheetSettings {
+	fn default() -> Self {
+		// A continuous roll with one label across
+		Self {
+			name:           "roll".to_owned(),
+			columns:        1,
+			rows:           1,
+			page_width:     None,
+			page_height:    None,
+			left_offset:    0.0,
+			top_offset:     0.0,
+			horizontal_gap: 0.0,
+			vertical_gap:   0.0,
+		}
+	}
+}
+
+impl LabelSheetSettings {
+	/// The number of labels that fit on a single page.
+	pub fn labels_per_page(&self) -> usize {
+		(self.columns * self.rows) as usize
+	}
+}
+
+/// Settings for device lifecycle tracking.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct LifecycleSettings {
+	/// How many days ahead to look for expiring warranties, if a report doesn't
+	/// specify.
+	pub expiry_warning_days:     u32,
+	/// A change to make to a device's column data automatically once its
+	/// warranty has expired, such as setting its status to `Out of warranty`.
+	pub warranty_expired_status: Option<ColumnValueSettings>,
+}
+
+impl Default for LifecycleSettings {
+	fn default() -> Self {
+		Self {
+			expiry_warning_days:     30,
+			warranty_expired_status: None,
+		}
+	}
+}
+
+/// A value to set a column to.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ColumnValueSettings {
+	/// The name of the column.
+	pub column: String,
+	/// The value to set it to. If the column only allows its possible values,
+	/// this must be one of them.
+	pub value:  String,
+}
+
+/// Settings for how long deleted data is kept before it's purged.
+///
+/// These are applied by the `retention` job.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct RetentionSettings {
+	/// How many days to keep deleted attachments for before purging them. If
+	/// this isn't set, they're kept until they're purged manually.
+	pub deleted_attachment_days: Option<u32>,
+	/// Whether to remove the references to users who have been removed from
+	/// the system from the device change log.
+	pub anonymise_removed_users: bool,
+}
+
+/// Settings for the background jobs that run on a schedule.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct JobSettings {
+	/// Whether to run jobs on a schedule at all. Jobs can still be run manually
+	/// if this is off.
+	pub enabled:          bool,
+	/// Removes login tokens that have expired or been invalidated.
+	pub token_cleanup:    JobSchedule,
+	/// Checks for loans that are past their due date.
+	pub overdue_loans:    JobSchedule,
+	/// Makes the configured change to devices with expired warranties.
+	pub warranty_expiry:  JobSchedule,
+	/// Checks the database for corruption, broken references, and tampering
+	/// with the device change log.
+	pub integrity_check:  JobSchedule,
+	/// Generates the warranty and end-of-life report.
+	pub lifecycle_report: JobSchedule,
+	/// Purges deleted data according to the retention settings.
+	pub retention:        JobSchedule,
+}
+
+impl Default for JobSettings {
+	fn default() -> Self {
+		Self {
+			enabled:          true,
+			token_cleanup:    JobSchedule::every(60),
+			overdue_loans:    JobSchedule::every(60),
+			warranty_expiry:  JobSchedule::every(24 * 60),
+			integrity_check:  JobSchedule::every(24 * 60),
+			lifecycle_report: JobSchedule::every(24 * 60),
+			retention:        JobSchedule::every(24 * 60),
+		}
+	}
+}
+
+/// How often a background job runs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct JobSchedule {
+	/// Whether the job runs on a schedule.
+	pub enabled:          bool,
+	/// The number of minutes between runs.
+	pub interval_minutes: u32,
+}
+
+impl JobSchedule {
+	const fn every(interval_minutes: u32) -> Self {
+		Self {
+			enabled: true,
+			interval_minutes,
+		}
+	}
+}
+
+/// Settings for the webhooks that are notified of device changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct WebhookSettings {
+	/// The number of times to try sending a change to a webhook before giving
+	/// up on it.
+	pub max_attempts:        u32,
+	/// The number of seconds to wait before retrying a failed delivery. This
+	/// doubles after each failed attempt.
+	pub retry_delay_seconds: u32,
+	/// The number of seconds to wait for a webhook to respond.
+	pub timeout_seconds:     u32,
+	/// The webhooks to send changes to.
+	pub endpoints:           Vec<WebhookEndpoint>,
+}
+
+impl Default for WebhookSettings {
+	fn default() -> Self {
+		Self {
+			max_attempts:        8,
+			retry_delay_seconds: 30,
+			timeout_seconds:     10,
+			endpoints:           Vec::new(),
+		}
+	}
+}
+
+/// A webhook that is sent device changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct WebhookEndpoint {
+	/// The name of the webhook, which must be unique. Deliveries are logged
+	/// under it.
+	pub name:   String,
+	/// The URL to `POST` changes to.
+	pub url:    String,
+	/// The secret used to sign the request bodies, so that the receiver can
+	/// verify they came from this server.
+	pub secret: String,
+	/// The events to send. If this is empty, every event is sent.
+	#[serde(default)]
+	pub events: Vec<WebhookEvent>,
+}
+
+impl WebhookEndpoint {
+	/// Whether the webhook should be sent the given event.
+	pub fn receives(&self, event: WebhookEvent) -> bool {
+		self.events.is_empty() || self.events.contains(&event)
+	}
+}
+
+/// Settings for sending notifications by email.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EmailSettings {
+	/// Whether to send emails at all. Notifications are still queued if this is
+	/// off, but they're skipped instead of sent.
+	pub enabled:             bool,
+	/// The SMTP server to send emails through.
+	pub smtp_host:           String,
+	/// The port of the SMTP server.
+	pub smtp_port:           u16,
+	/// How the connection to the SMTP server is secured.
+	pub smtp_security:       SmtpSecurity,
+	/// The username to log in to the SMTP server with, if it needs one.
+	pub smtp_username:       Option<String>,
+	/// The password to log in to the SMTP server with, if it needs one.
+	pub smtp_password:       Option<String>,
+	/// The address emails are sent from, such as `Pecan <pecan@example.com>`.
+	pub from_address:        String,
+	/// The number of times to try sending an email before giving up on it.
+	pub max_attempts:        u32,
+	/// The number of seconds to wait before retrying a failed email. This
+	/// doubles after each failed attempt.
+	pub retry_delay_seconds: u32,
+	/// The templates for each kind of email.
+	pub templates:           EmailTemplates,
+}
+
+impl Default for EmailSettings {
+	fn default() -> Self {
+		Self {
+			enabled:             false,
+			smtp_host:           "localhost".to_owned(),
+			smtp_port:           587,
+			smtp_security:       SmtpSecurity::StartTls,
+			smtp_username:       None,
+			smtp_password:       None,
+			from_address:        "Pecan <pecan@localhost>".to_owned(),
+			max_attempts:        5,
+			retry_delay_seconds: 60,
+			templates:           EmailTemplates::default(),
+		}
+	}
+}
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub enum SmtpSecurity {
+	/// A plain, unencrypted connection. This should only be used with a local
+	/// server, such as a test sink.
+	#[serde(rename = "none")]
+	None,
+	/// A plain connection that's upgraded with `STARTTLS`.
+	#[serde(rename = "starttls")]
+	StartTls,
+	/// A TLS connection from the start.
+	#[serde(rename = "tls")]
+	Tls,
+}
+
+/// The templates for each kind of email.
+///
+/// Placeholders like `{device_id}` are replaced with the details of the
+/// notification, and `{user}` and `{link}` are available in all of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EmailTemplates {
+	/// Sent to a borrower when their loan is overdue. Has `{device_id}` and
+	/// `{due_at}`.
+	pub loan_overdue:      EmailTemplate,
+	/// Sent when a device's warranty is about to expire. Has `{device_id}`,
+	/// `{location}`, and `{warranty_ends_on}`.
+	pub warranty_expiring: EmailTemplate,
+	/// Sent to a borrower when someone else changes the device they have. Has
+	/// `{device_id}`, `{changed_by}`, and `{changed}`.
+	pub device_changed:    EmailTemplate,
+	/// Sent when a user asks for a test email.
+	pub test:              EmailTemplate,
+}
+
+impl Default for EmailTemplates {
+	fn default() -> Self {
+		Self {
+			loan_overdue:      EmailTemplate::new(
+				"{device_id} is overdue",
+				"Hi {user},\n\nThe device {device_id} that you borrowed was due back at {due_at}. \
+				 Please return it as soon as you can.\n\n{link}\n",
+			),
+			warranty_expiring: EmailTemplate::new(
+				"The warranty for {device_id} is expiring",
+				"Hi {user},\n\nThe warranty for {device_id} in {location} ends on \
+				 {warranty_ends_on}.\n\n{link}\n",
+			),
+			device_changed:    EmailTemplate::new(
+				"{device_id} was changed",
+				"Hi {user},\n\n{changed_by} changed the {changed} of {device_id}, which you have \
+				 on loan.\n\n{link}\n",
+			),
+			test:              EmailTemplate::new(
+				"Test email from Pecan",
+				"Hi {user},\n\nThis is a test email. If you can read it, email notifications are \
+				 working.\n",
+			),
+		}
+	}
+}
+
+/// The subject and body of an email.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct EmailTemplate {
+	pub subject: String,
+	pub body:    String,
+}
+
+impl EmailTemplate {
+	fn new(subject: &str, body: &str) -> Self {
+		Self {
+			subject: subject.to_owned(),
+			body:    body.to_owned(),
+		}
+	}
+}
+
 /// Settings for LDAP-based authentication.
 #[derive(Debug, Serialize, Deserialize)]
 #[non_exhaustive]
@@ -90,6 +622,13 @@ pub struct LdapSettings {
 	/// The LDAP attribute that stores the user display name. (first & last
 	/// name)
 	pub user_display_name_attribute: String,
+	/// The LDAP attribute that stores the user's email address, such as
+	/// `mail`.
+	///
+	/// If this is set, users' email addresses are kept in sync with LDAP, and
+	/// can't be changed in the app.
+	#[serde(default)]
+	pub user_email_attribute:        Option<String>,
 }
 
 /// The type of LDAP server that's being connected-to.
@@ -176,5 +715,151 @@ pub async fn validate_config(rocket: Rocket<Build>) -> Result<Rocket<Build>, Roc
 		return Err(rocket);
 	}
 
+	if let Ok(Some(public_url)) = rocket
+		.figment()
+		.extract_inner::<Option<String>>("public_url")
+	{
+		if !public_url.starts_with("http://") && !public_url.starts_with("https://") {
+			eprintln!("public_url must be an HTTP or HTTPS URL");
+			return Err(rocket);
+		}
+	}
+
+	let device_id_settings = rocket
+		.figment()
+		.extract_inner::<DeviceIdSettings>("device_ids")
+		.expect("figment ensures the value is present");
+	if device_id_settings.length < 1 || device_id_settings.length > MAX_DEVICE_ID_LENGTH {
+		eprintln!("device_ids.length must be between 1 and {MAX_DEVICE_ID_LENGTH}");
+		return Err(rocket);
+	}
+	if !device_id_settings
+		.prefix
+		.bytes()
+		.all(|c| c.is_ascii_alphanumeric() || c == b'-')
+	{
+		eprintln!("device_ids.prefix may only contain ASCII letters, digits, and hyphens");
+		return Err(rocket);
+	}
+	if device_id_settings.check_digit == CheckDigit::Damm
+		&& device_id_settings.alphabet != DeviceIdAlphabet::Numeric
+	{
+		eprintln!("device_ids.check_digit can only be \"damm\" with the \"numeric\" alphabet");
+		return Err(rocket);
+	}
+
+	let job_settings = rocket
+		.figment()
+		.extract_inner::<JobSettings>("jobs")
+		.expect("figment ensures the value is present");
+	if [
+		&job_settings.token_cleanup,
+		&job_settings.overdue_loans,
+		&job_settings.warranty_expiry,
+		&job_settings.integrity_check,
+		&job_settings.lifecycle_report,
+		&job_settings.retention,
+	]
+	.iter()
+	.any(|schedule| schedule.interval_minutes < 1)
+	{
+		eprintln!("jobs.*.interval_minutes must be a positive value");
+		return Err(rocket);
+	}
+
+	let retention_settings = rocket
+		.figment()
+		.extract_inner::<RetentionSettings>("retention")
+		.expect("figment ensures the value is present");
+	if retention_settings.deleted_attachment_days == Some(0) {
+		eprintln!("retention.deleted_attachment_days must be a positive value");
+		return Err(rocket);
+	}
+
+	let webhook_settings = rocket
+		.figment()
+		.extract_inner::<WebhookSettings>("webhooks")
+		.expect("figment ensures the value is present");
+	if webhook_settings.max_attempts < 1 || webhook_settings.timeout_seconds < 1 {
+		eprintln!("webhooks.max_attempts and webhooks.timeout_seconds must be positive values");
+		return Err(rocket);
+	}
+	for (i, endpoint) in webhook_settings.endpoints.iter().enumerate() {
+		if endpoint.name.is_empty() || endpoint.secret.is_empty() {
+			eprintln!("webhooks.endpoints.name and webhooks.endpoints.secret must not be empty");
+			return Err(rocket);
+		}
+		if !endpoint.url.starts_with("http://") && !endpoint.url.starts_with("https://") {
+			eprintln!("webhooks.endpoints.url must be an HTTP or HTTPS URL");
+			return Err(rocket);
+		}
+		if webhook_settings.endpoints[..i]
+			.iter()
+			.any(|other| other.name == endpoint.name)
+		{
+			eprintln!("webhooks.endpoints names must be unique");
+			return Err(rocket);
+		}
+	}
+
+	let email_settings = rocket
+		.figment()
+		.extract_inner::<EmailSettings>("email")
+		.expect("figment ensures the value is present");
+	if email_settings.enabled {
+		if email_settings.smtp_host.is_empty() {
+			eprintln!("email.smtp_host must not be empty");
+			return Err(rocket);
+		}
+		if email_settings.from_address.parse::<Mailbox>().is_err() {
+			eprintln!("email.from_address must be a valid email address");
+			return Err(rocket);
+		}
+		if email_settings.smtp_username.is_some() != email_settings.smtp_password.is_some() {
+			eprintln!("email.smtp_username and email.smtp_password must be set together");
+			return Err(rocket);
+		}
+	}
+	if email_settings.max_attempts < 1 {
+		eprintln!("email.max_attempts must be a positive value");
+		return Err(rocket);
+	}
+
+	let label_settings = rocket
+		.figment()
+		.extract_inner::<LabelSettings>("labels")
+		.expect("figment ensures the value is present");
+	if label_settings.width <= 2.0 * label_settings.margin
+		|| label_settings.height <= 2.0 * label_settings.margin
+	{
+		eprintln!("labels.width and labels.height must be larger than the margins");
+		return Err(rocket);
+	}
+	if label_settings.barcode_height <= 0.0 || label_settings.font_size <= 0.0 {
+		eprintln!("labels.barcode_height and labels.font_size must be positive values");
+		return Err(rocket);
+	}
+	if label_settings.zpl_dpi < 1 {
+		eprintln!("labels.zpl_dpi must be a positive value");
+		return Err(rocket);
+	}
+	if label_settings.sheets.is_empty() {
+		eprintln!("labels.sheets must contain at least one sheet format");
+		return Err(rocket);
+	}
+	for (i, sheet) in label_settings.sheets.iter().enumerate() {
+		if sheet.columns < 1 || sheet.rows < 1 {
+			eprintln!("labels.sheets.columns and labels.sheets.rows must be positive values");
+			return Err(rocket);
+		}
+		if label_settings.sheets[..i]
+			.iter()
+			.any(|other| other.name == sheet.name)
+		{
+			eprintln!("labels.sheets names must be unique");
+			return Err(rocket);
+		}
+	}
+
 	Ok(rocket)
 }
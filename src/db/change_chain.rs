@@ -0,0 +1,487 @@
+//! The hash chain over `device_changes`, which makes the change log
+//! tamper-evident.
+//!
+//! Each change stores the hash of the change before it, and a hash of its own
+//! content together with that previous hash. Editing a change breaks its own
+//! hash, and deleting one breaks the link from the change after it.
+//!
+//! Since anyone who can write to the database can also recompute the whole
+//! chain, the latest hash should be recorded somewhere else from time to time,
+//! so that it can be compared against later.
+
+// Uses
+use std::fmt::Write;
+
+use diesel::{
+	dsl::exists,
+	select,
+	update,
+	Connection,
+	ExpressionMethods,
+	OptionalExtension,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::serde::json::json;
+use serde_json::to_string as to_json_string;
+use sha2::{Digest, Sha256};
+
+use super::{models::DeviceChange, schema};
+use crate::error::{Context, Error, InternalError, UserError};
+
+// Constants
+/// The number of changes to load at a time while walking the chain.
+const CHAIN_BATCH_SIZE: i64 = 1000;
+
+// Type Definitions
+/// The result of verifying the hash chain.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangeChainVerification {
+	/// Whether every link in the chain is intact.
+	pub valid:             bool,
+	/// The number of changes that were verified before the first broken link.
+	pub checked_count:     usize,
+	/// The hash of the last change that was verified, which can be recorded
+	/// elsewhere to detect the chain being rewritten or cut short later.
+	pub head_hash:         Option<String>,
+	pub first_broken_link: Option<BrokenChangeLink>,
+}
+
+/// The first change at which the hash chain doesn't hold.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BrokenChangeLink {
+	pub change_id: i32,
+	pub reason:    BrokenLinkReason,
+}
+
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BrokenLinkReason {
+	/// The change has no hash at all.
+	MissingHash,
+	/// The change's previous hash doesn't match the hash of the change before
+	/// it, so a change in between was deleted or the chain was rewritten.
+	PreviousHashMismatch,
+	/// The change's hash doesn't match its content, so it was edited.
+	HashMismatch,
+}
+
+/// Calculates the hash of a change, chained to the hash of the change before
+/// it.
+///
+/// The change's own `previous_hash` and `hash` are ignored.
+pub fn hash_change(previous_hash: Option<&str>, device_change: &DeviceChange<'_>) -> String {
+	// Serialising the fields as a JSON array keeps their boundaries unambiguous
+	let content = to_json_string(&json!([
+		previous_hash,
+		device_change.id,
+		device_change.device_key_info_id,
+		device_change.timestamp,
+		device_change.done_automatically,
+		device_change.user_id,
+		device_change.change,
+		device_change.batch_id,
+	]))
+	.expect("serialising a JSON value can't fail");
+
+	let mut hash = String::with_capacity(64);
+	for byte in Sha256::digest(content.as_bytes()) {
+		write!(hash, "{byte:02x}").expect("writing to a `String` can't fail");
+	}
+	hash
+}
+
+/// Chains a newly-inserted change to the one before it, filling in its hashes.
+///
+/// This has to be called in the same transaction as the change is inserted
+/// in, so that no other change can be inserted in between.
+pub fn chain_change(conn: &mut SqliteConnection, change_id: i32) -> Result<(), Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	// Only the very first change has no previous hash, since a change before
+	// it without a hash means the chain is broken
+	let previous_hash_value = match device_changes
+		.filter(id.lt(change_id))
+		.order_by(id.desc())
+		.select(hash)
+		.first::<Option<String>>(conn)
+		.optional()
+		.with_context("unable to load the hash of the previous change")?
+	{
+		Some(Some(previous_hash_value)) => Some(previous_hash_value),
+		Some(None) => {
+			return Err(InternalError::Assertion("the previous change has no hash").into());
+		}
+		None => None,
+	};
+	let device_change = device_changes
+		.find(change_id)
+		.get_result::<DeviceChange<'_>>(conn)
+		.with_context("unable to load the new change")?;
+
+	let hash_value = hash_change(previous_hash_value.as_deref(), &device_change);
+	update(device_changes.find(change_id))
+		.set((previous_hash.eq(previous_hash_value), hash.eq(hash_value)))
+		.execute(conn)
+		.with_context("unable to save the hash of the new change")?;
+
+	Ok(())
+}
+
+/// Hashes the changes that were logged before the hash chain existed.
+///
+/// This only does anything if no change has been hashed yet, so that a change
+/// that has had its hash removed can't be quietly re-hashed later.
+pub fn backfill_change_hashes(conn: &mut SqliteConnection) -> Result<usize, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	conn.transaction(|tc| {
+		let already_hashed = select(exists(device_changes.filter(hash.is_not_null())))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for hashed changes")?;
+		if already_hashed {
+			return Ok(0);
+		}
+
+		let (hashed_count, _) = hash_changes_after(tc, 0, None)?;
+
+		Ok(hashed_count)
+	})
+}
+
+/// Ensures that the hash chain is intact before it's deliberately rewritten,
+/// so that a rewrite can't cover up earlier tampering, returning the latest
+/// hash from before the rewrite.
+pub fn ensure_change_chain_intact(conn: &mut SqliteConnection) -> Result<Option<String>, Error> {
+	let verification = verify_change_chain(conn)?;
+	if verification.valid {
+		Ok(verification.head_hash)
+	} else {
+		Err(UserError::Conflict(
+			"The device change log's hash chain is broken, so it can't be rewritten.",
+			json!(verification),
+		)
+		.into())
+	}
+}
+
+/// Re-hashes the chain from a change onwards, after changes have been
+/// deliberately removed or edited, returning the new latest hash.
+///
+/// The change doesn't have to exist any more. If there's no change before it,
+/// the first change from it onwards keeps its previous hash, since the changes
+/// before it may have been purged.
+pub fn rehash_change_chain_from(
+	conn: &mut SqliteConnection,
+	from_change_id: i32,
+) -> Result<Option<String>, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	let previous_hash_value = match device_changes
+		.filter(id.lt(from_change_id))
+		.order_by(id.desc())
+		.select(hash)
+		.first::<Option<String>>(conn)
+		.optional()
+		.with_context("unable to load the hash of the previous change")?
+	{
+		Some(previous_hash_value) => previous_hash_value,
+		None => device_changes
+			.filter(id.ge(from_change_id))
+			.order_by(id)
+			.select(previous_hash)
+			.first::<Option<String>>(conn)
+			.optional()
+			.with_context("unable to load the first previous hash")?
+			.flatten(),
+	};
+
+	let (_, head_hash) = hash_changes_after(conn, from_change_id - 1, previous_hash_value)?;
+	Ok(head_hash)
+}
+
+/// Hashes every change after `after_change_id` in order, starting from
+/// `previous_hash_value`, returning the number of changes hashed and the last
+/// hash.
+fn hash_changes_after(
+	conn: &mut SqliteConnection,
+	mut after_change_id: i32,
+	mut previous_hash_value: Option<String>,
+) -> Result<(usize, Option<String>), Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	let mut hashed_count = 0;
+	loop {
+		let change_batch = device_changes
+			.filter(id.gt(after_change_id))
+			.order_by(id)
+			.limit(CHAIN_BATCH_SIZE)
+			.load::<DeviceChange<'_>>(conn)
+			.with_context("unable to load the changes to hash")?;
+		let Some(last_change) = change_batch.last() else {
+			break;
+		};
+		after_change_id = last_change.id;
+
+		for device_change in &change_batch {
+			let hash_value = hash_change(previous_hash_value.as_deref(), device_change);
+			update(device_changes.find(device_change.id))
+				.set((previous_hash.eq(&previous_hash_value), hash.eq(&hash_value)))
+				.execute(conn)
+				.with_context("unable to save the hash of a change")?;
+			previous_hash_value = Some(hash_value);
+			hashed_count += 1;
+		}
+	}
+
+	Ok((hashed_count, previous_hash_value))
+}
+
+/// Walks the whole hash chain, stopping at the first broken link.
+///
+/// The first change in the table is trusted to have the right previous hash,
+/// since the changes before it may have been purged.
+pub fn verify_change_chain(conn: &mut SqliteConnection) -> Result<ChangeChainVerification, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	let mut checked_count = 0;
+	let mut head_hash = None::<String>;
+	let mut after_change_id = 0;
+	loop {
+		let change_batch = device_changes
+			.filter(id.gt(after_change_id))
+			.order_by(id)
+			.limit(CHAIN_BATCH_SIZE)
+			.load::<DeviceChange<'_>>(conn)
+			.with_context("unable to load the changes to verify")?;
+		let Some(last_change) = change_batch.last() else {
+			break;
+		};
+		after_change_id = last_change.id;
+
+		for device_change in &change_batch {
+			let reason = if let Some(hash_value) = &device_change.hash {
+				let previous_hash_value = device_change.previous_hash.as_deref();
+				if checked_count > 0 && previous_hash_value != head_hash.as_deref() {
+					Some(BrokenLinkReason::PreviousHashMismatch)
+				} else if hash_change(previous_hash_value, device_change) != *hash_value {
+					Some(BrokenLinkReason::HashMismatch)
+				} else {
+					None
+				}
+			} else {
+				Some(BrokenLinkReason::MissingHash)
+			};
+			if let Some(reason) = reason {
+				return Ok(ChangeChainVerification {
+					valid: false,
+					checked_count,
+					head_hash,
+					first_broken_link: Some(BrokenChangeLink {
+						change_id: device_change.id,
+						reason,
+					}),
+				});
+			}
+
+			head_hash = device_change.hash.as_deref().map(str::to_owned);
+			checked_count += 1;
+		}
+	}
+
+	Ok(ChangeChainVerification {
+		valid: true,
+		checked_count,
+		head_hash,
+		first_broken_link: None,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use std::borrow::Cow;
+
+	use chrono::NaiveDate;
+	use diesel::{delete, insert_into, sql_query};
+	use diesel_migrations::MigrationHarness;
+
+	use super::*;
+	use crate::db::{models::DeviceChangeNew, util::fetch_new_rowid_on, MIGRATIONS};
+
+	/// Sets up an in-memory database with `count` chained changes, returning
+	/// it along with the changes' IDs.
+	fn chained_changes(count: usize) -> (SqliteConnection, Vec<i32>) {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		// Foreign key constraints are left off, so that the changes don't need
+		// devices or users to exist
+		let mut conn = SqliteConnection::establish(":memory:").unwrap();
+		sql_query("PRAGMA foreign_keys = OFF;")
+			.execute(&mut conn)
+			.unwrap();
+		conn.run_pending_migrations(MIGRATIONS).unwrap();
+
+		let change_ids = (0..count)
+			.map(|i| {
+				insert_into(device_changes)
+					.values(DeviceChangeNew {
+						device_key_info_id: 1,
+						timestamp:          NaiveDate::from_ymd_opt(2026, 10, 19)
+							.unwrap()
+							.and_hms_opt(12, 0, i as u32)
+							.unwrap(),
+						done_automatically: false,
+						user_id:            Some(1),
+						change:             Cow::from(format!("{{\"change\":{i}}}")),
+						batch_id:           None,
+					})
+					.execute(&mut conn)
+					.unwrap();
+				let change_id = fetch_new_rowid_on(&mut conn).unwrap();
+				chain_change(&mut conn, change_id).unwrap();
+				change_id
+			})
+			.collect();
+
+		(conn, change_ids)
+	}
+
+	fn load_change(conn: &mut SqliteConnection, change_id: i32) -> DeviceChange<'static> {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		device_changes
+			.find(change_id)
+			.get_result::<DeviceChange<'_>>(conn)
+			.unwrap()
+	}
+
+	#[test]
+	fn hash_change_depends_on_previous_hash_and_content() {
+		let (mut conn, change_ids) = chained_changes(1);
+		let mut device_change = load_change(&mut conn, change_ids[0]);
+
+		let hash_value = hash_change(None, &device_change);
+		assert_eq!(hash_value.len(), 64);
+		assert_eq!(hash_value, hash_change(None, &device_change));
+		assert_ne!(hash_value, hash_change(Some("previous"), &device_change));
+
+		device_change.change = Cow::from("{\"change\":\"edited\"}");
+		assert_ne!(hash_value, hash_change(None, &device_change));
+	}
+
+	#[test]
+	fn intact_chain_verifies() {
+		let (mut conn, change_ids) = chained_changes(3);
+
+		let verification = verify_change_chain(&mut conn).unwrap();
+		assert!(verification.valid);
+		assert_eq!(verification.checked_count, 3);
+		assert_eq!(
+			verification.head_hash,
+			load_change(&mut conn, change_ids[2])
+				.hash
+				.map(Cow::into_owned)
+		);
+		assert!(verification.first_broken_link.is_none());
+	}
+
+	#[test]
+	fn edited_change_is_a_hash_mismatch() {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		let (mut conn, change_ids) = chained_changes(3);
+		update(device_changes.find(change_ids[1]))
+			.set(change.eq("{\"change\":\"edited\"}"))
+			.execute(&mut conn)
+			.unwrap();
+
+		let verification = verify_change_chain(&mut conn).unwrap();
+		assert!(!verification.valid);
+		assert_eq!(verification.checked_count, 1);
+		let broken_link = verification.first_broken_link.unwrap();
+		assert_eq!(broken_link.change_id, change_ids[1]);
+		assert_eq!(broken_link.reason, BrokenLinkReason::HashMismatch);
+	}
+
+	#[test]
+	fn deleted_middle_change_is_a_previous_hash_mismatch() {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		let (mut conn, change_ids) = chained_changes(3);
+		delete(device_changes.find(change_ids[1]))
+			.execute(&mut conn)
+			.unwrap();
+
+		let verification = verify_change_chain(&mut conn).unwrap();
+		assert!(!verification.valid);
+		let broken_link = verification.first_broken_link.unwrap();
+		assert_eq!(broken_link.change_id, change_ids[2]);
+		assert_eq!(broken_link.reason, BrokenLinkReason::PreviousHashMismatch);
+	}
+
+	#[test]
+	fn chaining_after_a_change_without_a_hash_fails() {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		let (mut conn, change_ids) = chained_changes(2);
+		update(device_changes.find(change_ids[0]))
+			.set(hash.eq(None::<String>))
+			.execute(&mut conn)
+			.unwrap();
+
+		assert!(chain_change(&mut conn, change_ids[1]).is_err());
+	}
+
+	#[test]
+	fn purged_first_change_still_verifies() {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		let (mut conn, change_ids) = chained_changes(3);
+		delete(device_changes.find(change_ids[0]))
+			.execute(&mut conn)
+			.unwrap();
+
+		let verification = verify_change_chain(&mut conn).unwrap();
+		assert!(verification.valid);
+		assert_eq!(verification.checked_count, 2);
+	}
+
+	#[test]
+	fn rehashing_after_a_purge_restores_the_chain() {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		let (mut conn, change_ids) = chained_changes(3);
+		let first_hash = load_change(&mut conn, change_ids[0]).hash;
+		delete(device_changes.find(change_ids[1]))
+			.execute(&mut conn)
+			.unwrap();
+
+		let head_hash = rehash_change_chain_from(&mut conn, change_ids[1]).unwrap();
+
+		let verification = verify_change_chain(&mut conn).unwrap();
+		assert!(verification.valid);
+		assert_eq!(verification.checked_count, 2);
+		assert_eq!(verification.head_hash, head_hash);
+		assert_eq!(load_change(&mut conn, change_ids[0]).hash, first_hash);
+		assert_eq!(
+			load_change(&mut conn, change_ids[2]).previous_hash,
+			first_hash
+		);
+	}
+}
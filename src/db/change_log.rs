@@ -1,29 +1,39 @@
 // Uses
 use std::{borrow::Cow, collections::HashMap, hash::Hash};
 
-use chrono::Utc;
+use chrono::{NaiveDate, NaiveDateTime, Utc};
 use diesel::{insert_into, RunQueryDsl, SqliteConnection};
 use serde_json::to_string as to_json_string;
 use serde_with::skip_serializing_none;
 
 use super::{
+	change_chain::chain_change,
 	models::{
 		DeviceAttachmentExisting,
 		DeviceAttachmentMetadata,
 		DeviceAttachmentNew,
 		DeviceAttachmentUpsert,
 		DeviceChangeNew,
-		DeviceComponent,
+		DeviceComponentData,
+		DeviceComponentDataValue,
 		DeviceComponentNew,
 		DeviceComponentUpsert,
+		DeviceComponentWithData,
 		DeviceData,
 		DeviceDataNew,
 		DeviceInfo,
 		DeviceKeyInfoNew,
+		DeviceLifecycle,
+		DeviceLifecycleNew,
 	},
 	schema,
+	util::fetch_new_rowid_on,
+};
+use crate::{
+	error::{Context, Error},
+	notifications::enqueue_device_changed_notification,
+	webhooks::{enqueue_webhook_event, WebhookEvent},
 };
-use crate::error::{Context, Error};
 
 pub trait Diff<B, A>
 where
@@ -123,6 +133,50 @@ pub fn log_change(
 	device_id: i32,
 	user_id_value: i32,
 	diff: &DeviceDiff<'_>,
+) -> Result<(), Error> {
+	insert_change(conn, device_id, Some(user_id_value), diff, false, None)
+}
+
+/// Logs a change that was made automatically, as a side effect of some other
+/// operation rather than by editing the device directly.
+///
+/// `user_id_value` is the user the change was made on behalf of, if any.
+/// Changes made by the server on its own (such as on a schedule) have none.
+pub fn log_automatic_change(
+	conn: &mut SqliteConnection,
+	device_id: i32,
+	user_id_value: Option<i32>,
+	diff: &DeviceDiff<'_>,
+) -> Result<(), Error> {
+	insert_change(conn, device_id, user_id_value, diff, true, None)
+}
+
+/// Logs a change that was made as part of a bulk edit, so that it can be
+/// grouped with the changes made to the other devices in the same batch.
+pub fn log_batch_change(
+	conn: &mut SqliteConnection,
+	device_id: i32,
+	user_id_value: i32,
+	diff: &DeviceDiff<'_>,
+	batch: i32,
+) -> Result<(), Error> {
+	insert_change(
+		conn,
+		device_id,
+		Some(user_id_value),
+		diff,
+		false,
+		Some(batch),
+	)
+}
+
+fn insert_change(
+	conn: &mut SqliteConnection,
+	device_id: i32,
+	user_id_value: Option<i32>,
+	diff: &DeviceDiff<'_>,
+	automatic: bool,
+	batch: Option<i32>,
 ) -> Result<(), Error> {
 	// Uses
 	use schema::device_changes::dsl::*;
@@ -135,12 +189,26 @@ pub fn log_change(
 		.values(DeviceChangeNew {
 			device_key_info_id: device_id,
 			timestamp:          Utc::now().naive_utc(),
-			done_automatically: false,
+			done_automatically: automatic,
 			user_id:            user_id_value,
 			change:             Cow::from(serialised_diff),
+			batch_id:           batch,
 		})
 		.execute(conn)
 		.with_context("unable to insert into device_changes")?;
+	let change_id =
+		fetch_new_rowid_on(conn).with_context("unable to get the ID of the new change")?;
+
+	// Chain the change to the one before it, so that tampering can be detected
+	chain_change(conn, change_id).with_context("unable to chain the new change")?;
+
+	// Queue the change for the webhooks
+	enqueue_webhook_event(conn, change_id, WebhookEvent::from(diff))
+		.with_context("unable to queue the change for the webhooks")?;
+
+	// Let the borrower know, if someone else changed a device they have
+	enqueue_device_changed_notification(conn, device_id, user_id_value, diff)
+		.with_context("unable to queue the change notification")?;
 
 	Ok(())
 }
@@ -152,13 +220,23 @@ pub fn log_change(
 #[serde(rename_all = "camelCase")]
 pub struct DeviceDiff<'a> {
 	#[serde(default)]
-	pub device_key_info:    Option<DeviceKeyInfoDiff>,
+	pub device_key_info:     Option<DeviceKeyInfoDiff>,
+	#[serde(default)]
+	pub device_data:         Option<DeviceDataDiff<'a>>,
+	#[serde(default)]
+	pub device_components:   Option<DeviceComponentsDiff<'a>>,
+	#[serde(default)]
+	pub device_attachments:  Option<DeviceAttachmentsDiff<'a>>,
+	#[serde(default)]
+	pub device_loan:         Option<DeviceLoanDiff<'a>>,
 	#[serde(default)]
-	pub device_data:        Option<DeviceDataDiff<'a>>,
+	pub device_booking:      Option<DeviceBookingDiff<'a>>,
 	#[serde(default)]
-	pub device_components:  Option<DeviceComponentsDiff<'a>>,
+	pub device_parent:       Option<DeviceParentDiff<'a>>,
 	#[serde(default)]
-	pub device_attachments: Option<DeviceAttachmentsDiff<'a>>,
+	pub component_promotion: Option<ComponentPromotionDiff<'a>>,
+	#[serde(default)]
+	pub device_lifecycle:    Option<DeviceLifecycleDiff>,
 }
 
 impl<'a>
@@ -166,7 +244,7 @@ impl<'a>
 		(
 			DeviceInfo<'a>,
 			Vec<DeviceData<'a>>,
-			Vec<DeviceComponent<'a>>,
+			Vec<DeviceComponentWithData<'a>>,
 			Vec<DeviceAttachmentMetadata<'a>>,
 		),
 		(
@@ -181,7 +259,7 @@ impl<'a>
 		before: &(
 			DeviceInfo<'a>,
 			Vec<DeviceData<'a>>,
-			Vec<DeviceComponent<'a>>,
+			Vec<DeviceComponentWithData<'a>>,
 			Vec<DeviceAttachmentMetadata<'a>>,
 		),
 		after: &(
@@ -192,10 +270,15 @@ impl<'a>
 		),
 	) -> Option<Self> {
 		none_if_empty(Self {
-			device_key_info:    DeviceKeyInfoDiff::calculate_diff(&before.0, &after.0),
-			device_data:        DeviceDataDiff::calculate_diff(&before.1, &after.1),
-			device_components:  DeviceComponentsDiff::calculate_diff(&before.2, &after.2),
-			device_attachments: DeviceAttachmentsDiff::calculate_diff(&before.3, &after.3),
+			device_key_info:     DeviceKeyInfoDiff::calculate_diff(&before.0, &after.0),
+			device_data:         DeviceDataDiff::calculate_diff(&before.1, &after.1),
+			device_components:   DeviceComponentsDiff::calculate_diff(&before.2, &after.2),
+			device_attachments:  DeviceAttachmentsDiff::calculate_diff(&before.3, &after.3),
+			device_loan:         None,
+			device_booking:      None,
+			device_parent:       None,
+			component_promotion: None,
+			device_lifecycle:    None,
 		})
 	}
 
@@ -204,6 +287,11 @@ impl<'a>
 			&& self.device_data.is_none()
 			&& self.device_components.is_none()
 			&& self.device_attachments.is_none()
+			&& self.device_loan.is_none()
+			&& self.device_booking.is_none()
+			&& self.device_parent.is_none()
+			&& self.component_promotion.is_none()
+			&& self.device_lifecycle.is_none()
 	}
 }
 
@@ -224,10 +312,15 @@ impl<'a>
 		),
 	) -> Self {
 		Self {
-			device_key_info:    none_if_empty(DeviceKeyInfoDiff::from(&after.0)),
-			device_data:        none_if_empty(DeviceDataDiff::from(&after.1)),
-			device_components:  none_if_empty(DeviceComponentsDiff::from(&after.2)),
-			device_attachments: none_if_empty(DeviceAttachmentsDiff::from(&after.3)),
+			device_key_info:     none_if_empty(DeviceKeyInfoDiff::from(&after.0)),
+			device_data:         none_if_empty(DeviceDataDiff::from(&after.1)),
+			device_components:   none_if_empty(DeviceComponentsDiff::from(&after.2)),
+			device_attachments:  none_if_empty(DeviceAttachmentsDiff::from(&after.3)),
+			device_loan:         None,
+			device_booking:      None,
+			device_parent:       None,
+			component_promotion: None,
+			device_lifecycle:    None,
 		}
 	}
 }
@@ -344,19 +437,19 @@ impl<'a> From<&DeviceDataNew<'a>> for DeviceDataColumnDiff<'a> {
 #[serde(rename_all = "camelCase")]
 pub struct DeviceComponentsDiff<'a>(Vec<DeviceComponentsComponentDiff<'a>>);
 
-impl<'a> Diff<Vec<DeviceComponent<'a>>, Vec<DeviceComponentUpsert<'a>>>
+impl<'a> Diff<Vec<DeviceComponentWithData<'a>>, Vec<DeviceComponentUpsert<'a>>>
 	for DeviceComponentsDiff<'a>
 {
 	fn calculate_diff(
-		before: &Vec<DeviceComponent<'a>>,
+		before: &Vec<DeviceComponentWithData<'a>>,
 		after: &Vec<DeviceComponentUpsert<'a>>,
 	) -> Option<Self> {
 		calculate_vec_diff(
 			before,
-			|before| before.component_id.clone(),
+			|before| before.component.component_id.clone(),
 			after,
 			|after| match after {
-				DeviceComponentUpsert::NewExisting(DeviceComponentNew { component_id, .. })
+				DeviceComponentUpsert::NewExisting(DeviceComponentNew { component_id, .. }, _)
 				| DeviceComponentUpsert::Delete(component_id) => component_id.clone(),
 			},
 			|after| Some(DeviceComponentsComponentDiff::from(after)),
@@ -396,24 +489,33 @@ pub enum DeviceComponentsComponentDiff<'a> {
 	},
 }
 
-impl<'a> Diff<DeviceComponent<'a>, DeviceComponentUpsert<'a>>
+impl<'a> Diff<DeviceComponentWithData<'a>, DeviceComponentUpsert<'a>>
 	for DeviceComponentsComponentDiff<'a>
 {
 	fn calculate_diff(
-		before: &DeviceComponent<'a>,
+		before: &DeviceComponentWithData<'a>,
 		after: &DeviceComponentUpsert<'a>,
 	) -> Option<Self> {
 		match after {
-			DeviceComponentUpsert::NewExisting(new_component) => {
+			DeviceComponentUpsert::NewExisting(new_component, new_component_data) => {
 				assert_eq!(
-					before.component_id, new_component.component_id,
+					before.component.component_id, new_component.component_id,
 					"component_id values must match"
 				);
 
 				none_if_empty(Self::Edit(DeviceComponentsComponentDiffData {
 					component_id:   new_component.component_id.clone(),
-					component_type: (before.component_type != new_component.component_type)
+					component_type: (before.component.component_type
+						!= new_component.component_type)
 						.then_some(new_component.component_type.clone()),
+					column_data:    calculate_vec_diff(
+						&before.column_data,
+						|before| before.component_column_definition_id,
+						new_component_data,
+						|after| after.component_column_definition_id,
+						|after| Some(DeviceComponentDataColumnDiff::from(after)),
+						|_| None,
+					),
 				}))
 			}
 			DeviceComponentUpsert::Delete(component_id) => Some(Self::Delete {
@@ -424,16 +526,18 @@ impl<'a> Diff<DeviceComponent<'a>, DeviceComponentUpsert<'a>>
 
 	fn is_empty(&self) -> bool {
 		match self {
-			Self::Add(diff) | Self::Edit(diff) => diff.component_type.is_none(),
+			Self::Add(diff) | Self::Edit(diff) => {
+				diff.component_type.is_none() && diff.column_data.is_none()
+			}
 			Self::Delete { .. } | Self::Restore { .. } => false,
 		}
 	}
 }
 
-impl<'a> From<&DeviceComponent<'a>> for DeviceComponentsComponentDiff<'a> {
-	fn from(before: &DeviceComponent<'a>) -> Self {
+impl<'a> From<&DeviceComponentWithData<'a>> for DeviceComponentsComponentDiff<'a> {
+	fn from(before: &DeviceComponentWithData<'a>) -> Self {
 		Self::Delete {
-			component_id: before.component_id.clone(),
+			component_id: before.component.component_id.clone(),
 		}
 	}
 }
@@ -441,13 +545,22 @@ impl<'a> From<&DeviceComponent<'a>> for DeviceComponentsComponentDiff<'a> {
 impl<'a> From<&DeviceComponentUpsert<'a>> for DeviceComponentsComponentDiff<'a> {
 	fn from(after: &DeviceComponentUpsert<'a>) -> Self {
 		Self::Add(match after {
-			DeviceComponentUpsert::NewExisting(DeviceComponentNew {
-				component_id,
-				component_type,
-				..
-			}) => DeviceComponentsComponentDiffData {
+			DeviceComponentUpsert::NewExisting(
+				DeviceComponentNew {
+					component_id,
+					component_type,
+					..
+				},
+				column_data,
+			) => DeviceComponentsComponentDiffData {
 				component_id:   component_id.clone(),
 				component_type: Some(component_type.clone()),
+				column_data:    (!column_data.is_empty()).then(|| {
+					column_data
+						.iter()
+						.map(DeviceComponentDataColumnDiff::from)
+						.collect()
+				}),
 			},
 			DeviceComponentUpsert::Delete(_) => {
 				unreachable!("the component should already exist if it's being deleted")
@@ -463,6 +576,50 @@ pub struct DeviceComponentsComponentDiffData<'a> {
 	pub component_id:   Cow<'a, str>,
 	#[serde(default)]
 	pub component_type: Option<Cow<'a, str>>,
+	#[serde(default)]
+	pub column_data:    Option<Vec<DeviceComponentDataColumnDiff<'a>>>,
+}
+
+#[skip_serializing_none]
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceComponentDataColumnDiff<'a> {
+	pub component_column_definition_id: i32,
+	#[serde(default)]
+	pub data_value:                     Option<Cow<'a, str>>,
+}
+
+impl<'a> Diff<DeviceComponentData<'a>, DeviceComponentDataValue<'a>>
+	for DeviceComponentDataColumnDiff<'a>
+{
+	fn calculate_diff(
+		before: &DeviceComponentData<'a>,
+		after: &DeviceComponentDataValue<'a>,
+	) -> Option<Self> {
+		assert_eq!(
+			before.component_column_definition_id, after.component_column_definition_id,
+			"component_column_definition_id values must match"
+		);
+
+		none_if_empty(Self {
+			component_column_definition_id: after.component_column_definition_id,
+			data_value:                     (before.data_value != after.data_value)
+				.then_some(after.data_value.clone()),
+		})
+	}
+
+	fn is_empty(&self) -> bool {
+		self.data_value.is_none()
+	}
+}
+
+impl<'a> From<&DeviceComponentDataValue<'a>> for DeviceComponentDataColumnDiff<'a> {
+	fn from(after: &DeviceComponentDataValue<'a>) -> Self {
+		Self {
+			component_column_definition_id: after.component_column_definition_id,
+			data_value:                     Some(after.data_value.clone()),
+		}
+	}
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -616,3 +773,117 @@ pub struct DeviceAttachmentsAttachmentDiffData<'a> {
 	#[serde(default)]
 	pub file_name:     Option<Cow<'a, str>>,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "camelCase")]
+pub enum DeviceLoanDiff<'a> {
+	#[serde(rename_all = "camelCase")]
+	CheckOut {
+		loan_id:          i32,
+		#[serde(default)]
+		borrower_user_id: Option<i32>,
+		#[serde(default)]
+		borrower_name:    Option<Cow<'a, str>>,
+		#[serde(default)]
+		due_at:           Option<NaiveDateTime>,
+		notes:            Cow<'a, str>,
+	},
+	#[serde(rename_all = "camelCase")]
+	CheckIn { loan_id: i32, notes: Cow<'a, str> },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "camelCase")]
+pub enum DeviceBookingDiff<'a> {
+	#[serde(rename_all = "camelCase")]
+	Add {
+		booking_id: i32,
+		starts_at:  NaiveDateTime,
+		ends_at:    NaiveDateTime,
+		purpose:    Cow<'a, str>,
+	},
+	#[serde(rename_all = "camelCase")]
+	Cancel { booking_id: i32 },
+}
+
+/// Changes to which device a device is attached to. These are logged on the
+/// child device, and on each of the parent devices involved.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "camelCase")]
+pub enum DeviceParentDiff<'a> {
+	#[serde(rename_all = "camelCase")]
+	SetParent {
+		#[serde(default)]
+		old_parent_device_id: Option<Cow<'a, str>>,
+		#[serde(default)]
+		new_parent_device_id: Option<Cow<'a, str>>,
+	},
+	#[serde(rename_all = "camelCase")]
+	AddChild { child_device_id: Cow<'a, str> },
+	#[serde(rename_all = "camelCase")]
+	RemoveChild { child_device_id: Cow<'a, str> },
+}
+
+/// A component being promoted to a standalone device. This is logged on both
+/// the original device and the new one, each referring to the other.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "camelCase")]
+pub enum ComponentPromotionDiff<'a> {
+	#[serde(rename_all = "camelCase")]
+	PromotedFrom {
+		device_id:    Cow<'a, str>,
+		component_id: Cow<'a, str>,
+	},
+	#[serde(rename_all = "camelCase")]
+	PromotedTo {
+		component_id: Cow<'a, str>,
+		device_id:    Cow<'a, str>,
+	},
+}
+
+/// A change to a device's lifecycle dates.
+///
+/// Each field is `None` if the date didn't change, and `Some(None)` if it was
+/// cleared.
+#[skip_serializing_none]
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::option_option, clippy::struct_field_names)]
+pub struct DeviceLifecycleDiff {
+	#[serde(default, with = "::serde_with::rust::double_option")]
+	pub purchased_on:     Option<Option<NaiveDate>>,
+	#[serde(default, with = "::serde_with::rust::double_option")]
+	pub warranty_ends_on: Option<Option<NaiveDate>>,
+	#[serde(default, with = "::serde_with::rust::double_option")]
+	pub end_of_life_on:   Option<Option<NaiveDate>>,
+}
+
+impl Diff<Option<DeviceLifecycle>, DeviceLifecycleNew> for DeviceLifecycleDiff {
+	fn calculate_diff(
+		before: &Option<DeviceLifecycle>,
+		after: &DeviceLifecycleNew,
+	) -> Option<Self> {
+		let (purchased_on, warranty_ends_on, end_of_life_on) =
+			before.as_ref().map_or((None, None, None), |before| {
+				(
+					before.purchased_on,
+					before.warranty_ends_on,
+					before.end_of_life_on,
+				)
+			});
+
+		none_if_empty(Self {
+			purchased_on:     (purchased_on != after.purchased_on).then_some(after.purchased_on),
+			warranty_ends_on: (warranty_ends_on != after.warranty_ends_on)
+				.then_some(after.warranty_ends_on),
+			end_of_life_on:   (end_of_life_on != after.end_of_life_on)
+				.then_some(after.end_of_life_on),
+		})
+	}
+
+	fn is_empty(&self) -> bool {
+		self.purchased_on.is_none()
+			&& self.warranty_ends_on.is_none()
+			&& self.end_of_life_on.is_none()
+	}
+}
@@ -3,10 +3,13 @@ use std::fmt::Debug;
 
 use diesel::{sql_query, RunQueryDsl, SqliteConnection};
 use diesel_migrations::{embed_migrations, EmbeddedMigrations, MigrationHarness};
-use rocket::{Build, Rocket};
-use rocket_sync_db_pools::database;
+use rocket::{Build, Phase, Rocket};
+use rocket_sync_db_pools::{database, ConnectionPool};
+
+use self::change_chain::backfill_change_hashes;
 
 // Modules
+pub mod change_chain;
 pub mod change_log;
 pub mod enums;
 pub mod functions;
@@ -18,6 +21,23 @@ pub mod util;
 #[database("sqlite_database")]
 pub struct DbConn(pub(crate) SqliteConnection);
 
+/// A handle to the database connection pool, for background tasks that only
+/// need a connection every now and then.
+#[derive(Clone)]
+pub struct DbPool(ConnectionPool<DbConn, SqliteConnection>);
+
+impl DbPool {
+	/// Gets the connection pool that's attached to `rocket`.
+	pub fn get_from<P: Phase>(rocket: &Rocket<P>) -> Option<Self> {
+		DbConn::pool(rocket).cloned().map(Self)
+	}
+
+	/// Takes a connection from the pool, waiting for one to become free.
+	pub async fn get_one(&self) -> Option<DbConn> {
+		self.0.get().await.map(DbConn)
+	}
+}
+
 // Embed the database migrations so they can be run on startup, straight from
 // the compiled binary.
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!("migrations");
@@ -63,6 +83,20 @@ pub async fn init(rocket: Rocket<Build>) -> Result<Rocket<Build>, Rocket<Build>>
 		return Err(rocket);
 	}
 
+	// Hash the changes logged before the hash chain existed - SQLite can't do this
+	// itself, so it can't be part of the migration
+	if conn
+		.run(|c| {
+			is_err_display_error(
+				backfill_change_hashes(c),
+				"unable to hash the existing device changes",
+			)
+		})
+		.await
+	{
+		return Err(rocket);
+	}
+
 	// Enable foreign key constraints after running pending migrations
 	if conn
 		.run(|c| {
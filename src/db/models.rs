@@ -3,10 +3,10 @@
 // Uses
 use std::borrow::Cow;
 
-use chrono::NaiveDateTime;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::{
 	dsl::Nullable,
-	sql_types::{Bool, Integer, Text, Timestamp},
+	sql_types::{self, Bool, Integer, Text, Timestamp},
 	NullableExpressionMethods,
 };
 
@@ -30,6 +30,7 @@ pub struct UserNew {
 	pub source:            UserSource,
 	pub unique_identifier: String,
 	pub display_name:      String,
+	pub email:             Option<String>,
 }
 #[derive(Associations, Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = tokens, belongs_to(User, foreign_key = user_id))]
@@ -88,28 +89,69 @@ pub struct ColumnPossibleValueNew<'a> {
 	pub column_definition_id: i32,
 	pub value:                Cow<'a, str>,
 }
+#[derive(Associations, Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = component_column_possible_values, belongs_to(ComponentColumnDefinitionSelected<'_>, foreign_key = component_column_definition_id))]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentColumnPossibleValue<'a> {
+	pub id:                             i32,
+	pub component_column_definition_id: i32,
+	pub value:                          Cow<'a, str>,
+}
 
 #[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = locations)]
 #[serde(rename_all = "camelCase")]
 pub struct LocationDefinition<'a> {
-	pub id:   i32,
-	pub name: Cow<'a, str>,
+	pub id:        i32,
+	pub parent_id: Option<i32>,
+	pub name:      Cow<'a, str>,
 }
 #[derive(Insertable, Debug, Clone)]
 #[diesel(table_name = locations)]
 pub struct LocationDefinitionNew<'a> {
-	pub name: Cow<'a, str>,
+	pub parent_id: Option<i32>,
+	pub name:      Cow<'a, str>,
+}
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = location_paths)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationPath<'a> {
+	pub id:        i32,
+	pub parent_id: Option<i32>,
+	pub name:      Cow<'a, str>,
+	pub path:      Cow<'a, str>,
+	pub depth:     i32,
+}
+#[derive(QueryableByName, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationSummary<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:                 i32,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub parent_id:          Option<i32>,
+	#[diesel(sql_type = Text)]
+	pub name:               Cow<'a, str>,
+	#[diesel(sql_type = Text)]
+	pub path:               Cow<'a, str>,
+	#[diesel(sql_type = Integer)]
+	pub depth:              i32,
+	/// The number of devices directly in the location.
+	#[diesel(sql_type = Integer)]
+	pub device_count:       i32,
+	/// The number of devices in the location and all of its descendants.
+	#[diesel(sql_type = Integer)]
+	pub total_device_count: i32,
 }
 
 #[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
 #[diesel(table_name = device_key_info)]
 #[serde(rename_all = "camelCase")]
 pub struct DeviceKeyInfo<'a> {
-	pub id:          i32,
-	pub device_id:   Cow<'a, str>,
-	pub deleted:     bool,
-	pub location_id: i32,
+	pub id:               i32,
+	pub device_id:        Cow<'a, str>,
+	pub deleted:          bool,
+	pub location_id:      i32,
+	pub parent_device_id: Option<i32>,
 }
 #[derive(Insertable, Debug, Clone)]
 #[diesel(table_name = device_key_info)]
@@ -147,7 +189,7 @@ pub struct DeviceComponent<'a> {
 }
 #[derive(Debug, Clone)]
 pub enum DeviceComponentUpsert<'a> {
-	NewExisting(DeviceComponentNew<'a>),
+	NewExisting(DeviceComponentNew<'a>, Vec<DeviceComponentDataValue<'a>>),
 	Delete(Cow<'a, str>),
 }
 #[derive(Insertable, Debug, Clone)]
@@ -157,6 +199,39 @@ pub struct DeviceComponentNew<'a> {
 	pub component_id:       Cow<'a, str>,
 	pub component_type:     Cow<'a, str>,
 }
+/// A component, along with its structured data. The data is serialised
+/// alongside the component's own fields.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceComponentWithData<'a> {
+	#[serde(flatten)]
+	pub component:   DeviceComponent<'a>,
+	pub column_data: Vec<DeviceComponentData<'a>>,
+}
+
+#[derive(Associations, Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = device_component_data, belongs_to(DeviceComponent<'_>, foreign_key = device_component_id))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceComponentData<'a> {
+	pub id:                             i32,
+	pub device_component_id:            i32,
+	pub component_column_definition_id: i32,
+	pub data_value:                     Cow<'a, str>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = device_component_data)]
+pub struct DeviceComponentDataNew<'a> {
+	pub device_component_id:            i32,
+	pub component_column_definition_id: i32,
+	pub data_value:                     Cow<'a, str>,
+}
+/// A submitted component data value, for a component that might not have been
+/// inserted yet.
+#[derive(Debug, Clone)]
+pub struct DeviceComponentDataValue<'a> {
+	pub component_column_definition_id: i32,
+	pub data_value:                     Cow<'a, str>,
+}
 
 #[derive(Associations, Identifiable, Queryable, Debug, Clone)]
 #[diesel(table_name = device_attachments, belongs_to(DeviceInfo<'_>, foreign_key = device_key_info_id))]
@@ -212,6 +287,10 @@ pub struct DeviceChange<'a> {
 	pub done_automatically: bool,
 	pub user_id:            Option<i32>,
 	pub change:             Cow<'a, str>,
+	pub batch_id:           Option<i32>,
+	/// The hash of the change before this one, which this one is chained to.
+	pub previous_hash:      Option<Cow<'a, str>>,
+	pub hash:               Option<Cow<'a, str>>,
 }
 #[derive(Insertable, Debug, Clone)]
 #[diesel(table_name = device_changes)]
@@ -219,8 +298,111 @@ pub struct DeviceChangeNew<'a> {
 	pub device_key_info_id: i32,
 	pub timestamp:          NaiveDateTime,
 	pub done_automatically: bool,
-	pub user_id:            i32,
+	pub user_id:            Option<i32>,
 	pub change:             Cow<'a, str>,
+	pub batch_id:           Option<i32>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = device_change_batches)]
+pub struct DeviceChangeBatchNew {
+	pub user_id:    i32,
+	pub created_at: NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = reserved_device_ids)]
+pub struct ReservedDeviceIdNew<'a> {
+	pub device_id:   Cow<'a, str>,
+	pub reserved_by: i32,
+	pub reserved_at: NaiveDateTime,
+}
+
+#[derive(Associations, Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = device_loans, belongs_to(DeviceInfo<'_>, foreign_key = device_key_info_id))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLoan<'a> {
+	pub id:                 i32,
+	pub device_key_info_id: i32,
+	pub borrower_user_id:   Option<i32>,
+	pub borrower_name:      Option<Cow<'a, str>>,
+	pub loaned_by:          i32,
+	pub loaned_at:          NaiveDateTime,
+	pub due_at:             Option<NaiveDateTime>,
+	pub notes:              Cow<'a, str>,
+	pub returned_at:        Option<NaiveDateTime>,
+	pub returned_to:        Option<i32>,
+	pub return_notes:       Option<Cow<'a, str>>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = device_loans)]
+pub struct DeviceLoanNew<'a> {
+	pub device_key_info_id: i32,
+	pub borrower_user_id:   Option<i32>,
+	pub borrower_name:      Option<Cow<'a, str>>,
+	pub loaned_by:          i32,
+	pub loaned_at:          NaiveDateTime,
+	pub due_at:             Option<NaiveDateTime>,
+	pub notes:              Cow<'a, str>,
+}
+
+#[derive(Associations, Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = device_bookings, belongs_to(DeviceInfo<'_>, foreign_key = device_key_info_id), belongs_to(User, foreign_key = user_id))]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceBooking<'a> {
+	pub id:                 i32,
+	pub device_key_info_id: i32,
+	pub user_id:            i32,
+	pub starts_at:          NaiveDateTime,
+	pub ends_at:            NaiveDateTime,
+	pub purpose:            Cow<'a, str>,
+	pub cancelled:          bool,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = device_bookings)]
+pub struct DeviceBookingNew<'a> {
+	pub device_key_info_id: i32,
+	pub user_id:            i32,
+	pub starts_at:          NaiveDateTime,
+	pub ends_at:            NaiveDateTime,
+	pub purpose:            Cow<'a, str>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = calendar_feed_tokens)]
+pub struct CalendarFeedTokenNew<'a> {
+	pub user_id: i32,
+	pub value:   Cow<'a, str>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = audit_sessions)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditSession<'a> {
+	pub id:          i32,
+	pub location_id: i32,
+	pub started_by:  i32,
+	pub started_at:  NaiveDateTime,
+	pub closed_by:   Option<i32>,
+	pub closed_at:   Option<NaiveDateTime>,
+	pub report:      Option<Cow<'a, str>>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_sessions)]
+pub struct AuditSessionNew {
+	pub location_id: i32,
+	pub started_by:  i32,
+	pub started_at:  NaiveDateTime,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_scans)]
+pub struct AuditScanNew<'a> {
+	pub audit_session_id:   i32,
+	pub scanned_value:      Cow<'a, str>,
+	pub device_key_info_id: Option<i32>,
+	pub scanned_by:         i32,
+	pub scanned_at:         NaiveDateTime,
 }
 
 // Select Definitions
@@ -298,6 +480,15 @@ select_def_const! {
 	)
 }
 
+select_def_const! {
+	DEVICE_COMPONENT_DATA: DeviceComponentDataSelect = (
+		device_component_data::id,
+		device_component_data::device_component_id,
+		device_component_data::component_column_definition_id,
+		device_component_data::data_value,
+	)
+}
+
 select_def_const! {
 	DEVICE_ATTACHMENT_METADATA: DeviceAttachmentMetadataSelect = (
 		device_attachments::id,
@@ -362,6 +553,20 @@ impl<'a> From<DeviceInfoByName<'a>> for DeviceInfo<'a> {
 	}
 }
 
+#[derive(QueryableByName, Debug)]
+pub struct DeviceTreeEntry<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:               i32,
+	#[diesel(sql_type = Text)]
+	pub device_id:        Cow<'a, str>,
+	#[diesel(sql_type = Integer)]
+	pub parent_device_id: i32,
+	#[diesel(sql_type = Integer)]
+	pub location_id:      i32,
+	#[diesel(sql_type = Text)]
+	pub location:         Cow<'a, str>,
+}
+
 select_def_fn! {
 	COLUMN_DEFINITION: ColumnDefinitionSelect = (
 		(column_definitions::id),
@@ -392,6 +597,32 @@ pub struct ColumnDefinitionSelected<'a> {
 	pub default_value:               Option<Cow<'a, str>>,
 }
 
+select_def_fn! {
+	COMPONENT_COLUMN_DEFINITION: ComponentColumnDefinitionSelect = (
+		(component_column_definitions::id),
+		(component_column_definitions::name),
+		(component_column_definitions::ordering_key),
+		(component_column_definitions::not_null),
+		(component_column_definitions::unique_values),
+		(component_column_definitions::exclusively_possible_values),
+		(component_column_definitions::default_value_id),
+		(component_column_possible_values::value.nullable(), Nullable<component_column_possible_values::value>),
+	)
+}
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = component_column_definitions)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentColumnDefinitionSelected<'a> {
+	pub id:                          i32,
+	pub name:                        Cow<'a, str>,
+	pub ordering_key:                Option<i32>,
+	pub not_null:                    bool,
+	pub unique_values:               bool,
+	pub exclusively_possible_values: bool,
+	pub default_value_id:            Option<i32>,
+	pub default_value:               Option<Cow<'a, str>>,
+}
+
 select_def_fn! {
 	DEVICE_CHANGE: DeviceChangeSelect = (
 		(device_changes::id),
@@ -401,6 +632,7 @@ select_def_fn! {
 		(device_changes::user_id),
 		(user_info::display_name.nullable(), Nullable<user_info::display_name>),
 		(device_changes::change),
+		(device_changes::batch_id),
 	)
 }
 #[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
@@ -414,4 +646,418 @@ pub struct DeviceChangeDisplay<'a> {
 	pub user_id:            Option<i32>,
 	pub user:               Option<Cow<'a, str>>,
 	pub change:             Cow<'a, str>,
+	pub batch_id:           Option<i32>,
+}
+
+/// A device change from the global change history.
+///
+/// This is loaded with raw SQL, because the kind of change is derived from the
+/// change's JSON.
+#[derive(QueryableByName, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceChangeHistoryEntry<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:                 i32,
+	#[diesel(sql_type = Integer)]
+	pub device_key_info_id: i32,
+	#[diesel(sql_type = Text)]
+	pub device_id:          Cow<'a, str>,
+	/// The device's current location, not necessarily the one it was in when
+	/// the change was made.
+	#[diesel(sql_type = Integer)]
+	pub location_id:        i32,
+	#[diesel(sql_type = Text)]
+	pub location:           Cow<'a, str>,
+	#[diesel(sql_type = Timestamp)]
+	pub timestamp:          NaiveDateTime,
+	#[diesel(sql_type = Bool)]
+	pub done_automatically: bool,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub user_id:            Option<i32>,
+	#[diesel(sql_type = sql_types::Nullable<Text>)]
+	pub user:               Option<Cow<'a, str>>,
+	/// The name of the kind of change, as used by webhooks.
+	#[diesel(sql_type = Text)]
+	pub event:              Cow<'a, str>,
+	#[diesel(sql_type = Text)]
+	pub change:             Cow<'a, str>,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub batch_id:           Option<i32>,
+}
+/// A deleted device, with when it was deleted and by whom.
+///
+/// This is loaded with raw SQL, because the deletion is found by looking
+/// through the device's change JSON.
+#[derive(QueryableByName, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedDeviceEntry<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:              i32,
+	#[diesel(sql_type = Text)]
+	pub device_id:       Cow<'a, str>,
+	#[diesel(sql_type = Integer)]
+	pub location_id:     i32,
+	#[diesel(sql_type = Text)]
+	pub location:        Cow<'a, str>,
+	/// This is only missing if the deletion's change has been removed from the
+	/// change log.
+	#[diesel(sql_type = sql_types::Nullable<Timestamp>)]
+	pub deleted_at:      Option<NaiveDateTime>,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub deleted_by:      Option<i32>,
+	#[diesel(sql_type = sql_types::Nullable<Text>)]
+	pub deleted_by_name: Option<Cow<'a, str>>,
+}
+/// The number of rows matched by a raw SQL query.
+#[derive(QueryableByName, Debug)]
+pub struct RowCount {
+	#[diesel(sql_type = sql_types::BigInt)]
+	pub count: i64,
+}
+
+select_def_const! {
+	RESERVED_DEVICE_ID: ReservedDeviceIdSelect = (
+		reserved_device_ids::id,
+		reserved_device_ids::device_id,
+		reserved_device_ids::reserved_by,
+		user_info::display_name,
+		reserved_device_ids::reserved_at,
+	)
+}
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = reserved_device_ids)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservedDeviceIdDisplay<'a> {
+	pub id:               i32,
+	pub device_id:        Cow<'a, str>,
+	pub reserved_by:      i32,
+	pub reserved_by_name: Cow<'a, str>,
+	pub reserved_at:      NaiveDateTime,
+}
+
+/// A loan, with the names of everyone involved filled in.
+///
+/// This is loaded with raw SQL, because it joins against `user_info` several
+/// times.
+#[derive(QueryableByName, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLoanDisplay<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:               i32,
+	#[diesel(sql_type = Text)]
+	pub device_id:        Cow<'a, str>,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub borrower_user_id: Option<i32>,
+	#[diesel(sql_type = Text)]
+	pub borrower:         Cow<'a, str>,
+	#[diesel(sql_type = Integer)]
+	pub loaned_by:        i32,
+	#[diesel(sql_type = Text)]
+	pub loaned_by_name:   Cow<'a, str>,
+	#[diesel(sql_type = Timestamp)]
+	pub loaned_at:        NaiveDateTime,
+	#[diesel(sql_type = sql_types::Nullable<Timestamp>)]
+	pub due_at:           Option<NaiveDateTime>,
+	#[diesel(sql_type = Text)]
+	pub notes:            Cow<'a, str>,
+	#[diesel(sql_type = sql_types::Nullable<Timestamp>)]
+	pub returned_at:      Option<NaiveDateTime>,
+	#[diesel(sql_type = sql_types::Nullable<Integer>)]
+	pub returned_to:      Option<i32>,
+	#[diesel(sql_type = sql_types::Nullable<Text>)]
+	pub returned_to_name: Option<Cow<'a, str>>,
+	#[diesel(sql_type = sql_types::Nullable<Text>)]
+	pub return_notes:     Option<Cow<'a, str>>,
+}
+
+select_def_const! {
+	DEVICE_BOOKING: DeviceBookingSelect = (
+		device_bookings::id,
+		device_key_info::device_id,
+		device_bookings::user_id,
+		user_info::display_name,
+		device_bookings::starts_at,
+		device_bookings::ends_at,
+		device_bookings::purpose,
+		device_bookings::cancelled,
+	)
+}
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = device_bookings)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceBookingDisplay<'a> {
+	pub id:        i32,
+	pub device_id: Cow<'a, str>,
+	pub user_id:   i32,
+	pub user:      Cow<'a, str>,
+	pub starts_at: NaiveDateTime,
+	pub ends_at:   NaiveDateTime,
+	pub purpose:   Cow<'a, str>,
+	pub cancelled: bool,
+}
+
+select_def_const! {
+	AUDIT_SESSION: AuditSessionSelect = (
+		audit_sessions::id,
+		audit_sessions::location_id,
+		location_paths::path,
+		audit_sessions::started_by,
+		user_info::display_name,
+		audit_sessions::started_at,
+		audit_sessions::closed_at,
+	)
+}
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = audit_sessions)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditSessionDisplay<'a> {
+	pub id:              i32,
+	pub location_id:     i32,
+	pub location:        Cow<'a, str>,
+	pub started_by:      i32,
+	pub started_by_name: Cow<'a, str>,
+	pub started_at:      NaiveDateTime,
+	pub closed_at:       Option<NaiveDateTime>,
+}
+
+/// A device that's either expected to be found in an audit, or was scanned
+/// during it.
+#[derive(QueryableByName, Debug)]
+pub struct AuditDeviceEntry<'a> {
+	#[diesel(sql_type = Integer)]
+	pub id:          i32,
+	#[diesel(sql_type = Text)]
+	pub device_id:   Cow<'a, str>,
+	#[diesel(sql_type = Integer)]
+	pub location_id: i32,
+	#[diesel(sql_type = Text)]
+	pub location:    Cow<'a, str>,
+	/// Whether the device is recorded as being in the audited location.
+	#[diesel(sql_type = Bool)]
+	pub expected:    bool,
+	#[diesel(sql_type = Bool)]
+	pub scanned:     bool,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug, Clone)]
+#[diesel(table_name = device_lifecycles)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLifecycle {
+	pub id:                        i32,
+	pub device_key_info_id:        i32,
+	pub purchased_on:              Option<NaiveDate>,
+	pub warranty_ends_on:          Option<NaiveDate>,
+	pub end_of_life_on:            Option<NaiveDate>,
+	pub warranty_expiry_processed: bool,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = device_lifecycles)]
+pub struct DeviceLifecycleNew {
+	pub device_key_info_id:        i32,
+	pub purchased_on:              Option<NaiveDate>,
+	pub warranty_ends_on:          Option<NaiveDate>,
+	pub end_of_life_on:            Option<NaiveDate>,
+	pub warranty_expiry_processed: bool,
+}
+
+select_def_const! {
+	DEVICE_LIFECYCLE_ENTRY: DeviceLifecycleEntrySelect = (
+		device_key_info::device_id,
+		device_key_info::location_id,
+		location_paths::path,
+		device_lifecycles::purchased_on,
+		device_lifecycles::warranty_ends_on,
+		device_lifecycles::end_of_life_on,
+	)
+}
+#[derive(Queryable, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceLifecycleEntry<'a> {
+	pub device_id:        Cow<'a, str>,
+	pub location_id:      i32,
+	pub location:         Cow<'a, str>,
+	pub purchased_on:     Option<NaiveDate>,
+	pub warranty_ends_on: Option<NaiveDate>,
+	pub end_of_life_on:   Option<NaiveDate>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = job_runs)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRun<'a> {
+	pub id:           i32,
+	pub job_name:     Cow<'a, str>,
+	pub started_at:   NaiveDateTime,
+	pub finished_at:  Option<NaiveDateTime>,
+	pub succeeded:    Option<bool>,
+	pub triggered_by: Option<i32>,
+	pub output:       Option<Cow<'a, str>>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = job_runs)]
+pub struct JobRunNew<'a> {
+	pub job_name:     Cow<'a, str>,
+	pub started_at:   NaiveDateTime,
+	pub triggered_by: Option<i32>,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = webhook_outbox)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookOutboxEntry<'a> {
+	pub id:               i32,
+	pub device_change_id: i32,
+	pub event:            Cow<'a, str>,
+	pub created_at:       NaiveDateTime,
+	pub completed_at:     Option<NaiveDateTime>,
+	/// When a delivery is next due, if one may be due now.
+	pub next_attempt_at:  Option<NaiveDateTime>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = webhook_outbox)]
+pub struct WebhookOutboxEntryNew<'a> {
+	pub device_change_id: i32,
+	pub event:            Cow<'a, str>,
+	pub created_at:       NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = webhook_deliveries)]
+#[serde(rename_all = "camelCase")]
+pub struct WebhookDelivery<'a> {
+	pub id:                i32,
+	pub webhook_outbox_id: i32,
+	pub webhook_name:      Cow<'a, str>,
+	pub attempt:           i32,
+	pub attempted_at:      NaiveDateTime,
+	pub status_code:       Option<i32>,
+	pub error:             Option<Cow<'a, str>>,
+	pub succeeded:         bool,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = webhook_deliveries)]
+pub struct WebhookDeliveryNew<'a> {
+	pub webhook_outbox_id: i32,
+	pub webhook_name:      Cow<'a, str>,
+	pub attempt:           i32,
+	pub attempted_at:      NaiveDateTime,
+	pub status_code:       Option<i32>,
+	pub error:             Option<Cow<'a, str>>,
+	pub succeeded:         bool,
+}
+
+#[derive(
+	Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize, Debug, Clone,
+)]
+#[diesel(table_name = notification_preferences, primary_key(user_id))]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreferences {
+	pub user_id:           i32,
+	pub loan_overdue:      bool,
+	pub warranty_expiring: bool,
+	pub device_changed:    bool,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = notifications)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification<'a> {
+	pub id:              i32,
+	pub user_id:         i32,
+	pub kind:            Cow<'a, str>,
+	pub details:         Cow<'a, str>,
+	pub dedupe_key:      Option<Cow<'a, str>>,
+	pub created_at:      NaiveDateTime,
+	pub status:          Cow<'a, str>,
+	pub attempts:        i32,
+	pub next_attempt_at: NaiveDateTime,
+	pub finished_at:     Option<NaiveDateTime>,
+	pub last_error:      Option<Cow<'a, str>>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = notifications)]
+pub struct NotificationNew<'a> {
+	pub user_id:         i32,
+	pub kind:            Cow<'a, str>,
+	pub details:         Cow<'a, str>,
+	pub dedupe_key:      Option<Cow<'a, str>>,
+	pub created_at:      NaiveDateTime,
+	pub status:          Cow<'a, str>,
+	pub next_attempt_at: NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = saved_searches)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearch<'a> {
+	pub id:         i32,
+	pub user_id:    i32,
+	pub name:       Cow<'a, str>,
+	pub query:      Cow<'a, str>,
+	pub created_at: NaiveDateTime,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = saved_searches)]
+pub struct SavedSearchNew<'a> {
+	pub user_id:    i32,
+	pub name:       Cow<'a, str>,
+	pub query:      Cow<'a, str>,
+	pub created_at: NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = watches)]
+#[serde(rename_all = "camelCase")]
+pub struct Watch {
+	pub id:                 i32,
+	pub user_id:            i32,
+	pub device_key_info_id: Option<i32>,
+	pub location_id:        Option<i32>,
+	pub saved_search_id:    Option<i32>,
+	pub created_at:         NaiveDateTime,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = watches)]
+pub struct WatchNew {
+	pub user_id:            i32,
+	pub device_key_info_id: Option<i32>,
+	pub location_id:        Option<i32>,
+	pub saved_search_id:    Option<i32>,
+	pub created_at:         NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable, Insertable, AsChangeset, Serialize, Deserialize, Debug)]
+#[diesel(table_name = watch_feed_visits, primary_key(user_id))]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFeedVisit {
+	pub user_id:             i32,
+	pub last_seen_change_id: i32,
+	pub visited_at:          NaiveDateTime,
+}
+
+#[derive(Identifiable, Queryable, Serialize, Deserialize, Debug)]
+#[diesel(table_name = audit_log)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditLogEntry<'a> {
+	pub id:            i32,
+	pub timestamp:     NaiveDateTime,
+	pub actor_user_id: Option<i32>,
+	pub action:        Cow<'a, str>,
+	pub target_type:   Cow<'a, str>,
+	pub target_id:     Option<Cow<'a, str>>,
+	pub before_state:  Option<Cow<'a, str>>,
+	pub after_state:   Option<Cow<'a, str>>,
+	pub client_ip:     Option<Cow<'a, str>>,
+}
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = audit_log)]
+pub struct AuditLogEntryNew<'a> {
+	pub timestamp:     NaiveDateTime,
+	pub actor_user_id: Option<i32>,
+	pub action:        Cow<'a, str>,
+	pub target_type:   Cow<'a, str>,
+	pub target_id:     Option<Cow<'a, str>>,
+	pub before_state:  Option<Cow<'a, str>>,
+	pub after_state:   Option<Cow<'a, str>>,
+	pub client_ip:     Option<Cow<'a, str>>,
 }
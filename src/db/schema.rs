@@ -1,5 +1,187 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+	/// Representation of the `audit_log` table.
+	///
+	/// (Automatically generated by Diesel.)
+	audit_log (id) {
+		/// The `id` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `timestamp` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		timestamp -> Timestamp,
+		/// The `actor_user_id` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		actor_user_id -> Nullable<Integer>,
+		/// The `action` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		action -> Text,
+		/// The `target_type` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		target_type -> Text,
+		/// The `target_id` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		target_id -> Nullable<Text>,
+		/// The `before_state` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		before_state -> Nullable<Text>,
+		/// The `after_state` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		after_state -> Nullable<Text>,
+		/// The `client_ip` column of the `audit_log` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		client_ip -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `audit_scans` table.
+	///
+	/// (Automatically generated by Diesel.)
+	audit_scans (id) {
+		/// The `id` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `audit_session_id` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		audit_session_id -> Integer,
+		/// The `scanned_value` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		scanned_value -> Text,
+		/// The `device_key_info_id` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Nullable<Integer>,
+		/// The `scanned_by` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		scanned_by -> Integer,
+		/// The `scanned_at` column of the `audit_scans` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		scanned_at -> Timestamp,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `audit_sessions` table.
+	///
+	/// (Automatically generated by Diesel.)
+	audit_sessions (id) {
+		/// The `id` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `location_id` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		location_id -> Integer,
+		/// The `started_by` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		started_by -> Integer,
+		/// The `started_at` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		started_at -> Timestamp,
+		/// The `closed_by` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		closed_by -> Nullable<Integer>,
+		/// The `closed_at` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		closed_at -> Nullable<Timestamp>,
+		/// The `report` column of the `audit_sessions` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		report -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `calendar_feed_tokens` table.
+	///
+	/// (Automatically generated by Diesel.)
+	calendar_feed_tokens (id) {
+		/// The `id` column of the `calendar_feed_tokens` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `user_id` column of the `calendar_feed_tokens` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		user_id -> Integer,
+		/// The `value` column of the `calendar_feed_tokens` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		value -> Text,
+	}
+}
+
 diesel::table! {
 	/// Representation of the `column_definitions` table.
 	///
@@ -11,395 +193,1211 @@ diesel::table! {
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `name` column of the `column_definitions` table.
-		///
-		/// Its SQL type is `Text`.
-		///
-		/// (Automatically generated by Diesel.)
-		name -> Text,
-		/// The `ordering_key` column of the `column_definitions` table.
+		/// The `name` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		name -> Text,
+		/// The `ordering_key` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		ordering_key -> Nullable<Integer>,
+		/// The `not_null` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		not_null -> Bool,
+		/// The `unique_values` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		unique_values -> Bool,
+		/// The `show_in_main_page` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		show_in_main_page -> Bool,
+		/// The `show_on_labels` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		show_on_labels -> Bool,
+		/// The `exclusively_possible_values` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		exclusively_possible_values -> Bool,
+		/// The `default_value_id` column of the `column_definitions` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		default_value_id -> Nullable<Integer>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `column_possible_values` table.
+	///
+	/// (Automatically generated by Diesel.)
+	column_possible_values (id) {
+		/// The `id` column of the `column_possible_values` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `column_definition_id` column of the `column_possible_values` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		column_definition_id -> Integer,
+		/// The `value` column of the `column_possible_values` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		value -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `component_column_definitions` table.
+	///
+	/// (Automatically generated by Diesel.)
+	component_column_definitions (id) {
+		/// The `id` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `name` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		name -> Text,
+		/// The `ordering_key` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		ordering_key -> Nullable<Integer>,
+		/// The `not_null` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		not_null -> Bool,
+		/// The `unique_values` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		unique_values -> Bool,
+		/// The `exclusively_possible_values` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		exclusively_possible_values -> Bool,
+		/// The `default_value_id` column of the `component_column_definitions` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		default_value_id -> Nullable<Integer>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `component_column_possible_values` table.
+	///
+	/// (Automatically generated by Diesel.)
+	component_column_possible_values (id) {
+		/// The `id` column of the `component_column_possible_values` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `component_column_definition_id` column of the `component_column_possible_values` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		component_column_definition_id -> Integer,
+		/// The `value` column of the `component_column_possible_values` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		value -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_attachments` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_attachments (id) {
+		/// The `id` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `attachment_id` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		attachment_id -> Text,
+		/// The `deleted` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		deleted -> Bool,
+		/// The `description` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		description -> Text,
+		/// The `file_name` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		file_name -> Text,
+		/// The `file_data` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Binary`.
+		///
+		/// (Automatically generated by Diesel.)
+		file_data -> Binary,
+		/// The `deleted_at` column of the `device_attachments` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		deleted_at -> Nullable<Timestamp>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_bookings` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_bookings (id) {
+		/// The `id` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `user_id` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		user_id -> Integer,
+		/// The `starts_at` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		starts_at -> Timestamp,
+		/// The `ends_at` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		ends_at -> Timestamp,
+		/// The `purpose` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		purpose -> Text,
+		/// The `cancelled` column of the `device_bookings` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		cancelled -> Bool,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_change_batches` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_change_batches (id) {
+		/// The `id` column of the `device_change_batches` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `user_id` column of the `device_change_batches` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		user_id -> Integer,
+		/// The `created_at` column of the `device_change_batches` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		created_at -> Timestamp,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_changes` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_changes (id) {
+		/// The `id` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `timestamp` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		timestamp -> Timestamp,
+		/// The `done_automatically` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		done_automatically -> Bool,
+		/// The `user_id` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		user_id -> Nullable<Integer>,
+		/// The `change` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		change -> Text,
+		/// The `batch_id` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		batch_id -> Nullable<Integer>,
+		/// The `previous_hash` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		previous_hash -> Nullable<Text>,
+		/// The `hash` column of the `device_changes` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		hash -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_component_data` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_component_data (id) {
+		/// The `id` column of the `device_component_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_component_id` column of the `device_component_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_component_id -> Integer,
+		/// The `component_column_definition_id` column of the `device_component_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		component_column_definition_id -> Integer,
+		/// The `data_value` column of the `device_component_data` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		data_value -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_components` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_components (id) {
+		/// The `id` column of the `device_components` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_components` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `component_id` column of the `device_components` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		component_id -> Text,
+		/// The `deleted` column of the `device_components` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		deleted -> Bool,
+		/// The `component_type` column of the `device_components` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		component_type -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_data` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_data (id) {
+		/// The `id` column of the `device_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `column_definition_id` column of the `device_data` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		column_definition_id -> Integer,
+		/// The `data_value` column of the `device_data` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		data_value -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_key_info` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_key_info (id) {
+		/// The `id` column of the `device_key_info` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_id` column of the `device_key_info` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_id -> Text,
+		/// The `deleted` column of the `device_key_info` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		deleted -> Bool,
+		/// The `location_id` column of the `device_key_info` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		location_id -> Integer,
+		/// The `parent_device_id` column of the `device_key_info` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		parent_device_id -> Nullable<Integer>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_lifecycles` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_lifecycles (id) {
+		/// The `id` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `purchased_on` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Nullable<Date>`.
+		///
+		/// (Automatically generated by Diesel.)
+		purchased_on -> Nullable<Date>,
+		/// The `warranty_ends_on` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Nullable<Date>`.
+		///
+		/// (Automatically generated by Diesel.)
+		warranty_ends_on -> Nullable<Date>,
+		/// The `end_of_life_on` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Nullable<Date>`.
+		///
+		/// (Automatically generated by Diesel.)
+		end_of_life_on -> Nullable<Date>,
+		/// The `warranty_expiry_processed` column of the `device_lifecycles` table.
+		///
+		/// Its SQL type is `Bool`.
+		///
+		/// (Automatically generated by Diesel.)
+		warranty_expiry_processed -> Bool,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `device_loans` table.
+	///
+	/// (Automatically generated by Diesel.)
+	device_loans (id) {
+		/// The `id` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `device_key_info_id` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Integer,
+		/// The `borrower_user_id` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		borrower_user_id -> Nullable<Integer>,
+		/// The `borrower_name` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		borrower_name -> Nullable<Text>,
+		/// The `loaned_by` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		loaned_by -> Integer,
+		/// The `loaned_at` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		loaned_at -> Timestamp,
+		/// The `due_at` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		due_at -> Nullable<Timestamp>,
+		/// The `notes` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		notes -> Text,
+		/// The `returned_at` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		returned_at -> Nullable<Timestamp>,
+		/// The `returned_to` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		returned_to -> Nullable<Integer>,
+		/// The `return_notes` column of the `device_loans` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		return_notes -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `job_runs` table.
+	///
+	/// (Automatically generated by Diesel.)
+	job_runs (id) {
+		/// The `id` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `job_name` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		job_name -> Text,
+		/// The `started_at` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		started_at -> Timestamp,
+		/// The `finished_at` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		finished_at -> Nullable<Timestamp>,
+		/// The `succeeded` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Nullable<Bool>`.
+		///
+		/// (Automatically generated by Diesel.)
+		succeeded -> Nullable<Bool>,
+		/// The `triggered_by` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		triggered_by -> Nullable<Integer>,
+		/// The `output` column of the `job_runs` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		output -> Nullable<Text>,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `location_paths` view, which holds the full path of
+	/// every location.
+	///
+	/// Diesel doesn't generate views, so this is maintained by hand.
+	location_paths (id) {
+		/// The `id` column of the `location_paths` view.
+		///
+		/// Its SQL type is `Integer`.
+		id -> Integer,
+		/// The `parent_id` column of the `location_paths` view.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		parent_id -> Nullable<Integer>,
+		/// The `name` column of the `location_paths` view.
+		///
+		/// Its SQL type is `Text`.
+		name -> Text,
+		/// The `path` column of the `location_paths` view.
+		///
+		/// Its SQL type is `Text`.
+		path -> Text,
+		/// The `depth` column of the `location_paths` view.
+		///
+		/// Its SQL type is `Integer`.
+		depth -> Integer,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `locations` table.
+	///
+	/// (Automatically generated by Diesel.)
+	locations (id) {
+		/// The `id` column of the `locations` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		id -> Integer,
+		/// The `parent_id` column of the `locations` table.
 		///
 		/// Its SQL type is `Nullable<Integer>`.
 		///
 		/// (Automatically generated by Diesel.)
-		ordering_key -> Nullable<Integer>,
-		/// The `not_null` column of the `column_definitions` table.
+		parent_id -> Nullable<Integer>,
+		/// The `name` column of the `locations` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		not_null -> Bool,
-		/// The `unique_values` column of the `column_definitions` table.
+		name -> Text,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `notification_preferences` table.
+	///
+	/// (Automatically generated by Diesel.)
+	notification_preferences (user_id) {
+		/// The `user_id` column of the `notification_preferences` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		unique_values -> Bool,
-		/// The `show_in_main_page` column of the `column_definitions` table.
+		user_id -> Integer,
+		/// The `loan_overdue` column of the `notification_preferences` table.
 		///
 		/// Its SQL type is `Bool`.
 		///
 		/// (Automatically generated by Diesel.)
-		show_in_main_page -> Bool,
-		/// The `show_on_labels` column of the `column_definitions` table.
+		loan_overdue -> Bool,
+		/// The `warranty_expiring` column of the `notification_preferences` table.
 		///
 		/// Its SQL type is `Bool`.
 		///
 		/// (Automatically generated by Diesel.)
-		show_on_labels -> Bool,
-		/// The `exclusively_possible_values` column of the `column_definitions` table.
+		warranty_expiring -> Bool,
+		/// The `device_changed` column of the `notification_preferences` table.
 		///
 		/// Its SQL type is `Bool`.
 		///
 		/// (Automatically generated by Diesel.)
-		exclusively_possible_values -> Bool,
-		/// The `default_value_id` column of the `column_definitions` table.
-		///
-		/// Its SQL type is `Nullable<Integer>`.
-		///
-		/// (Automatically generated by Diesel.)
-		default_value_id -> Nullable<Integer>,
+		device_changed -> Bool,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `column_possible_values` table.
+	/// Representation of the `notifications` table.
 	///
 	/// (Automatically generated by Diesel.)
-	column_possible_values (id) {
-		/// The `id` column of the `column_possible_values` table.
+	notifications (id) {
+		/// The `id` column of the `notifications` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `column_definition_id` column of the `column_possible_values` table.
+		/// The `user_id` column of the `notifications` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		column_definition_id -> Integer,
-		/// The `value` column of the `column_possible_values` table.
+		user_id -> Integer,
+		/// The `kind` column of the `notifications` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		value -> Text,
-	}
-}
-
-diesel::table! {
-	/// Representation of the `device_attachments` table.
-	///
-	/// (Automatically generated by Diesel.)
-	device_attachments (id) {
-		/// The `id` column of the `device_attachments` table.
+		kind -> Text,
+		/// The `details` column of the `notifications` table.
 		///
-		/// Its SQL type is `Integer`.
+		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		id -> Integer,
-		/// The `device_key_info_id` column of the `device_attachments` table.
+		details -> Text,
+		/// The `dedupe_key` column of the `notifications` table.
 		///
-		/// Its SQL type is `Integer`.
+		/// Its SQL type is `Nullable<Text>`.
 		///
 		/// (Automatically generated by Diesel.)
-		device_key_info_id -> Integer,
-		/// The `attachment_id` column of the `device_attachments` table.
+		dedupe_key -> Nullable<Text>,
+		/// The `created_at` column of the `notifications` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		created_at -> Timestamp,
+		/// The `status` column of the `notifications` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		attachment_id -> Text,
-		/// The `deleted` column of the `device_attachments` table.
+		status -> Text,
+		/// The `attempts` column of the `notifications` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		deleted -> Bool,
-		/// The `description` column of the `device_attachments` table.
+		attempts -> Integer,
+		/// The `next_attempt_at` column of the `notifications` table.
 		///
-		/// Its SQL type is `Text`.
+		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		description -> Text,
-		/// The `file_name` column of the `device_attachments` table.
+		next_attempt_at -> Timestamp,
+		/// The `finished_at` column of the `notifications` table.
 		///
-		/// Its SQL type is `Text`.
+		/// Its SQL type is `Nullable<Timestamp>`.
 		///
 		/// (Automatically generated by Diesel.)
-		file_name -> Text,
-		/// The `file_data` column of the `device_attachments` table.
+		finished_at -> Nullable<Timestamp>,
+		/// The `last_error` column of the `notifications` table.
 		///
-		/// Its SQL type is `Binary`.
+		/// Its SQL type is `Nullable<Text>`.
 		///
 		/// (Automatically generated by Diesel.)
-		file_data -> Binary,
+		last_error -> Nullable<Text>,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `device_changes` table.
+	/// Representation of the `reserved_device_ids` table.
 	///
 	/// (Automatically generated by Diesel.)
-	device_changes (id) {
-		/// The `id` column of the `device_changes` table.
+	reserved_device_ids (id) {
+		/// The `id` column of the `reserved_device_ids` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `device_key_info_id` column of the `device_changes` table.
+		/// The `device_id` column of the `reserved_device_ids` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_id -> Text,
+		/// The `reserved_by` column of the `reserved_device_ids` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		device_key_info_id -> Integer,
-		/// The `timestamp` column of the `device_changes` table.
+		reserved_by -> Integer,
+		/// The `reserved_at` column of the `reserved_device_ids` table.
 		///
 		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		timestamp -> Timestamp,
-		/// The `done_automatically` column of the `device_changes` table.
+		reserved_at -> Timestamp,
+	}
+}
+
+diesel::table! {
+	/// Representation of the `saved_searches` table.
+	///
+	/// (Automatically generated by Diesel.)
+	saved_searches (id) {
+		/// The `id` column of the `saved_searches` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		done_automatically -> Bool,
-		/// The `user_id` column of the `device_changes` table.
+		id -> Integer,
+		/// The `user_id` column of the `saved_searches` table.
 		///
-		/// Its SQL type is `Nullable<Integer>`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		user_id -> Nullable<Integer>,
-		/// The `change` column of the `device_changes` table.
+		user_id -> Integer,
+		/// The `name` column of the `saved_searches` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		change -> Text,
+		name -> Text,
+		/// The `query` column of the `saved_searches` table.
+		///
+		/// Its SQL type is `Text`.
+		///
+		/// (Automatically generated by Diesel.)
+		query -> Text,
+		/// The `created_at` column of the `saved_searches` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		created_at -> Timestamp,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `device_components` table.
+	/// Representation of the `tokens` table.
 	///
 	/// (Automatically generated by Diesel.)
-	device_components (id) {
-		/// The `id` column of the `device_components` table.
+	tokens (id) {
+		/// The `id` column of the `tokens` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `device_key_info_id` column of the `device_components` table.
+		/// The `user_id` column of the `tokens` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		device_key_info_id -> Integer,
-		/// The `component_id` column of the `device_components` table.
+		user_id -> Integer,
+		/// The `value` column of the `tokens` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		component_id -> Text,
-		/// The `deleted` column of the `device_components` table.
+		value -> Text,
+		/// The `expires` column of the `tokens` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		deleted -> Bool,
-		/// The `component_type` column of the `device_components` table.
+		expires -> Timestamp,
+		/// The `valid` column of the `tokens` table.
 		///
-		/// Its SQL type is `Text`.
+		/// Its SQL type is `Bool`.
 		///
 		/// (Automatically generated by Diesel.)
-		component_type -> Text,
+		valid -> Bool,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `device_data` table.
+	/// Representation of the `user_info` table.
 	///
 	/// (Automatically generated by Diesel.)
-	device_data (id) {
-		/// The `id` column of the `device_data` table.
+	user_info (id) {
+		/// The `id` column of the `user_info` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `device_key_info_id` column of the `device_data` table.
+		/// The `source` column of the `user_info` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		device_key_info_id -> Integer,
-		/// The `column_definition_id` column of the `device_data` table.
+		source -> Integer,
+		/// The `unique_identifier` column of the `user_info` table.
 		///
-		/// Its SQL type is `Integer`.
+		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		column_definition_id -> Integer,
-		/// The `data_value` column of the `device_data` table.
+		unique_identifier -> Text,
+		/// The `display_name` column of the `user_info` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		data_value -> Text,
+		display_name -> Text,
+		/// The `associated_location_id` column of the `user_info` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		associated_location_id -> Nullable<Integer>,
+		/// The `email` column of the `user_info` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		email -> Nullable<Text>,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `device_key_info` table.
+	/// Representation of the `watch_feed_visits` table.
 	///
 	/// (Automatically generated by Diesel.)
-	device_key_info (id) {
-		/// The `id` column of the `device_key_info` table.
+	watch_feed_visits (user_id) {
+		/// The `user_id` column of the `watch_feed_visits` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		id -> Integer,
-		/// The `device_id` column of the `device_key_info` table.
-		///
-		/// Its SQL type is `Text`.
-		///
-		/// (Automatically generated by Diesel.)
-		device_id -> Text,
-		/// The `deleted` column of the `device_key_info` table.
+		user_id -> Integer,
+		/// The `last_seen_change_id` column of the `watch_feed_visits` table.
 		///
-		/// Its SQL type is `Bool`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		deleted -> Bool,
-		/// The `location_id` column of the `device_key_info` table.
+		last_seen_change_id -> Integer,
+		/// The `visited_at` column of the `watch_feed_visits` table.
 		///
-		/// Its SQL type is `Integer`.
+		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		location_id -> Integer,
+		visited_at -> Timestamp,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `locations` table.
+	/// Representation of the `watches` table.
 	///
 	/// (Automatically generated by Diesel.)
-	locations (id) {
-		/// The `id` column of the `locations` table.
+	watches (id) {
+		/// The `id` column of the `watches` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `name` column of the `locations` table.
+		/// The `user_id` column of the `watches` table.
 		///
-		/// Its SQL type is `Text`.
+		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		name -> Text,
+		user_id -> Integer,
+		/// The `device_key_info_id` column of the `watches` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		device_key_info_id -> Nullable<Integer>,
+		/// The `location_id` column of the `watches` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		location_id -> Nullable<Integer>,
+		/// The `saved_search_id` column of the `watches` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		saved_search_id -> Nullable<Integer>,
+		/// The `created_at` column of the `watches` table.
+		///
+		/// Its SQL type is `Timestamp`.
+		///
+		/// (Automatically generated by Diesel.)
+		created_at -> Timestamp,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `tokens` table.
+	/// Representation of the `webhook_deliveries` table.
 	///
 	/// (Automatically generated by Diesel.)
-	tokens (id) {
-		/// The `id` column of the `tokens` table.
+	webhook_deliveries (id) {
+		/// The `id` column of the `webhook_deliveries` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `user_id` column of the `tokens` table.
+		/// The `webhook_outbox_id` column of the `webhook_deliveries` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		user_id -> Integer,
-		/// The `value` column of the `tokens` table.
+		webhook_outbox_id -> Integer,
+		/// The `webhook_name` column of the `webhook_deliveries` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		value -> Text,
-		/// The `expires` column of the `tokens` table.
+		webhook_name -> Text,
+		/// The `attempt` column of the `webhook_deliveries` table.
+		///
+		/// Its SQL type is `Integer`.
+		///
+		/// (Automatically generated by Diesel.)
+		attempt -> Integer,
+		/// The `attempted_at` column of the `webhook_deliveries` table.
 		///
 		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		expires -> Timestamp,
-		/// The `valid` column of the `tokens` table.
+		attempted_at -> Timestamp,
+		/// The `status_code` column of the `webhook_deliveries` table.
+		///
+		/// Its SQL type is `Nullable<Integer>`.
+		///
+		/// (Automatically generated by Diesel.)
+		status_code -> Nullable<Integer>,
+		/// The `error` column of the `webhook_deliveries` table.
+		///
+		/// Its SQL type is `Nullable<Text>`.
+		///
+		/// (Automatically generated by Diesel.)
+		error -> Nullable<Text>,
+		/// The `succeeded` column of the `webhook_deliveries` table.
 		///
 		/// Its SQL type is `Bool`.
 		///
 		/// (Automatically generated by Diesel.)
-		valid -> Bool,
+		succeeded -> Bool,
 	}
 }
 
 diesel::table! {
-	/// Representation of the `user_info` table.
+	/// Representation of the `webhook_outbox` table.
 	///
 	/// (Automatically generated by Diesel.)
-	user_info (id) {
-		/// The `id` column of the `user_info` table.
+	webhook_outbox (id) {
+		/// The `id` column of the `webhook_outbox` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
 		id -> Integer,
-		/// The `source` column of the `user_info` table.
+		/// The `device_change_id` column of the `webhook_outbox` table.
 		///
 		/// Its SQL type is `Integer`.
 		///
 		/// (Automatically generated by Diesel.)
-		source -> Integer,
-		/// The `unique_identifier` column of the `user_info` table.
+		device_change_id -> Integer,
+		/// The `event` column of the `webhook_outbox` table.
 		///
 		/// Its SQL type is `Text`.
 		///
 		/// (Automatically generated by Diesel.)
-		unique_identifier -> Text,
-		/// The `display_name` column of the `user_info` table.
+		event -> Text,
+		/// The `created_at` column of the `webhook_outbox` table.
 		///
-		/// Its SQL type is `Text`.
+		/// Its SQL type is `Timestamp`.
 		///
 		/// (Automatically generated by Diesel.)
-		display_name -> Text,
-		/// The `associated_location_id` column of the `user_info` table.
+		created_at -> Timestamp,
+		/// The `completed_at` column of the `webhook_outbox` table.
 		///
-		/// Its SQL type is `Nullable<Integer>`.
+		/// Its SQL type is `Nullable<Timestamp>`.
 		///
 		/// (Automatically generated by Diesel.)
-		associated_location_id -> Nullable<Integer>,
+		completed_at -> Nullable<Timestamp>,
+		/// The `next_attempt_at` column of the `webhook_outbox` table.
+		///
+		/// Its SQL type is `Nullable<Timestamp>`.
+		///
+		/// (Automatically generated by Diesel.)
+		next_attempt_at -> Nullable<Timestamp>,
 	}
 }
 
+diesel::joinable!(audit_log -> user_info (actor_user_id));
+diesel::joinable!(audit_scans -> audit_sessions (audit_session_id));
+diesel::joinable!(audit_scans -> device_key_info (device_key_info_id));
+diesel::joinable!(audit_sessions -> location_paths (location_id));
+diesel::joinable!(audit_sessions -> locations (location_id));
+diesel::joinable!(calendar_feed_tokens -> user_info (user_id));
 diesel::joinable!(column_possible_values -> column_definitions (column_definition_id));
+diesel::joinable!(component_column_possible_values -> component_column_definitions (component_column_definition_id));
 diesel::joinable!(device_attachments -> device_key_info (device_key_info_id));
+diesel::joinable!(device_bookings -> device_key_info (device_key_info_id));
+diesel::joinable!(device_bookings -> user_info (user_id));
+diesel::joinable!(device_changes -> device_change_batches (batch_id));
 diesel::joinable!(device_changes -> device_key_info (device_key_info_id));
 diesel::joinable!(device_changes -> user_info (user_id));
+diesel::joinable!(device_component_data -> component_column_definitions (component_column_definition_id));
+diesel::joinable!(device_component_data -> device_components (device_component_id));
 diesel::joinable!(device_components -> device_key_info (device_key_info_id));
 diesel::joinable!(device_data -> column_definitions (column_definition_id));
 diesel::joinable!(device_data -> device_key_info (device_key_info_id));
+diesel::joinable!(device_key_info -> location_paths (location_id));
 diesel::joinable!(device_key_info -> locations (location_id));
+diesel::joinable!(device_lifecycles -> device_key_info (device_key_info_id));
+diesel::joinable!(device_loans -> device_key_info (device_key_info_id));
+diesel::joinable!(notification_preferences -> user_info (user_id));
+diesel::joinable!(notifications -> user_info (user_id));
+diesel::joinable!(reserved_device_ids -> user_info (reserved_by));
+diesel::joinable!(saved_searches -> user_info (user_id));
 diesel::joinable!(tokens -> user_info (user_id));
 diesel::joinable!(user_info -> locations (associated_location_id));
+diesel::joinable!(watch_feed_visits -> user_info (user_id));
+diesel::joinable!(watches -> device_key_info (device_key_info_id));
+diesel::joinable!(watches -> locations (location_id));
+diesel::joinable!(watches -> saved_searches (saved_search_id));
+diesel::joinable!(watches -> user_info (user_id));
+diesel::joinable!(webhook_deliveries -> webhook_outbox (webhook_outbox_id));
+diesel::joinable!(webhook_outbox -> device_changes (device_change_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+	audit_log,
+	audit_scans,
+	audit_sessions,
+	calendar_feed_tokens,
 	column_definitions,
 	column_possible_values,
+	component_column_definitions,
+	component_column_possible_values,
 	device_attachments,
+	device_bookings,
+	device_change_batches,
 	device_changes,
+	device_component_data,
 	device_components,
 	device_data,
 	device_key_info,
+	device_lifecycles,
+	device_loans,
+	job_runs,
+	location_paths,
 	locations,
+	notification_preferences,
+	notifications,
+	reserved_device_ids,
+	saved_searches,
 	tokens,
 	user_info,
+	watch_feed_visits,
+	watches,
+	webhook_deliveries,
+	webhook_outbox,
 );
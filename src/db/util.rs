@@ -4,7 +4,9 @@
 // Uses
 use diesel::{
 	dsl::{exists, not},
+	result::OptionalExtension,
 	select,
+	BoolExpressionMethods,
 	ExpressionMethods,
 	QueryDsl,
 	RunQueryDsl,
@@ -60,3 +62,64 @@ pub fn data_value_exists(
 		.get_result::<bool>(conn)
 		.with_context("unable to query the database for data value existence")
 }
+
+/// Checks if a `device_component_data` value exists already.
+///
+/// `component_opt` is the device ID and component ID of the component being
+/// edited, if any.
+pub fn component_data_value_exists(
+	conn: &mut SqliteConnection,
+	column_id: i32,
+	component_opt: Option<(&str, &str)>,
+	value: &str,
+) -> Result<bool, Error> {
+	use schema::{
+		device_component_data::dsl::*,
+		device_components::dsl::*,
+		device_key_info::dsl::*,
+	};
+
+	let mut sub_query = device_component_data
+		.inner_join(device_components.inner_join(device_key_info))
+		.filter(schema::device_key_info::dsl::deleted.eq(false))
+		.filter(schema::device_components::dsl::deleted.eq(false))
+		.filter(component_column_definition_id.eq(column_id))
+		.filter(data_value.eq(value))
+		.into_boxed();
+
+	// Ensure that the value for the current component doesn't count as a match
+	if let Some((device, component)) = component_opt {
+		sub_query = sub_query.filter(not(device_id.eq(device).and(component_id.eq(component))));
+	}
+
+	select(exists(sub_query))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for component data value existence")
+}
+
+/// Checks whether a location is the same as, or a descendant of, another one,
+/// by walking up its ancestors.
+pub fn location_is_within(
+	conn: &mut SqliteConnection,
+	location: i32,
+	ancestor: i32,
+) -> Result<bool, Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	let mut current = Some(location);
+	while let Some(current_id) = current {
+		if current_id == ancestor {
+			return Ok(true);
+		}
+		current = locations
+			.filter(id.eq(current_id))
+			.select(parent_id)
+			.get_result::<Option<i32>>(conn)
+			.optional()
+			.with_context("unable to query the database for a location's parent")?
+			.flatten();
+	}
+
+	Ok(false)
+}
@@ -11,9 +11,10 @@ use rocket::{
 	http::Status,
 	response::{
 		self,
-		status::{BadRequest, NotFound},
+		status::{BadRequest, Custom, NotFound},
 		Responder,
 	},
+	serde::json::{json, Value as JsonValue},
 	Request,
 };
 use thiserror::Error;
@@ -56,7 +57,13 @@ pub enum UserError {
 	#[error("{0}")]
 	BadRequest(&'static str),
 	#[error("{0}")]
+	Forbidden(&'static str),
+	#[error("{0}")]
 	NotFound(&'static str),
+	/// The request was based on data that has changed since. The details of
+	/// what changed are sent along with the message.
+	#[error("{0}")]
+	Conflict(&'static str, JsonValue),
 }
 
 /// The [`InternalError`] type, with context.
@@ -154,7 +161,13 @@ impl<'r, 'o: 'r> Responder<'r, 'o> for UserError {
 	fn respond_to(self, request: &Request<'_>) -> response::Result<'o> {
 		match self {
 			UserError::BadRequest(message) => BadRequest(Some(message)).respond_to(request),
+			UserError::Forbidden(message) => Custom(Status::Forbidden, message).respond_to(request),
 			UserError::NotFound(message) => NotFound(Some(message)).respond_to(request),
+			UserError::Conflict(message, details) => Custom(
+				Status::Conflict,
+				json!({ "message": message, "details": details }),
+			)
+			.respond_to(request),
 		}
 	}
 }
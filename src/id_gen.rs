@@ -5,14 +5,20 @@
 // Uses
 use rand::{distributions::Distribution, thread_rng, Rng};
 
-use crate::error::Error;
+use crate::error::{Error, InternalError};
+
+// Constants
+/// The most IDs to try before giving up, which only happens when almost every
+/// ID of the requested length is already in use.
+const MAX_ID_ATTEMPTS: usize = 1000;
 
 /// A completely generic ID generator that generates unused IDs from whatever
 /// `distribution` is provided, as long as the `distribution` provides values
 /// that can be converted to `char` values infallibly.
 ///
 /// It verifies that the new ID is unused by executing the predicate
-/// `should_use`. This can query a database or whatever else.
+/// `should_use`. This can query a database or whatever else. If no unused ID
+/// turns up within [`MAX_ID_ATTEMPTS`] tries, it returns an error.
 pub fn gen_new_id<D, I, P>(
 	distribution: D,
 	length: usize,
@@ -26,7 +32,7 @@ where
 	let mut rng = thread_rng();
 
 	let mut new_id = String::with_capacity(length);
-	loop {
+	for _ in 0..MAX_ID_ATTEMPTS {
 		// Generate the new ID
 		new_id.clear();
 		(&mut rng)
@@ -37,11 +43,11 @@ where
 
 		// Verify that the ID can be used
 		if !should_use(new_id.as_str())? {
-			break;
+			return Ok(new_id);
 		}
 	}
 
-	Ok(new_id)
+	Err(InternalError::Assertion("no unused ID could be found").into())
 }
 
 // Distributions
@@ -55,21 +61,43 @@ where
 pub struct NumericAscii;
 
 impl NumericAscii {
+	pub const CHARSET: &'static [u8] = b"0123456789";
 	pub const RANGE: u32 = 10;
 }
 
 impl Distribution<u8> for NumericAscii {
 	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
-		const GEN_CHARSET: &[u8] = b"0123456789";
 		loop {
 			let var = rng.next_u32() >> (32 - 4); // 4 bits needed to represent values up to 10 (2^4 = 16)
 			if var < Self::RANGE {
-				return GEN_CHARSET[var as usize];
+				return Self::CHARSET[var as usize];
 			}
 		}
 	}
 }
 
+/// Provides ASCII characters from
+/// [Crockford's Base32](https://www.crockford.com/base32.html) set, which
+/// leaves out letters that are easily mistaken for others. (I, L, O, and U)
+///
+/// Largely copied from [`Alphanumeric`].
+///
+/// [`Alphanumeric`]: rand::distributions::Alphanumeric
+#[derive(Debug, Copy, Clone)]
+pub struct CrockfordBase32;
+
+impl CrockfordBase32 {
+	pub const CHARSET: &'static [u8] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+	pub const RANGE: u32 = 32;
+}
+
+impl Distribution<u8> for CrockfordBase32 {
+	fn sample<R: Rng + ?Sized>(&self, rng: &mut R) -> u8 {
+		// 5 bits needed to represent values up to 32 (2^5 = 32)
+		Self::CHARSET[(rng.next_u32() >> (32 - 5)) as usize]
+	}
+}
+
 /// Provides ASCII Base64 characters from the
 /// [Bcrypt](https://docs.rs/base64/latest/base64/enum.CharacterSet.html#variant.Bcrypt)
 /// set.
@@ -94,3 +122,109 @@ impl Distribution<u8> for Base64 {
 		GEN_CHARSET[(rng.next_u32() >> (32 - 6)) as usize]
 	}
 }
+
+// Check Digits
+
+/// Calculates a check character for `payload` using the
+/// [Luhn mod N](https://en.wikipedia.org/wiki/Luhn_mod_N_algorithm) algorithm,
+/// where N is the size of `charset`.
+///
+/// With a numeric `charset`, this is the same as the standard Luhn algorithm.
+///
+/// Returns `None` if `payload` contains characters that aren't in `charset`.
+pub fn luhn_mod_n(charset: &[u8], payload: &str) -> Option<u8> {
+	let n = charset.len() as u32;
+
+	// Every second code point is doubled, starting from the rightmost one, and the
+	// digits of the result (in base N) are summed
+	let mut sum = 0;
+	for (i, c) in payload.bytes().rev().enumerate() {
+		let mut code_point = charset.iter().position(|charset_c| *charset_c == c)? as u32;
+		if i % 2 == 0 {
+			code_point *= 2;
+			code_point = code_point / n + code_point % n;
+		}
+		sum += code_point;
+	}
+
+	Some(charset[((n - sum % n) % n) as usize])
+}
+
+/// Calculates a check digit for `payload` using the
+/// [Damm](https://en.wikipedia.org/wiki/Damm_algorithm) algorithm, which catches
+/// all single-digit errors and adjacent transpositions.
+///
+/// Returns `None` if `payload` contains anything other than ASCII digits.
+pub fn damm(payload: &str) -> Option<u8> {
+	/// A totally anti-symmetric quasigroup of order 10.
+	const TABLE: [[u8; 10]; 10] = [
+		[0, 3, 1, 7, 5, 9, 8, 6, 4, 2],
+		[7, 0, 9, 2, 1, 5, 4, 8, 6, 3],
+		[4, 2, 0, 6, 8, 7, 1, 3, 5, 9],
+		[1, 7, 5, 0, 9, 8, 3, 4, 2, 6],
+		[6, 1, 2, 3, 0, 4, 5, 9, 7, 8],
+		[3, 6, 7, 4, 2, 0, 9, 5, 8, 1],
+		[5, 8, 6, 9, 7, 2, 0, 1, 3, 4],
+		[8, 9, 4, 5, 3, 6, 2, 0, 1, 7],
+		[9, 4, 3, 8, 6, 1, 7, 2, 0, 5],
+		[2, 5, 8, 1, 4, 3, 6, 7, 9, 0],
+	];
+
+	let mut interim = 0;
+	for c in payload.bytes() {
+		if !c.is_ascii_digit() {
+			return None;
+		}
+		interim = TABLE[interim as usize][(c - b'0') as usize];
+	}
+
+	Some(b'0' + interim)
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use super::*;
+
+	#[test]
+	fn luhn_matches_published_values() {
+		assert_eq!(luhn_mod_n(NumericAscii::CHARSET, "7992739871"), Some(b'3'));
+		assert_eq!(luhn_mod_n(b"abcdef", "abcdef"), Some(b'e'));
+		assert_eq!(luhn_mod_n(NumericAscii::CHARSET, "12A4"), None);
+	}
+
+	#[test]
+	fn damm_matches_published_values() {
+		assert_eq!(damm("572"), Some(b'4'));
+		assert_eq!(damm("5724"), Some(b'0'));
+		assert_eq!(damm("57A"), None);
+	}
+
+	#[test]
+	fn gen_new_id_skips_used_ids() {
+		let mut attempts = 0;
+		let new_id = gen_new_id(CrockfordBase32, 8, |_| {
+			attempts += 1;
+			Ok(attempts < 3)
+		})
+		.unwrap();
+
+		assert_eq!(attempts, 3);
+		assert_eq!(new_id.len(), 8);
+		assert!(new_id
+			.bytes()
+			.all(|c| CrockfordBase32::CHARSET.contains(&c)));
+	}
+
+	#[test]
+	fn gen_new_id_gives_up_when_every_id_is_used() {
+		let mut attempts = 0;
+		let result = gen_new_id(NumericAscii, 1, |_| {
+			attempts += 1;
+			Ok(true)
+		});
+
+		assert!(result.is_err());
+		assert_eq!(attempts, MAX_ID_ATTEMPTS);
+	}
+}
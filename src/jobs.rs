@@ -0,0 +1,442 @@
+//! Background jobs, which run on a schedule or when triggered manually.
+//!
+//! Every run is recorded in `job_runs`, along with its output.
+
+// Uses
+use std::{borrow::Cow, time::Duration};
+
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use diesel::{
+	delete,
+	dsl::count_star,
+	insert_into,
+	result::OptionalExtension,
+	sql_query,
+	sql_types::{Integer, Nullable, Text},
+	update,
+	BoolExpressionMethods,
+	Connection,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	serde::json::{json, Value as JsonValue},
+	tokio::{spawn, time::interval},
+	Orbit,
+	Rocket,
+};
+use serde_json::to_string as to_json_string;
+
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	config::{JobSchedule, JobSettings, LifecycleSettings, RetentionSettings},
+	db::{
+		change_chain::verify_change_chain,
+		models::{JobRun, JobRunNew},
+		schema,
+		util::fetch_new_rowid_on,
+		DbPool,
+	},
+	error::{Context, Error},
+	lifecycle::{apply_warranty_expiry_transitions, load_expiry_report},
+	notifications::{
+		enqueue_notification,
+		enqueue_warranty_expiring_notifications,
+		NotificationKind,
+	},
+	purge::apply_retention_policy,
+};
+
+// Constants
+/// How often the scheduler checks for jobs that are due.
+const SCHEDULER_TICK: Duration = Duration::from_mins(1);
+
+/// A background job.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Job {
+	TokenCleanup,
+	OverdueLoans,
+	WarrantyExpiry,
+	IntegrityCheck,
+	LifecycleReport,
+	Retention,
+}
+
+impl Job {
+	pub const ALL: [Self; 6] = [
+		Self::TokenCleanup,
+		Self::OverdueLoans,
+		Self::WarrantyExpiry,
+		Self::IntegrityCheck,
+		Self::LifecycleReport,
+		Self::Retention,
+	];
+
+	/// The name the job is stored and requested by, which matches its key in
+	/// the config.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::TokenCleanup => "token_cleanup",
+			Self::OverdueLoans => "overdue_loans",
+			Self::WarrantyExpiry => "warranty_expiry",
+			Self::IntegrityCheck => "integrity_check",
+			Self::LifecycleReport => "lifecycle_report",
+			Self::Retention => "retention",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|job| job.name() == name)
+	}
+
+	pub fn schedule(self, settings: &JobSettings) -> &JobSchedule {
+		match self {
+			Self::TokenCleanup => &settings.token_cleanup,
+			Self::OverdueLoans => &settings.overdue_loans,
+			Self::WarrantyExpiry => &settings.warranty_expiry,
+			Self::IntegrityCheck => &settings.integrity_check,
+			Self::LifecycleReport => &settings.lifecycle_report,
+			Self::Retention => &settings.retention,
+		}
+	}
+
+	/// Does the actual work of the job, returning a summary of what it did.
+	fn perform(
+		self,
+		conn: &mut SqliteConnection,
+		lifecycle_settings: &LifecycleSettings,
+		retention_settings: &RetentionSettings,
+	) -> Result<JsonValue, Error> {
+		match self {
+			Self::TokenCleanup => clean_up_tokens(conn),
+			Self::OverdueLoans => check_overdue_loans(conn),
+			Self::WarrantyExpiry => {
+				let transitioned_device_ids = apply_warranty_expiry_transitions(
+					conn,
+					lifecycle_settings,
+					Utc::now().date_naive(),
+					None,
+				)?;
+				Ok(json!({ "deviceIds": transitioned_device_ids }))
+			}
+			Self::IntegrityCheck => check_integrity(conn),
+			Self::LifecycleReport => {
+				let today = Utc::now().date_naive();
+				let report =
+					load_expiry_report(conn, today, lifecycle_settings.expiry_warning_days)?;
+				let queued_count =
+					enqueue_warranty_expiring_notifications(conn, &report.warranty_expiring)?;
+				Ok(json!({
+					"today": today,
+					"report": report,
+					"notificationsQueued": queued_count,
+				}))
+			}
+			Self::Retention => {
+				apply_retention_policy(conn, retention_settings, Utc::now().naive_utc())
+			}
+		}
+	}
+}
+
+/// A problem found by `PRAGMA integrity_check`.
+#[derive(QueryableByName, Debug)]
+struct IntegrityCheckEntry {
+	#[diesel(sql_type = Text)]
+	integrity_check: String,
+}
+/// A broken reference found by `PRAGMA foreign_key_check`.
+#[derive(QueryableByName, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ForeignKeyCheckEntry {
+	#[diesel(sql_type = Text)]
+	table:  String,
+	#[diesel(sql_type = Nullable<Integer>)]
+	rowid:  Option<i32>,
+	#[diesel(sql_type = Text)]
+	parent: String,
+}
+
+/// Runs a job and records the run, whether it succeeds or not.
+///
+/// `triggered_by` is the user who triggered the run manually, if any.
+pub fn run_job<'a>(
+	conn: &mut SqliteConnection,
+	job: Job,
+	lifecycle_settings: &LifecycleSettings,
+	retention_settings: &RetentionSettings,
+	triggered_by_value: Option<i32>,
+) -> Result<JobRun<'a>, Error> {
+	// Uses
+	use schema::job_runs::dsl::*;
+
+	insert_into(job_runs)
+		.values(JobRunNew {
+			job_name:     Cow::from(job.name()),
+			started_at:   Utc::now().naive_utc(),
+			triggered_by: triggered_by_value,
+		})
+		.execute(conn)
+		.with_context("unable to insert into job_runs")?;
+	let run_id = fetch_new_rowid_on(conn).with_context("unable to get the ID of the new run")?;
+
+	let (run_succeeded, run_output) = match conn
+		.transaction::<_, Error, _>(|tc| job.perform(tc, lifecycle_settings, retention_settings))
+	{
+		Ok(job_output) => (true, job_output),
+		Err(e) => (false, json!({ "error": e.to_string() })),
+	};
+	let serialised_output =
+		to_json_string(&run_output).with_context("unable to serialise the job output to JSON")?;
+
+	update(job_runs.filter(id.eq(run_id)))
+		.set((
+			finished_at.eq(Utc::now().naive_utc()),
+			succeeded.eq(run_succeeded),
+			output.eq(serialised_output),
+		))
+		.execute(conn)
+		.with_context("unable to update job_runs")?;
+
+	job_runs
+		.filter(id.eq(run_id))
+		.get_result::<JobRun<'_>>(conn)
+		.with_context("unable to load the job run")
+}
+
+/// Fetches the most recent run of a job, if it's ever been run.
+pub fn load_last_job_run<'a>(
+	conn: &mut SqliteConnection,
+	job: Job,
+) -> Result<Option<JobRun<'a>>, Error> {
+	// Uses
+	use schema::job_runs::dsl::*;
+
+	job_runs
+		.filter(job_name.eq(job.name()))
+		.order_by(started_at.desc())
+		.first::<JobRun<'_>>(conn)
+		.optional()
+		.with_context("unable to load the last job run")
+}
+
+/// Runs every scheduled job that's due.
+fn run_due_jobs(
+	conn: &mut SqliteConnection,
+	job_settings: &JobSettings,
+	lifecycle_settings: &LifecycleSettings,
+	retention_settings: &RetentionSettings,
+	now: NaiveDateTime,
+) -> Result<(), Error> {
+	for job in Job::ALL {
+		let schedule = job.schedule(job_settings);
+		if !schedule.enabled {
+			continue;
+		}
+
+		let is_due = load_last_job_run(conn, job)?.is_none_or(|last_run| {
+			last_run.started_at + ChronoDuration::minutes(i64::from(schedule.interval_minutes))
+				<= now
+		});
+		if is_due {
+			run_job(conn, job, lifecycle_settings, retention_settings, None)?;
+		}
+	}
+
+	Ok(())
+}
+
+/// Starts the scheduler in the background, if it's enabled.
+///
+/// A database connection is only taken from the pool for each tick, so that
+/// the scheduler doesn't keep one out of use while it's idle.
+pub async fn start_scheduler(rocket: &Rocket<Orbit>) {
+	let (Ok(job_settings), Ok(lifecycle_settings), Ok(retention_settings)) = (
+		rocket.figment().extract_inner::<JobSettings>("jobs"),
+		rocket
+			.figment()
+			.extract_inner::<LifecycleSettings>("lifecycle"),
+		rocket
+			.figment()
+			.extract_inner::<RetentionSettings>("retention"),
+	) else {
+		eprintln!("unable to load the job settings");
+		return;
+	};
+	if !job_settings.enabled {
+		return;
+	}
+
+	let Some(pool) = DbPool::get_from(rocket) else {
+		eprintln!("the database connection pool isn't attached");
+		return;
+	};
+
+	spawn(async move {
+		let mut ticker = interval(SCHEDULER_TICK);
+		loop {
+			ticker.tick().await;
+
+			let Some(conn) = pool.get_one().await else {
+				eprintln!("unable to get a database connection for the job scheduler");
+				continue;
+			};
+			let job_settings = job_settings.clone();
+			let lifecycle_settings = lifecycle_settings.clone();
+			let retention_settings = retention_settings.clone();
+			if let Err(e) = conn
+				.run(move |c| {
+					run_due_jobs(
+						c,
+						&job_settings,
+						&lifecycle_settings,
+						&retention_settings,
+						Utc::now().naive_utc(),
+					)
+				})
+				.await
+			{
+				eprintln!("unable to run the scheduled jobs: {e}");
+			}
+		}
+	});
+}
+
+/// Removes login tokens that have expired or been invalidated, recording the
+/// removal for each user they belonged to.
+fn clean_up_tokens(conn: &mut SqliteConnection) -> Result<JsonValue, Error> {
+	// Uses
+	use schema::tokens::dsl::*;
+
+	let removable_tokens = tokens.filter(expires.le(Utc::now().naive_utc()).or(valid.eq(false)));
+	let removed_per_user = removable_tokens
+		.group_by(user_id)
+		.select((user_id, count_star()))
+		.load::<(i32, i64)>(conn)
+		.with_context("unable to count the tokens to remove")?;
+	let removed_count = delete(removable_tokens)
+		.execute(conn)
+		.with_context("unable to delete from tokens")?;
+
+	for (token_user_id, token_count) in removed_per_user {
+		record_audit_event(
+			conn,
+			AuditEvent {
+				actor:     None,
+				action:    AuditAction::TokenRevoke,
+				target:    AuditTarget::User(Some(token_user_id)),
+				before:    Some(json!({ "kind": "login", "count": token_count })),
+				after:     None,
+				client_ip: None,
+			},
+		)?;
+	}
+
+	Ok(json!({ "removedTokens": removed_count }))
+}
+
+/// Finds the loans that are past their due date, and lets each borrower who's
+/// a user know. Borrowers are only notified once per loan.
+fn check_overdue_loans(conn: &mut SqliteConnection) -> Result<JsonValue, Error> {
+	// Uses
+	use schema::{device_key_info::dsl::*, device_loans::dsl::*};
+
+	let overdue_results = device_loans
+		.inner_join(device_key_info)
+		.filter(returned_at.is_null())
+		.filter(due_at.lt(Utc::now().naive_utc()))
+		.order_by(due_at)
+		.select((
+			schema::device_loans::dsl::id,
+			device_id,
+			borrower_user_id,
+			borrower_name,
+			due_at,
+		))
+		.load::<(
+			i32,
+			String,
+			Option<i32>,
+			Option<String>,
+			Option<NaiveDateTime>,
+		)>(conn)
+		.with_context("unable to load the overdue loans")?;
+
+	let mut queued_count = 0;
+	for (loan_id, loan_device_id, loan_borrower_user_id, _, loan_due_at) in &overdue_results {
+		let (Some(loan_borrower_user_id), Some(loan_due_at)) = (loan_borrower_user_id, loan_due_at)
+		else {
+			continue;
+		};
+		let queued = enqueue_notification(
+			conn,
+			*loan_borrower_user_id,
+			NotificationKind::LoanOverdue,
+			&json!({
+				"device_id": loan_device_id,
+				"due_at": loan_due_at.format("%Y-%m-%d %H:%M UTC").to_string(),
+			}),
+			Some(format!("loan_overdue:{loan_id}").as_str()),
+		)?;
+		if queued {
+			queued_count += 1;
+		}
+	}
+
+	Ok(json!({
+		"notificationsQueued": queued_count,
+		"overdueLoans": overdue_results
+			.into_iter()
+			.map(|(loan_id, loan_device_id, loan_borrower_user_id, loan_borrower_name, loan_due_at)| {
+				json!({
+					"loanId": loan_id,
+					"deviceId": loan_device_id,
+					"borrowerUserId": loan_borrower_user_id,
+					"borrowerName": loan_borrower_name,
+					"dueAt": loan_due_at,
+				})
+			})
+			.collect::<Vec<_>>(),
+	}))
+}
+
+/// Checks the database file for corruption, for rows that refer to rows that
+/// don't exist, and for device changes that have been tampered with.
+fn check_integrity(conn: &mut SqliteConnection) -> Result<JsonValue, Error> {
+	let integrity_problems = sql_query("PRAGMA integrity_check;")
+		.load::<IntegrityCheckEntry>(conn)
+		.with_context("unable to check the database integrity")?
+		.into_iter()
+		.map(|entry| entry.integrity_check)
+		.filter(|message| message != "ok")
+		.collect::<Vec<_>>();
+	let foreign_key_problems = sql_query("PRAGMA foreign_key_check;")
+		.load::<ForeignKeyCheckEntry>(conn)
+		.with_context("unable to check the database foreign keys")?;
+	let change_chain = verify_change_chain(conn)?;
+
+	if !integrity_problems.is_empty() || !foreign_key_problems.is_empty() {
+		eprintln!(
+			"the database integrity check found {} problem(s) and {} broken reference(s)",
+			integrity_problems.len(),
+			foreign_key_problems.len()
+		);
+	}
+	if let Some(broken_link) = &change_chain.first_broken_link {
+		eprintln!(
+			"the device change log's hash chain is broken at change {}",
+			broken_link.change_id
+		);
+	}
+
+	Ok(json!({
+		"healthy": integrity_problems.is_empty()
+			&& foreign_key_problems.is_empty()
+			&& change_chain.valid,
+		"integrityProblems": integrity_problems,
+		"foreignKeyProblems": foreign_key_problems,
+		"changeChain": change_chain,
+	}))
+}
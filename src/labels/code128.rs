@@ -0,0 +1,114 @@
+//! A minimal Code 128 barcode encoder.
+//!
+//! Only code set B is used, since it covers all printable ASCII characters and
+//! device IDs are short enough that the density gains from code set C don't
+//! matter.
+
+// Constants
+/// The bar/space widths for each symbol value, in modules.
+///
+/// Every pattern starts with a bar, and alternates between bars and spaces.
+const PATTERNS: [&[u8]; 107] = [
+	b"212222", b"222122", b"222221", b"121223", b"121322", b"131222", b"122213", b"122312",
+	b"132212", b"221213", b"221312", b"231212", b"112232", b"122132", b"122231", b"113222",
+	b"123122", b"123221", b"223211", b"221132", b"221231", b"213212", b"223112", b"312131",
+	b"311222", b"321122", b"321221", b"312212", b"322112", b"322211", b"212123", b"212321",
+	b"232121", b"111323", b"131123", b"131321", b"112313", b"132113", b"132311", b"211313",
+	b"231113", b"231311", b"112133", b"112331", b"132131", b"113123", b"113321", b"133121",
+	b"313121", b"211331", b"231131", b"213113", b"213311", b"213131", b"311123", b"311321",
+	b"331121", b"312113", b"312311", b"332111", b"314111", b"221411", b"431111", b"111224",
+	b"111422", b"121124", b"121421", b"141122", b"141221", b"112214", b"112412", b"122114",
+	b"122411", b"142112", b"142211", b"241211", b"221114", b"413111", b"241112", b"134111",
+	b"111242", b"121142", b"121241", b"114212", b"124112", b"124211", b"411212", b"421112",
+	b"421211", b"212141", b"214121", b"412121", b"111143", b"111341", b"131141", b"114113",
+	b"114311", b"411113", b"411311", b"113141", b"114131", b"311141", b"411131", b"211412",
+	b"211214", b"211232", b"2331112",
+];
+const START_B: usize = 104;
+const STOP: usize = 106;
+const CHECKSUM_MODULO: usize = 103;
+
+/// Encodes `data` as a Code 128 barcode.
+///
+/// The result is the list of modules from left to right, where `true` is a bar
+/// and `false` is a space. Quiet zones are not included.
+///
+/// Returns `None` if `data` contains characters that aren't printable ASCII.
+pub fn encode(data: &str) -> Option<Vec<bool>> {
+	// Convert the data to code set B symbol values
+	let mut symbols = Vec::with_capacity(data.len() + 3);
+	symbols.push(START_B);
+	for c in data.chars() {
+		if !(' '..='~').contains(&c) {
+			return None;
+		}
+		symbols.push(c as usize - ' ' as usize);
+	}
+
+	// Calculate the checksum - the start symbol has a weight of 1, same as the
+	// first data symbol
+	let checksum = symbols
+		.iter()
+		.enumerate()
+		.map(|(i, symbol)| i.max(1) * symbol)
+		.sum::<usize>()
+		% CHECKSUM_MODULO;
+	symbols.push(checksum);
+	symbols.push(STOP);
+
+	// Expand the symbols to modules
+	let mut modules = Vec::new();
+	for symbol in symbols {
+		for (i, width) in PATTERNS[symbol].iter().enumerate() {
+			let is_bar = i % 2 == 0;
+			for _ in 0..(width - b'0') {
+				modules.push(is_bar);
+			}
+		}
+	}
+
+	Some(modules)
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use super::*;
+
+	/// Converts modules back to the widths of each bar and space.
+	fn module_widths(modules: &[bool]) -> String {
+		let mut widths = String::new();
+		let mut run_length = 0;
+		for (i, is_bar) in modules.iter().enumerate() {
+			run_length += 1;
+			if modules.get(i + 1) != Some(is_bar) {
+				widths.push_str(run_length.to_string().as_str());
+				run_length = 0;
+			}
+		}
+
+		widths
+	}
+
+	#[test]
+	fn encodes_with_checksum_and_stop() {
+		// Start B, "PJJ123C", the checksum (55), then the stop symbol
+		let expected_widths = [
+			"211214", "313121", "112133", "112133", "123221", "223211", "221132", "131321",
+			"311321", "2331112",
+		]
+		.concat();
+
+		let modules = encode("PJJ123C").unwrap();
+		assert_eq!(modules.len(), 11 * 10 + 2);
+		assert_eq!(module_widths(modules.as_slice()), expected_widths);
+		assert!(modules.first().copied().unwrap());
+		assert!(modules.last().copied().unwrap());
+	}
+
+	#[test]
+	fn rejects_non_printable_characters() {
+		assert!(encode("ABC\n").is_none());
+		assert!(encode("\u{e9}").is_none());
+	}
+}
@@ -0,0 +1,131 @@
+//! Machine-readable codes (barcodes and QR codes), and rendering them as
+//! standalone images.
+
+// Uses
+use std::fmt::Write;
+
+use qrcode::{Color, EcLevel, QrCode};
+
+use super::{code128, png};
+
+/// A grid of modules making up a barcode or QR code, where `true` is dark.
+#[derive(Debug, Clone)]
+pub struct CodeMatrix {
+	pub width:      usize,
+	pub height:     usize,
+	pub modules:    Vec<bool>,
+	/// The blank space required around the code for it to scan reliably, in
+	/// modules.
+	pub quiet_zone: usize,
+}
+
+impl CodeMatrix {
+	/// Builds a Code 128 barcode that's `height` modules tall.
+	///
+	/// Returns `None` if `data` contains characters that aren't printable
+	/// ASCII.
+	pub fn barcode(data: &str, height: usize) -> Option<Self> {
+		const QUIET_ZONE: usize = 10;
+
+		let row = code128::encode(data)?;
+		Some(Self {
+			width: row.len(),
+			height,
+			modules: row.repeat(height),
+			quiet_zone: QUIET_ZONE,
+		})
+	}
+
+	/// Builds a QR code.
+	///
+	/// Returns `None` if `data` is too long to fit in a QR code.
+	pub fn qr_code(data: &str) -> Option<Self> {
+		const QUIET_ZONE: usize = 4;
+
+		let code = QrCode::with_error_correction_level(data, EcLevel::M).ok()?;
+		let width = code.width();
+		Some(Self {
+			width,
+			height: width,
+			modules: code
+				.into_colors()
+				.into_iter()
+				.map(|color| color == Color::Dark)
+				.collect(),
+			quiet_zone: QUIET_ZONE,
+		})
+	}
+
+	/// Returns every horizontal run of dark modules as `(row, start, length)`.
+	///
+	/// Drawing runs instead of individual modules keeps the output much
+	/// smaller.
+	pub fn dark_runs(&self) -> Vec<(usize, usize, usize)> {
+		let mut runs = Vec::new();
+		for (y, row) in self.modules.chunks(self.width).enumerate() {
+			let mut run_start = None;
+			for (x, is_dark) in row.iter().chain([&false]).enumerate() {
+				match (*is_dark, run_start) {
+					(true, None) => run_start = Some(x),
+					(false, Some(start)) => {
+						runs.push((y, start, x - start));
+						run_start = None;
+					}
+					(true, Some(_)) | (false, None) => {}
+				}
+			}
+		}
+
+		runs
+	}
+
+	/// Renders the code as an SVG image, including the quiet zone.
+	///
+	/// The image is measured in modules, so it scales to whatever size it's
+	/// displayed at.
+	pub fn render_svg(&self) -> String {
+		let total_width = self.width + 2 * self.quiet_zone;
+		let total_height = self.height + 2 * self.quiet_zone;
+
+		// The unwraps are fine because writing to a `String` can't fail
+		let mut svg = String::new();
+		write!(
+			svg,
+			"<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {total_width} \
+			 {total_height}\" shape-rendering=\"crispEdges\"><rect width=\"100%\" height=\"100%\" \
+			 fill=\"#FFFFFF\"/><path fill=\"#000000\" d=\""
+		)
+		.unwrap();
+		for (y, x, length) in self.dark_runs() {
+			write!(
+				svg,
+				"M{},{}h{length}v1h-{length}z",
+				x + self.quiet_zone,
+				y + self.quiet_zone
+			)
+			.unwrap();
+		}
+		svg.push_str("\"/></svg>");
+
+		svg
+	}
+
+	/// Renders the code as a PNG image, including the quiet zone, with each
+	/// module taking up `module_size` pixels square.
+	pub fn render_png(&self, module_size: usize) -> Vec<u8> {
+		let total_width = (self.width + 2 * self.quiet_zone) * module_size;
+		let total_height = (self.height + 2 * self.quiet_zone) * module_size;
+
+		let mut pixels = vec![false; total_width * total_height];
+		for (y, x, length) in self.dark_runs() {
+			let left = (x + self.quiet_zone) * module_size;
+			let top = (y + self.quiet_zone) * module_size;
+			for pixel_y in top..(top + module_size) {
+				let row_start = pixel_y * total_width;
+				pixels[(row_start + left)..(row_start + left + length * module_size)].fill(true);
+			}
+		}
+
+		png::encode(total_width, total_height, pixels.as_slice())
+	}
+}
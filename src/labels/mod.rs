@@ -0,0 +1,275 @@
+//! Server-side label generation.
+//!
+//! Labels can be rendered as PDF for regular printers, or as ZPL for Zebra
+//! thermal printers.
+
+// Uses
+use diesel::{
+	dsl::exists,
+	select,
+	ExpressionMethods,
+	OptionalExtension,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::request::FromParam;
+
+// Exports
+pub use self::{codes::CodeMatrix, pdf::render_pdf, zpl::render_zpl};
+use crate::{
+	config::{LabelCode, LabelSettings, LabelSheetSettings},
+	db::schema,
+	error::{Context, Error, UserError},
+};
+
+// Modules
+mod code128;
+mod codes;
+mod pdf;
+mod png;
+mod zpl;
+
+/// The data that gets printed on a single label.
+#[derive(Debug, Clone)]
+pub struct LabelData {
+	/// The device ID, which is also encoded in the barcode.
+	pub device_id: String,
+	/// The contents of the QR code, if one is used.
+	pub link:      String,
+	/// The column name & value pairs for all columns shown on labels.
+	pub fields:    Vec<(String, String)>,
+}
+
+impl LabelData {
+	/// Builds the code printed on the label.
+	///
+	/// Returns an error if the data can't be encoded.
+	fn build_code(&self, settings: &LabelSettings) -> Result<CodeMatrix, Error> {
+		match settings.code {
+			LabelCode::Barcode => CodeMatrix::barcode(self.device_id.as_str(), 1).ok_or(
+				UserError::BadRequest("The device ID can't be encoded as a barcode.").into(),
+			),
+			LabelCode::QrCode => CodeMatrix::qr_code(self.link.as_str()).ok_or(
+				UserError::BadRequest("The device link can't be encoded as a QR code.").into(),
+			),
+		}
+	}
+}
+
+/// The output formats supported for labels.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LabelFormat {
+	Pdf,
+	Zpl,
+}
+
+impl LabelFormat {
+	/// The file extension for the format.
+	pub fn extension(self) -> &'static str {
+		match self {
+			Self::Pdf => "pdf",
+			Self::Zpl => "zpl",
+		}
+	}
+}
+
+/// The output formats supported for standalone code images.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ImageFormat {
+	Svg,
+	Png,
+}
+
+impl ImageFormat {
+	/// The file extension for the format.
+	pub fn extension(self) -> &'static str {
+		match self {
+			Self::Svg => "svg",
+			Self::Png => "png",
+		}
+	}
+}
+
+impl<'a> FromParam<'a> for ImageFormat {
+	type Error = &'a str;
+
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		match param.to_ascii_lowercase().as_str() {
+			"svg" => Ok(Self::Svg),
+			"png" => Ok(Self::Png),
+			_ => Err(param),
+		}
+	}
+}
+
+impl<'a> FromParam<'a> for LabelFormat {
+	type Error = &'a str;
+
+	fn from_param(param: &'a str) -> Result<Self, Self::Error> {
+		match param.to_ascii_lowercase().as_str() {
+			"pdf" => Ok(Self::Pdf),
+			"zpl" => Ok(Self::Zpl),
+			_ => Err(param),
+		}
+	}
+}
+
+/// Builds the link to a device's page, for use in QR codes.
+///
+/// If no public URL is configured, the link is just the device ID, since a
+/// relative link is useless to a phone camera.
+pub fn device_link(public_url: Option<&str>, device: &str) -> String {
+	match public_url {
+		Some(public_url) => format!("{}/edit/{device}", public_url.trim_end_matches('/')),
+		None => device.to_owned(),
+	}
+}
+
+/// Loads the data to print on a device's label.
+///
+/// Reserved device IDs that haven't been claimed yet get a label with no
+/// fields, so that labels can be printed ahead of time.
+pub fn load_label_data(
+	conn: &mut SqliteConnection,
+	public_url: Option<&str>,
+	device: &str,
+) -> Result<LabelData, Error> {
+	// Uses
+	use schema::{
+		column_definitions::dsl::*,
+		device_data::dsl::*,
+		device_key_info::dsl::*,
+		reserved_device_ids::dsl::reserved_device_ids,
+	};
+
+	let internal_id = device_key_info
+		.filter(deleted.eq(false))
+		.filter(device_id.eq(device))
+		.select(schema::device_key_info::dsl::id)
+		.get_result::<i32>(conn)
+		.optional()
+		.with_context("unable to load device info")?;
+	let Some(internal_id) = internal_id else {
+		let is_reserved = select(exists(
+			reserved_device_ids.filter(schema::reserved_device_ids::dsl::device_id.eq(device)),
+		))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for reserved device IDs")?;
+		if !is_reserved {
+			return Err(UserError::NotFound("Invalid device ID.").into());
+		}
+
+		return Ok(LabelData {
+			device_id: device.to_owned(),
+			link:      device_link(public_url, device),
+			fields:    Vec::new(),
+		});
+	};
+
+	let fields = device_data
+		.inner_join(column_definitions)
+		.filter(device_key_info_id.eq(internal_id))
+		.filter(show_on_labels.eq(true))
+		.filter(data_value.ne(""))
+		.order_by(ordering_key)
+		.then_order_by(column_definition_id)
+		.select((name, data_value))
+		.get_results::<(String, String)>(conn)
+		.with_context("unable to load the device data")?;
+
+	Ok(LabelData {
+		device_id: device.to_owned(),
+		link: device_link(public_url, device),
+		fields,
+	})
+}
+
+/// Resolves the contents of a scanned code to the ID of the device it refers
+/// to.
+///
+/// The contents can be any of the following:
+/// - A bare device ID
+/// - A component ID, in the form `device-component`
+/// - A full URL to a device's page, as encoded in label QR codes
+///
+/// Returns `None` if no device matches.
+pub fn resolve_scanned_code(
+	conn: &mut SqliteConnection,
+	scanned: &str,
+) -> Result<Option<String>, Error> {
+	// Uses
+	use schema::{device_components::dsl::*, device_key_info::dsl::*};
+
+	// Pull the device ID out of URLs, ignoring anything after it
+	let mut scanned = scanned.trim();
+	if let Some((_, after_path)) = scanned.rsplit_once("/edit/") {
+		scanned = after_path
+			.split(['/', '?', '#'])
+			.next()
+			.expect("split always returns at least one item");
+	}
+	if scanned.is_empty() {
+		return Ok(None);
+	}
+
+	// Try it as a device ID first
+	let found_device = device_key_info
+		.filter(schema::device_key_info::dsl::deleted.eq(false))
+		.filter(device_id.eq(scanned))
+		.select(device_id)
+		.get_result::<String>(conn)
+		.optional()
+		.with_context("unable to query the database for device existence")?;
+	if found_device.is_some() {
+		return Ok(found_device);
+	}
+
+	// Then as a component ID
+	let Some((scanned_device, scanned_component)) = scanned.rsplit_once('-') else {
+		return Ok(None);
+	};
+	device_key_info
+		.inner_join(device_components)
+		.filter(schema::device_key_info::dsl::deleted.eq(false))
+		.filter(schema::device_components::dsl::deleted.eq(false))
+		.filter(device_id.eq(scanned_device))
+		.filter(component_id.eq(scanned_component))
+		.select(device_id)
+		.get_result::<String>(conn)
+		.optional()
+		.with_context("unable to query the database for component existence")
+}
+
+/// Renders labels in the requested format, laid out according to `sheet`.
+///
+/// Returns an error if a device ID can't be encoded as a barcode.
+pub fn render_labels(
+	settings: &LabelSettings,
+	sheet: &LabelSheetSettings,
+	format: LabelFormat,
+	labels: &[LabelData],
+	skip_slots: usize,
+) -> Result<Vec<u8>, Error> {
+	match format {
+		LabelFormat::Pdf => render_pdf(settings, sheet, labels, skip_slots),
+		LabelFormat::Zpl => render_zpl(settings, sheet, labels, skip_slots).map(String::into_bytes),
+	}
+}
+
+/// Converts a measurement in millimetres to PDF points.
+fn mm_to_points(mm: f32) -> f32 {
+	const POINTS_PER_INCH: f32 = 72.0;
+	const MM_PER_INCH: f32 = 25.4;
+
+	mm / MM_PER_INCH * POINTS_PER_INCH
+}
+
+/// Formats a single label field for display.
+fn format_field(settings: &LabelSettings, field: &(String, String)) -> String {
+	if settings.show_column_names {
+		format!("{}: {}", field.0, field.1)
+	} else {
+		field.1.clone()
+	}
+}
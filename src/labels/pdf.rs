@@ -0,0 +1,182 @@
+// Uses
+use pdf_writer::{Content, Finish, Name, Pdf, Rect, Ref, Str};
+
+use super::{format_field, mm_to_points, LabelData};
+use crate::{
+	config::{LabelCode, LabelSettings, LabelSheetSettings},
+	error::Error,
+};
+
+// Constants
+const FONT_NAME: Name<'static> = Name(b"F1");
+const LINE_HEIGHT_FACTOR: f32 = 1.2;
+
+/// Renders a set of labels as a PDF document, laid out according to `sheet`.
+///
+/// The first `skip_slots` label positions are left empty, which allows for
+/// printing onto partially-used sheets.
+pub fn render_pdf(
+	settings: &LabelSettings,
+	sheet: &LabelSheetSettings,
+	labels: &[LabelData],
+	skip_slots: usize,
+) -> Result<Vec<u8>, Error> {
+	let mut pdf = Pdf::new();
+
+	let labels_per_page = sheet.labels_per_page();
+	let page_count = (skip_slots + labels.len()).div_ceil(labels_per_page);
+
+	// Reference IDs are allocated as follows: the catalog, page tree, and font
+	// come first, then each page is given a pair of IDs for the page and its
+	// contents
+	let catalog_id = Ref::new(1);
+	let page_tree_id = Ref::new(2);
+	let font_id = Ref::new(3);
+	let page_ids = (0..page_count)
+		.map(|i| (Ref::new(4 + 2 * i as i32), Ref::new(5 + 2 * i as i32)))
+		.collect::<Vec<_>>();
+
+	pdf.catalog(catalog_id).pages(page_tree_id);
+	pdf.pages(page_tree_id)
+		.kids(page_ids.iter().map(|(page_id, _)| *page_id))
+		.count(page_count as i32);
+	pdf.type1_font(font_id)
+		.base_font(Name(b"Helvetica"))
+		.encoding_predefined(Name(b"WinAnsiEncoding"));
+
+	// Figure out the page size, fitting it to the labels if it's not specified
+	let label_width = mm_to_points(settings.width);
+	let label_height = mm_to_points(settings.height);
+	let left_offset = mm_to_points(sheet.left_offset);
+	let top_offset = mm_to_points(sheet.top_offset);
+	let horizontal_pitch = label_width + mm_to_points(sheet.horizontal_gap);
+	let vertical_pitch = label_height + mm_to_points(sheet.vertical_gap);
+	let page_width = sheet.page_width.map_or_else(
+		|| {
+			2.0 * left_offset + horizontal_pitch * sheet.columns as f32
+				- mm_to_points(sheet.horizontal_gap)
+		},
+		mm_to_points,
+	);
+	let page_height = sheet.page_height.map_or_else(
+		|| 2.0 * top_offset + vertical_pitch * sheet.rows as f32 - mm_to_points(sheet.vertical_gap),
+		mm_to_points,
+	);
+
+	for (page_index, (page_id, content_id)) in page_ids.into_iter().enumerate() {
+		let mut page = pdf.page(page_id);
+		page.media_box(Rect::new(0.0, 0.0, page_width, page_height))
+			.parent(page_tree_id)
+			.contents(content_id);
+		page.resources().fonts().pair(FONT_NAME, font_id);
+		page.finish();
+
+		// Draw each label in its slot on the page
+		let mut content = Content::new();
+		let first_slot = page_index * labels_per_page;
+		for slot in first_slot.max(skip_slots)..(first_slot + labels_per_page) {
+			let Some(label) = labels.get(slot - skip_slots) else {
+				break;
+			};
+
+			let column = (slot - first_slot) % sheet.columns as usize;
+			let row = (slot - first_slot) / sheet.columns as usize;
+			let x = left_offset + column as f32 * horizontal_pitch;
+			let y = page_height - top_offset - row as f32 * vertical_pitch - label_height;
+			draw_label(&mut content, settings, label, x, y)?;
+		}
+		pdf.stream(content_id, &content.finish());
+	}
+
+	Ok(pdf.finish())
+}
+
+/// Draws a single label with its bottom-left corner at (`x`, `y`), in points.
+fn draw_label(
+	content: &mut Content,
+	settings: &LabelSettings,
+	label: &LabelData,
+	x: f32,
+	y: f32,
+) -> Result<(), Error> {
+	let margin = mm_to_points(settings.margin);
+	let inner_width = mm_to_points(settings.width) - 2.0 * margin;
+	let inner_height = mm_to_points(settings.height) - 2.0 * margin;
+	let left = x + margin;
+	let top = y + margin + inner_height;
+
+	// Clip everything to the printable area so long values don't spill over onto
+	// neighbouring labels
+	content.save_state();
+	content
+		.rect(left, y + margin, inner_width, inner_height)
+		.clip_nonzero()
+		.end_path();
+
+	// Lay out the code - barcodes go across the top with the text below, and QR
+	// codes go on the left with the text beside them
+	let code = label.build_code(settings)?;
+	let (code_width, code_height, text_left, text_top) = match settings.code {
+		LabelCode::Barcode => {
+			let barcode_height = mm_to_points(settings.barcode_height);
+			(inner_width, barcode_height, left, top - barcode_height)
+		}
+		LabelCode::QrCode => {
+			let size = inner_height.min(inner_width / 2.0);
+			(size, size, left + size + margin, top)
+		}
+	};
+
+	// Draw the code, stretched to fill its space along with its quiet zone -
+	// barcodes only need theirs at the sides
+	let vertical_quiet_zone = match settings.code {
+		LabelCode::Barcode => 0,
+		LabelCode::QrCode => code.quiet_zone,
+	};
+	let module_width = code_width / (code.width + 2 * code.quiet_zone) as f32;
+	let module_height = code_height / (code.height + 2 * vertical_quiet_zone) as f32;
+	for (row, start, length) in code.dark_runs() {
+		content.rect(
+			left + (start + code.quiet_zone) as f32 * module_width,
+			top - (row + vertical_quiet_zone + 1) as f32 * module_height,
+			length as f32 * module_width,
+			module_height,
+		);
+	}
+	content.set_fill_gray(0.0).fill_nonzero();
+
+	// Draw the text - the device ID first, then each of the fields
+	let line_height = settings.font_size * LINE_HEIGHT_FACTOR;
+	content.begin_text().set_font(FONT_NAME, settings.font_size);
+	let mut line_top = text_top;
+	for line in [label.device_id.clone()].into_iter().chain(
+		label
+			.fields
+			.iter()
+			.map(|field| format_field(settings, field)),
+	) {
+		line_top -= line_height;
+		content.set_text_matrix([1.0, 0.0, 0.0, 1.0, text_left, line_top]);
+		content.show(Str(encode_win_ansi(line.as_str()).as_slice()));
+	}
+	content.end_text();
+
+	content.restore_state();
+
+	Ok(())
+}
+
+/// Encodes text for the standard Helvetica font with `WinAnsiEncoding`.
+///
+/// This is close enough to Latin-1 that characters in that range are passed
+/// through as-is, and anything else is replaced with `?`.
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+	text.chars()
+		.map(|c| {
+			u8::try_from(c)
+				.ok()
+				.filter(|b| matches!(b, b' '..=b'~' | 0xA0..=0xFF))
+				.unwrap_or(b'?')
+		})
+		.collect()
+}
@@ -0,0 +1,189 @@
+//! A minimal PNG encoder for black & white images.
+//!
+//! The image data is stored without compression, since the images are small
+//! and it avoids pulling in a whole compression library for them.
+
+// Constants
+const SIGNATURE: &[u8] = b"\x89PNG\r\n\x1A\n";
+const MAX_STORED_BLOCK_LENGTH: usize = 0xFFFF;
+
+/// Encodes a black & white image, where `true` pixels are black.
+///
+/// `pixels` is laid out row-by-row, and must be exactly `width * height` long.
+pub fn encode(width: usize, height: usize, pixels: &[bool]) -> Vec<u8> {
+	assert!(width > 0 && height > 0, "the image must not be empty");
+	assert_eq!(
+		pixels.len(),
+		width * height,
+		"pixel count must match the size"
+	);
+
+	// Pack the pixels at 1 bit per pixel, where 0 is black and 1 is white, with a
+	// filter type byte of 0 (none) at the start of each row
+	let row_length = 1 + width.div_ceil(8);
+	let mut raw_data = vec![0; row_length * height];
+	for (y, row) in pixels.chunks(width).enumerate() {
+		for (x, is_black) in row.iter().enumerate() {
+			if !is_black {
+				raw_data[y * row_length + 1 + x / 8] |= 0x80 >> (x % 8);
+			}
+		}
+	}
+
+	// Wrap the data in a zlib stream made up of uncompressed blocks
+	let mut zlib_data = vec![0x78, 0x01];
+	let block_count = raw_data.len().div_ceil(MAX_STORED_BLOCK_LENGTH);
+	for (i, block) in raw_data.chunks(MAX_STORED_BLOCK_LENGTH).enumerate() {
+		let length = block.len() as u16;
+		zlib_data.push(u8::from(i + 1 == block_count));
+		zlib_data.extend_from_slice(&length.to_le_bytes());
+		zlib_data.extend_from_slice(&(!length).to_le_bytes());
+		zlib_data.extend_from_slice(block);
+	}
+	zlib_data.extend_from_slice(&adler32(raw_data.as_slice()).to_be_bytes());
+
+	// Build the file
+	let mut header = Vec::with_capacity(13);
+	header.extend_from_slice(&(width as u32).to_be_bytes());
+	header.extend_from_slice(&(height as u32).to_be_bytes());
+	header.extend_from_slice(&[
+		1, // Bit depth
+		0, // Colour type (greyscale)
+		0, // Compression method
+		0, // Filter method
+		0, // Interlace method
+	]);
+
+	let mut png = SIGNATURE.to_vec();
+	write_chunk(&mut png, *b"IHDR", header.as_slice());
+	write_chunk(&mut png, *b"IDAT", zlib_data.as_slice());
+	write_chunk(&mut png, *b"IEND", &[]);
+
+	png
+}
+
+fn write_chunk(png: &mut Vec<u8>, chunk_type: [u8; 4], data: &[u8]) {
+	png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+	let crc_start = png.len();
+	png.extend_from_slice(&chunk_type);
+	png.extend_from_slice(data);
+	let crc = crc32(&png[crc_start..]);
+	png.extend_from_slice(&crc.to_be_bytes());
+}
+
+/// The CRC-32 checksum used by PNG chunks.
+///
+/// This is the simple bitwise version, which is plenty fast for small images.
+fn crc32(bytes: &[u8]) -> u32 {
+	const POLYNOMIAL: u32 = 0xEDB8_8320;
+
+	let mut crc = !0_u32;
+	for byte in bytes {
+		crc ^= u32::from(*byte);
+		for _ in 0..8 {
+			crc = if crc & 1 == 0 {
+				crc >> 1
+			} else {
+				(crc >> 1) ^ POLYNOMIAL
+			};
+		}
+	}
+
+	!crc
+}
+
+/// The Adler-32 checksum used by zlib streams.
+fn adler32(bytes: &[u8]) -> u32 {
+	const MODULO: u32 = 0xFFF1;
+
+	let (mut a, mut b) = (1_u32, 0_u32);
+	for byte in bytes {
+		a = (a + u32::from(*byte)) % MODULO;
+		b = (b + a) % MODULO;
+	}
+
+	(b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use super::*;
+
+	/// Splits a PNG file into its chunks' types and data, checking each CRC.
+	fn read_chunks(png: &[u8]) -> Vec<([u8; 4], Vec<u8>)> {
+		let mut chunks = Vec::new();
+		let mut rest = png.strip_prefix(SIGNATURE).unwrap();
+		while !rest.is_empty() {
+			let length = u32::from_be_bytes(rest[0..4].try_into().unwrap()) as usize;
+			let (type_and_data, after) = rest[4..].split_at(4 + length);
+			let crc = u32::from_be_bytes(after[0..4].try_into().unwrap());
+			assert_eq!(crc32(type_and_data), crc);
+			chunks.push((
+				type_and_data[0..4].try_into().unwrap(),
+				type_and_data[4..].to_vec(),
+			));
+			rest = &after[4..];
+		}
+
+		chunks
+	}
+
+	#[test]
+	fn crc32_matches_known_values() {
+		assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+		assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+	}
+
+	#[test]
+	fn adler32_matches_known_values() {
+		assert_eq!(adler32(b""), 1);
+		assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398);
+	}
+
+	#[test]
+	fn encodes_a_single_pixel() {
+		let png = encode(1, 1, &[true]);
+
+		assert!(png.starts_with(SIGNATURE));
+		assert!(png.ends_with(b"\0\0\0\0IEND\xAE\x42\x60\x82"));
+		let chunks = read_chunks(png.as_slice());
+		let chunk_types = chunks
+			.iter()
+			.map(|(chunk_type, _)| chunk_type)
+			.collect::<Vec<_>>();
+		assert_eq!(chunk_types, [b"IHDR", b"IDAT", b"IEND"]);
+		assert_eq!(chunks[0].1, [0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 0, 0]);
+
+		// A stored block holding the filter type and the black pixel, then the
+		// Adler-32 of that data
+		let idat = chunks[1].1.as_slice();
+		assert_eq!(u16::from_be_bytes([idat[0], idat[1]]) % 31, 0);
+		assert_eq!(idat[2..], [1, 2, 0, 0xFD, 0xFF, 0, 0, 0, 0x02, 0, 0x01]);
+	}
+
+	#[test]
+	fn splits_large_images_into_stored_blocks() {
+		// Each row is a filter type byte and a byte of pixels
+		let (width, height) = (8, 40_000);
+		let png = encode(width, height, vec![false; width * height].as_slice());
+		let chunks = read_chunks(png.as_slice());
+		let idat = chunks[1].1.as_slice();
+
+		let mut raw_data = Vec::new();
+		let mut rest = &idat[2..];
+		loop {
+			let is_final = rest[0] == 1;
+			let length = u16::from_le_bytes([rest[1], rest[2]]);
+			assert_eq!(!length, u16::from_le_bytes([rest[3], rest[4]]));
+			raw_data.extend_from_slice(&rest[5..(5 + length as usize)]);
+			rest = &rest[(5 + length as usize)..];
+			if is_final {
+				break;
+			}
+		}
+
+		assert_eq!(raw_data, [0, 0xFF].repeat(height));
+		assert_eq!(rest, adler32(raw_data.as_slice()).to_be_bytes());
+	}
+}
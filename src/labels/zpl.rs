@@ -0,0 +1,173 @@
+// Uses
+use std::fmt::Write;
+
+use super::{format_field, LabelData};
+use crate::{
+	config::{LabelCode, LabelSettings, LabelSheetSettings},
+	error::Error,
+};
+
+// Constants
+const MM_PER_INCH: f32 = 25.4;
+const POINTS_PER_INCH: f32 = 72.0;
+const LINE_HEIGHT_FACTOR: f32 = 1.2;
+const MAX_QR_MAGNIFICATION: u32 = 10;
+
+/// Renders a set of labels as a ZPL stream for Zebra thermal printers.
+///
+/// Each row of labels across the roll is printed as its own label format, and
+/// rows are never split across formats. The first `skip_slots` label positions
+/// are left empty, and any rows left completely empty are skipped entirely.
+///
+/// The codes are rendered by the printer itself, so only their module sizes
+/// are calculated here.
+pub fn render_zpl(
+	settings: &LabelSettings,
+	sheet: &LabelSheetSettings,
+	labels: &[LabelData],
+	skip_slots: usize,
+) -> Result<String, Error> {
+	let mm_to_dots = |mm: f32| (mm / MM_PER_INCH * settings.zpl_dpi as f32).round() as u32;
+
+	let columns = sheet.columns as usize;
+	let horizontal_pitch = mm_to_dots(settings.width) + mm_to_dots(sheet.horizontal_gap);
+	let print_width = 2 * mm_to_dots(sheet.left_offset) + horizontal_pitch * sheet.columns
+		- mm_to_dots(sheet.horizontal_gap);
+
+	let mut zpl = String::new();
+	let first_row = skip_slots / columns;
+	let row_count = (skip_slots + labels.len()).div_ceil(columns);
+	for row in first_row..row_count {
+		// Start the label format, using UTF-8 for field data
+		// The unwraps are fine because writing to a `String` can't fail
+		writeln!(zpl, "^XA").unwrap();
+		writeln!(zpl, "^CI28").unwrap();
+		writeln!(zpl, "^PW{print_width}").unwrap();
+		writeln!(zpl, "^LL{}", mm_to_dots(settings.height)).unwrap();
+
+		for column in 0..columns {
+			let slot = row * columns + column;
+			if slot < skip_slots {
+				continue;
+			}
+			let Some(label) = labels.get(slot - skip_slots) else {
+				break;
+			};
+
+			let offset = mm_to_dots(sheet.left_offset) + column as u32 * horizontal_pitch;
+			write_label(&mut zpl, settings, label, offset)?;
+		}
+
+		writeln!(zpl, "^XZ").unwrap();
+	}
+
+	Ok(zpl)
+}
+
+/// Writes the fields for a single label, with the label starting `offset` dots
+/// from the left edge of the print area.
+fn write_label(
+	zpl: &mut String,
+	settings: &LabelSettings,
+	label: &LabelData,
+	offset: u32,
+) -> Result<(), Error> {
+	let mm_to_dots = |mm: f32| (mm / MM_PER_INCH * settings.zpl_dpi as f32).round() as u32;
+
+	let margin = mm_to_dots(settings.margin);
+	let inner_width = mm_to_dots(settings.width) - 2 * margin;
+	let inner_height = mm_to_dots(settings.height) - 2 * margin;
+	let left = offset + margin;
+
+	let font_height =
+		(settings.font_size / POINTS_PER_INCH * settings.zpl_dpi as f32).round() as u32;
+	let line_height = (font_height as f32 * LINE_HEIGHT_FACTOR).round() as u32;
+
+	// Write the code - barcodes go across the top with the text below, and QR
+	// codes go on the left with the text beside them
+	let code = label.build_code(settings)?;
+	let (text_left, text_top, text_width) = match settings.code {
+		LabelCode::Barcode => {
+			// The module width has to be a whole number of dots, so the barcode usually
+			// ends up a bit narrower than the label, and room is left for the quiet zone
+			// at the sides
+			let module_width = (inner_width / (code.width + 2 * code.quiet_zone) as u32).max(1);
+			let barcode_left = left + code.quiet_zone as u32 * module_width;
+			let barcode_height = mm_to_dots(settings.barcode_height);
+
+			// The barcode, without the printer's own interpretation line
+			writeln!(
+				zpl,
+				"^FO{barcode_left},{margin}^BY{module_width}^BCN,{barcode_height},N,N,N^FH^FD{}^FS",
+				escape_field_data(label.device_id.as_str())
+			)
+			.unwrap();
+
+			(left, margin + barcode_height, inner_width)
+		}
+		LabelCode::QrCode => {
+			// Likewise, the magnification is a whole number of dots per module, up to the
+			// maximum the printer supports, and room is left for the quiet zone all the way
+			// around
+			let size = inner_height.min(inner_width / 2);
+			let total_modules = (code.width + 2 * code.quiet_zone) as u32;
+			let magnification = (size / total_modules).clamp(1, MAX_QR_MAGNIFICATION);
+			let qr_width = magnification * total_modules;
+			let quiet_zone_width = magnification * code.quiet_zone as u32;
+			let qr_left = left + quiet_zone_width;
+			let qr_top = margin + quiet_zone_width;
+
+			// The `MA` prefix selects medium error correction and automatic encoding
+			writeln!(
+				zpl,
+				"^FO{qr_left},{qr_top}^BQN,2,{magnification}^FH^FDMA,{}^FS",
+				escape_field_data(label.link.as_str())
+			)
+			.unwrap();
+
+			(
+				left + qr_width + margin,
+				margin,
+				inner_width.saturating_sub(qr_width + margin),
+			)
+		}
+	};
+
+	// The text - the device ID first, then each of the fields
+	let mut line_top = text_top;
+	for line in [label.device_id.clone()].into_iter().chain(
+		label
+			.fields
+			.iter()
+			.map(|field| format_field(settings, field)),
+	) {
+		line_top += line_height - font_height;
+		writeln!(
+			zpl,
+			"^FO{text_left},{line_top}^A0N,{font_height},{font_height}^FB{text_width},1,0,L,\
+			 0^FH^FD{}^FS",
+			escape_field_data(line.as_str())
+		)
+		.unwrap();
+		line_top += font_height;
+	}
+
+	Ok(())
+}
+
+/// Escapes field data so that it can't be interpreted as ZPL commands.
+///
+/// This relies on the `^FH` command preceding the field data, which enables
+/// hexadecimal escapes using `_` as the indicator.
+fn escape_field_data(data: &str) -> String {
+	let mut escaped = String::with_capacity(data.len());
+	for c in data.chars() {
+		match c {
+			'_' | '^' | '~' => write!(escaped, "_{:02X}", c as u32).unwrap(),
+			'\r' | '\n' => escaped.push(' '),
+			_ => escaped.push(c),
+		}
+	}
+
+	escaped
+}
@@ -0,0 +1,219 @@
+//! Device lifecycle tracking - purchase dates, warranties, and planned
+//! replacement.
+
+// Uses
+use std::borrow::Cow;
+
+use chrono::{Days, NaiveDate};
+use diesel::{
+	dsl::exists,
+	insert_into,
+	result::OptionalExtension,
+	select,
+	update,
+	upsert::excluded,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+
+use crate::{
+	config::LifecycleSettings,
+	db::{
+		change_log::{log_automatic_change, DeviceDataDiff, DeviceDiff, Diff},
+		models::{
+			DeviceData,
+			DeviceDataNew,
+			DeviceLifecycle,
+			DeviceLifecycleEntry,
+			DEVICE_DATA,
+			DEVICE_LIFECYCLE_ENTRY,
+		},
+		schema,
+	},
+	error::{Context, Error, UserError},
+};
+
+/// The devices that need attention soon, or already do.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpiryReport<'a> {
+	/// Devices with a warranty that ends between today and the end of the
+	/// reporting window, soonest first.
+	pub warranty_expiring:  Vec<DeviceLifecycleEntry<'a>>,
+	/// Devices that are past their planned end of life, oldest first.
+	pub end_of_life_passed: Vec<DeviceLifecycleEntry<'a>>,
+}
+
+/// Fetches the lifecycle dates for a device, if any have been set.
+pub fn load_device_lifecycle(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+) -> Result<Option<DeviceLifecycle>, Error> {
+	// Uses
+	use schema::device_lifecycles::dsl::*;
+
+	device_lifecycles
+		.filter(device_key_info_id.eq(internal_id))
+		.get_result::<DeviceLifecycle>(conn)
+		.optional()
+		.with_context("unable to load the device lifecycle")
+}
+
+/// Finds the devices with warranties expiring within `days` days of `today`,
+/// and the ones that are past their end of life.
+pub fn load_expiry_report<'a>(
+	conn: &mut SqliteConnection,
+	today: NaiveDate,
+	days: u32,
+) -> Result<ExpiryReport<'a>, Error> {
+	// Uses
+	use schema::{
+		device_key_info::dsl::{deleted, device_key_info},
+		device_lifecycles::dsl::*,
+		location_paths::dsl::location_paths,
+	};
+
+	let window_end = today
+		.checked_add_days(Days::new(u64::from(days)))
+		.ok_or(UserError::BadRequest("The number of days is too large."))?;
+
+	let warranty_expiring = device_lifecycles
+		.inner_join(device_key_info.inner_join(location_paths))
+		.filter(deleted.eq(false))
+		.filter(warranty_ends_on.ge(today))
+		.filter(warranty_ends_on.le(window_end))
+		.order_by(warranty_ends_on)
+		.select(DEVICE_LIFECYCLE_ENTRY)
+		.load::<DeviceLifecycleEntry<'_>>(conn)
+		.with_context("unable to load the devices with expiring warranties")?;
+
+	let end_of_life_passed = device_lifecycles
+		.inner_join(device_key_info.inner_join(location_paths))
+		.filter(deleted.eq(false))
+		.filter(end_of_life_on.lt(today))
+		.order_by(end_of_life_on)
+		.select(DEVICE_LIFECYCLE_ENTRY)
+		.load::<DeviceLifecycleEntry<'_>>(conn)
+		.with_context("unable to load the devices past their end of life")?;
+
+	Ok(ExpiryReport {
+		warranty_expiring,
+		end_of_life_passed,
+	})
+}
+
+/// Makes the configured column change to every device whose warranty expired
+/// before `today`, and which hasn't been changed for it already.
+///
+/// Each device is only changed once, so if somebody changes the value back
+/// afterwards it's left alone - unless the warranty end date is changed.
+///
+/// Returns the IDs of the devices that were changed.
+pub fn apply_warranty_expiry_transitions(
+	conn: &mut SqliteConnection,
+	settings: &LifecycleSettings,
+	today: NaiveDate,
+	triggered_by: Option<i32>,
+) -> Result<Vec<String>, Error> {
+	// Uses
+	use schema::{
+		column_definitions::dsl::{column_definitions, exclusively_possible_values, name},
+		column_possible_values::dsl::{column_possible_values, value},
+		device_data::dsl::*,
+		device_key_info::dsl::{deleted, device_id, device_key_info},
+		device_lifecycles::dsl::{device_lifecycles, warranty_ends_on, warranty_expiry_processed},
+	};
+
+	let Some(status_settings) = settings.warranty_expired_status.as_ref() else {
+		return Ok(Vec::new());
+	};
+
+	// Look up the configured column, and make sure the value is allowed in it
+	let (status_column_id, status_exclusively_possible_values) = column_definitions
+		.filter(name.eq(status_settings.column.as_str()))
+		.select((
+			schema::column_definitions::dsl::id,
+			exclusively_possible_values,
+		))
+		.get_result::<(i32, bool)>(conn)
+		.optional()
+		.with_context("unable to load the warranty status column")?
+		.ok_or("the configured warranty status column doesn't exist")?;
+	if status_exclusively_possible_values
+		&& !select(exists(
+			column_possible_values
+				.filter(
+					schema::column_possible_values::dsl::column_definition_id.eq(status_column_id),
+				)
+				.filter(value.eq(status_settings.value.as_str())),
+		))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for possible value existence")?
+	{
+		return Err(
+			"the configured warranty status value isn't one of the column's possible values".into(),
+		);
+	}
+
+	let expired_results = device_lifecycles
+		.inner_join(device_key_info)
+		.filter(deleted.eq(false))
+		.filter(warranty_expiry_processed.eq(false))
+		.filter(warranty_ends_on.lt(today))
+		.select((
+			schema::device_lifecycles::dsl::device_key_info_id,
+			device_id,
+		))
+		.load::<(i32, String)>(conn)
+		.with_context("unable to load the devices with expired warranties")?;
+
+	let mut transitioned_device_ids = Vec::new();
+	for (internal_id, expired_device_id) in expired_results {
+		let device_data_results = device_data
+			.filter(device_key_info_id.eq(internal_id))
+			.filter(column_definition_id.eq(status_column_id))
+			.select(DEVICE_DATA)
+			.load::<DeviceData<'_>>(conn)
+			.with_context("unable to load the device data")?;
+		let insertable_device_data = vec![DeviceDataNew {
+			device_key_info_id:   internal_id,
+			column_definition_id: status_column_id,
+			data_value:           Cow::from(status_settings.value.as_str()),
+		}];
+
+		if let Some(device_data_diff) =
+			DeviceDataDiff::calculate_diff(&device_data_results, &insertable_device_data)
+		{
+			for insertable_record in &insertable_device_data {
+				insert_into(device_data)
+					.values(insertable_record)
+					.on_conflict((device_key_info_id, column_definition_id))
+					.do_update()
+					.set(data_value.eq(excluded(data_value)))
+					.execute(conn)
+					.with_context("unable to upsert into device_data")?;
+			}
+
+			let diff = DeviceDiff {
+				device_data: Some(device_data_diff),
+				..Default::default()
+			};
+			log_automatic_change(conn, internal_id, triggered_by, &diff)
+				.with_context("unable to log device change")?;
+
+			transitioned_device_ids.push(expired_device_id);
+		}
+
+		update(
+			device_lifecycles
+				.filter(schema::device_lifecycles::dsl::device_key_info_id.eq(internal_id)),
+		)
+		.set(warranty_expiry_processed.eq(true))
+		.execute(conn)
+		.with_context("unable to update device_lifecycles")?;
+	}
+
+	Ok(transitioned_device_ids)
+}
@@ -0,0 +1,236 @@
+//! Live device change events, which are pushed to clients as changes are
+//! committed.
+//!
+//! A background task watches `device_changes` for new rows, and broadcasts
+//! them to every subscriber. Since it reads through its own connection, it only
+//! ever sees changes once their transactions have been committed.
+
+// Uses
+use std::{borrow::Cow, time::Duration};
+
+use chrono::NaiveDateTime;
+use diesel::{
+	result::OptionalExtension,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	tokio::{
+		spawn,
+		sync::broadcast::{channel, Receiver, Sender},
+		time::interval,
+	},
+	Orbit,
+	Rocket,
+};
+use serde_json::{from_str as from_json_str, Map as JsonMap, Value as JsonValue};
+
+use crate::{
+	db::{
+		change_log::DeviceDiff,
+		models::{DeviceChangeDisplay, DEVICE_CHANGE},
+		schema,
+		DbPool,
+	},
+	error::{Context, Error},
+	webhooks::WebhookEvent,
+};
+
+// Constants
+/// How often to check for new changes.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// The most changes to load on each check.
+const POLL_BATCH_SIZE: i64 = 500;
+/// The number of changes a subscriber can fall behind by before it starts
+/// missing them.
+const CHANNEL_CAPACITY: usize = 256;
+
+/// The channel that committed device changes are broadcast on.
+pub struct LiveChanges {
+	sender: Sender<LiveChange>,
+}
+
+impl LiveChanges {
+	pub fn new() -> Self {
+		let (sender, _) = channel(CHANNEL_CAPACITY);
+		Self { sender }
+	}
+
+	pub fn subscribe(&self) -> Receiver<LiveChange> {
+		self.sender.subscribe()
+	}
+}
+
+/// A summary of a committed device change.
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LiveChange {
+	pub change_id:          i32,
+	pub device_id:          String,
+	pub location_id:        i32,
+	pub timestamp:          NaiveDateTime,
+	pub done_automatically: bool,
+	pub user_id:            Option<i32>,
+	pub user:               Option<String>,
+	pub event:              WebhookEvent,
+	/// The parts of the device that were changed, such as `deviceData` or
+	/// `deviceLoan`.
+	pub changed:            Vec<String>,
+	/// The device's location, followed by each of its ancestors.
+	#[serde(skip)]
+	pub location_ancestry:  Vec<i32>,
+}
+
+impl LiveChange {
+	/// Whether the change is to the given device (if any), and to a device in
+	/// the given location or one of its descendants (if any).
+	pub fn matches(&self, location: Option<i32>, device: Option<&str>) -> bool {
+		location.is_none_or(|location_id| self.location_ancestry.contains(&location_id))
+			&& device.is_none_or(|device_id| self.device_id == device_id)
+	}
+}
+
+/// Finds the IDs of a location and each of its ancestors, starting with the
+/// location itself.
+fn load_location_ancestry(conn: &mut SqliteConnection, location: i32) -> Result<Vec<i32>, Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	let mut ancestry = Vec::new();
+	let mut current = Some(location);
+	while let Some(current_id) = current {
+		// Guard against cycles, even though they shouldn't be possible
+		if ancestry.contains(&current_id) {
+			break;
+		}
+		ancestry.push(current_id);
+		current = locations
+			.filter(id.eq(current_id))
+			.select(parent_id)
+			.get_result::<Option<i32>>(conn)
+			.optional()
+			.with_context("unable to query the database for a location's parent")?
+			.flatten();
+	}
+
+	Ok(ancestry)
+}
+
+/// Fetches the ID of the most recent change, so that only the changes after it
+/// are broadcast.
+fn load_latest_change_id(conn: &mut SqliteConnection) -> Result<i32, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	device_changes
+		.select(id)
+		.order_by(id.desc())
+		.first::<i32>(conn)
+		.optional()
+		.with_context("unable to load the latest device change")
+		.map(Option::unwrap_or_default)
+}
+
+/// Loads the changes made after `after_change_id`, oldest first.
+fn load_new_changes(
+	conn: &mut SqliteConnection,
+	after_change_id: i32,
+) -> Result<Vec<LiveChange>, Error> {
+	// Uses
+	use schema::{
+		device_changes::dsl::*,
+		device_key_info::dsl::{device_id, device_key_info, location_id},
+		user_info::dsl::user_info,
+	};
+
+	let change_results = device_changes
+		.left_join(user_info)
+		.inner_join(device_key_info)
+		.filter(schema::device_changes::dsl::id.gt(after_change_id))
+		.order_by(schema::device_changes::dsl::id)
+		.limit(POLL_BATCH_SIZE)
+		.select((DEVICE_CHANGE(), device_id, location_id))
+		.load::<(DeviceChangeDisplay<'_>, String, i32)>(conn)
+		.with_context("unable to load the new device changes")?;
+
+	let mut live_changes = Vec::with_capacity(change_results.len());
+	for (device_change, change_device_id, change_location_id) in change_results {
+		// A change that can't be parsed shouldn't hold up the ones after it
+		let change_event = from_json_str::<DeviceDiff<'_>>(&device_change.change)
+			.map_or(WebhookEvent::Update, |diff| WebhookEvent::from(&diff));
+		let changed_sections = from_json_str::<JsonMap<String, JsonValue>>(&device_change.change)
+			.map(|sections| sections.into_iter().map(|(section, _)| section).collect())
+			.unwrap_or_default();
+
+		live_changes.push(LiveChange {
+			change_id:          device_change.id,
+			device_id:          change_device_id,
+			location_id:        change_location_id,
+			timestamp:          device_change.timestamp,
+			done_automatically: device_change.done_automatically,
+			user_id:            device_change.user_id,
+			user:               device_change.user.map(Cow::into_owned),
+			event:              change_event,
+			changed:            changed_sections,
+			location_ancestry:  load_location_ancestry(conn, change_location_id)?,
+		});
+	}
+
+	Ok(live_changes)
+}
+
+/// Starts watching for new changes in the background, and broadcasting them on
+/// the managed [`LiveChanges`] channel.
+pub async fn start_live_changes(rocket: &Rocket<Orbit>) {
+	let Some(sender) = rocket
+		.state::<LiveChanges>()
+		.map(|live_changes| live_changes.sender.clone())
+	else {
+		eprintln!("the live changes channel isn't managed");
+		return;
+	};
+
+	let Some(pool) = DbPool::get_from(rocket) else {
+		eprintln!("the database connection pool isn't attached");
+		return;
+	};
+
+	spawn(async move {
+		let Some(conn) = pool.get_one().await else {
+			eprintln!("unable to get a database connection for live changes");
+			return;
+		};
+		let latest_change_id = conn.run(load_latest_change_id).await;
+		// The connection goes back to the pool between ticks
+		drop(conn);
+		let mut last_change_id = match latest_change_id {
+			Ok(change_id) => change_id,
+			Err(e) => {
+				eprintln!("unable to start watching for live changes: {e}");
+				return;
+			}
+		};
+
+		let mut ticker = interval(POLL_INTERVAL);
+		loop {
+			ticker.tick().await;
+
+			let Some(conn) = pool.get_one().await else {
+				eprintln!("unable to get a database connection for live changes");
+				continue;
+			};
+			match conn.run(move |c| load_new_changes(c, last_change_id)).await {
+				Ok(live_changes) => {
+					for live_change in live_changes {
+						last_change_id = live_change.change_id;
+						// Sending only fails when nobody is listening, which is fine
+						sender.send(live_change).ok();
+					}
+				}
+				Err(e) => eprintln!("unable to load the live changes: {e}"),
+			}
+		}
+	});
+}
@@ -54,13 +54,22 @@ extern crate serde_derive;
 use crate::routes::rocket;
 
 // Modules
+mod audit_log;
 mod auth;
+mod calendar;
 mod config;
 mod db;
 mod error;
 mod id_gen;
+mod jobs;
+mod labels;
+mod lifecycle;
+mod live_changes;
+mod notifications;
+mod purge;
 mod routes;
 mod util;
+mod webhooks;
 
 // Entry Point
 #[launch]
@@ -0,0 +1,610 @@
+//! Email notifications, such as overdue loan reminders.
+//!
+//! Notifications are written to a queue wherever they're raised, and a
+//! background task sends them by SMTP, so sending an email never holds up a
+//! request. Failed emails are retried with backoff.
+
+// Uses
+use std::{borrow::Cow, time::Duration};
+
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use diesel::{
+	insert_into,
+	result::OptionalExtension,
+	update,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use lettre::{
+	message::{header::ContentType, Mailbox},
+	transport::smtp::authentication::Credentials,
+	AsyncSmtpTransport,
+	AsyncTransport,
+	Message,
+	Tokio1Executor,
+};
+use rocket::{
+	serde::json::{json, Value as JsonValue},
+	tokio::{spawn, time::interval},
+	Orbit,
+	Rocket,
+};
+use serde_json::{from_str as from_json_str, to_string as to_json_string, Map as JsonMap};
+
+use crate::{
+	config::{EmailSettings, EmailTemplate, SmtpSecurity},
+	db::{
+		change_log::DeviceDiff,
+		models::{DeviceLifecycleEntry, Notification, NotificationNew, NotificationPreferences},
+		schema,
+		DbConn,
+		DbPool,
+	},
+	error::{Context, Error},
+	labels::device_link,
+};
+
+// Constants
+/// How often the mailer checks the queue for notifications to send.
+const MAILER_TICK: Duration = Duration::from_secs(30);
+/// The most notifications to send on each tick.
+const MAILER_BATCH_SIZE: i64 = 50;
+/// The most times the retry delay is doubled.
+const MAX_BACKOFF_DOUBLINGS: u32 = 16;
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_FAILED: &str = "failed";
+pub const STATUS_SKIPPED: &str = "skipped";
+
+// Type Definitions
+type SmtpTransport = AsyncSmtpTransport<Tokio1Executor>;
+
+/// The kind of notification a user is sent.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+	LoanOverdue,
+	WarrantyExpiring,
+	DeviceChanged,
+	Test,
+}
+
+impl NotificationKind {
+	pub const ALL: [Self; 4] = [
+		Self::LoanOverdue,
+		Self::WarrantyExpiring,
+		Self::DeviceChanged,
+		Self::Test,
+	];
+
+	/// The name the kind is stored as, which matches its template's key in the
+	/// config.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::LoanOverdue => "loan_overdue",
+			Self::WarrantyExpiring => "warranty_expiring",
+			Self::DeviceChanged => "device_changed",
+			Self::Test => "test",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|kind| kind.name() == name)
+	}
+
+	fn template(self, settings: &EmailSettings) -> &EmailTemplate {
+		match self {
+			Self::LoanOverdue => &settings.templates.loan_overdue,
+			Self::WarrantyExpiring => &settings.templates.warranty_expiring,
+			Self::DeviceChanged => &settings.templates.device_changed,
+			Self::Test => &settings.templates.test,
+		}
+	}
+}
+
+impl NotificationPreferences {
+	/// The preferences of a user who hasn't set any.
+	pub fn default_for(user: i32) -> Self {
+		Self {
+			user_id:           user,
+			loan_overdue:      true,
+			warranty_expiring: false,
+			device_changed:    true,
+		}
+	}
+
+	/// Whether the user wants to be sent the given kind of notification.
+	pub fn allows(&self, kind: NotificationKind) -> bool {
+		match kind {
+			NotificationKind::LoanOverdue => self.loan_overdue,
+			NotificationKind::WarrantyExpiring => self.warranty_expiring,
+			NotificationKind::DeviceChanged => self.device_changed,
+			NotificationKind::Test => true,
+		}
+	}
+}
+
+/// A notification that's due to be sent, along with who it's going to.
+#[derive(Debug)]
+struct PendingNotification {
+	notification: Notification<'static>,
+	display_name: String,
+	email:        Option<String>,
+}
+
+/// Loads a user's notification preferences, or the defaults if they haven't
+/// set any.
+pub fn load_notification_preferences(
+	conn: &mut SqliteConnection,
+	user: i32,
+) -> Result<NotificationPreferences, Error> {
+	// Uses
+	use schema::notification_preferences::dsl::*;
+
+	Ok(notification_preferences
+		.filter(user_id.eq(user))
+		.get_result::<NotificationPreferences>(conn)
+		.optional()
+		.with_context("unable to load the notification preferences")?
+		.unwrap_or_else(|| NotificationPreferences::default_for(user)))
+}
+
+/// Adds a notification to the queue, to be sent by email.
+///
+/// `details` holds the values filled into the template. If `dedupe_key` is
+/// given and a notification with the same key has been queued before, nothing
+/// is queued. Returns whether the notification was queued.
+pub fn enqueue_notification(
+	conn: &mut SqliteConnection,
+	user: i32,
+	notification_kind: NotificationKind,
+	notification_details: &JsonValue,
+	notification_dedupe_key: Option<&str>,
+) -> Result<bool, Error> {
+	// Uses
+	use schema::notifications::dsl::*;
+
+	let serialised_details = to_json_string(notification_details)
+		.with_context("unable to serialise the notification details to JSON")?;
+	let now = Utc::now().naive_utc();
+
+	let inserted_count = insert_into(notifications)
+		.values(NotificationNew {
+			user_id:         user,
+			kind:            Cow::from(notification_kind.name()),
+			details:         Cow::from(serialised_details),
+			dedupe_key:      notification_dedupe_key.map(Cow::from),
+			created_at:      now,
+			status:          Cow::from(STATUS_PENDING),
+			next_attempt_at: now,
+		})
+		.on_conflict(dedupe_key)
+		.do_nothing()
+		.execute(conn)
+		.with_context("unable to insert into notifications")?;
+
+	Ok(inserted_count > 0)
+}
+
+/// Queues a notification for the borrower of a device, if it's on loan and
+/// someone else changed it.
+///
+/// Loan changes are left out, since the borrower is already involved in those.
+pub fn enqueue_device_changed_notification(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+	changed_by_user: Option<i32>,
+	diff: &DeviceDiff<'_>,
+) -> Result<(), Error> {
+	// Uses
+	use schema::{device_key_info, device_loans, user_info};
+
+	if diff.device_loan.is_some() {
+		return Ok(());
+	}
+
+	let borrower = device_loans::table
+		.filter(device_loans::device_key_info_id.eq(internal_id))
+		.filter(device_loans::returned_at.is_null())
+		.select(device_loans::borrower_user_id)
+		.first::<Option<i32>>(conn)
+		.optional()
+		.with_context("unable to load the device's active loan")?
+		.flatten();
+	let Some(borrower) = borrower.filter(|borrower| Some(*borrower) != changed_by_user) else {
+		return Ok(());
+	};
+
+	let changed_device_id = device_key_info::table
+		.filter(device_key_info::id.eq(internal_id))
+		.select(device_key_info::device_id)
+		.get_result::<String>(conn)
+		.with_context("unable to load the changed device's ID")?;
+	let changed_by = match changed_by_user {
+		Some(changed_by_user) => user_info::table
+			.filter(user_info::id.eq(changed_by_user))
+			.select(user_info::display_name)
+			.get_result::<String>(conn)
+			.with_context("unable to load the name of the user who made the change")?,
+		None => "Pecan".to_owned(),
+	};
+
+	enqueue_notification(
+		conn,
+		borrower,
+		NotificationKind::DeviceChanged,
+		&json!({
+			"device_id": changed_device_id,
+			"changed_by": changed_by,
+			"changed": describe_changed_sections(diff).join(", "),
+		}),
+		None,
+	)?;
+
+	Ok(())
+}
+
+/// Queues a notification about each expiring warranty for every user who's
+/// asked for them. Each user is only notified once per device and warranty end
+/// date.
+///
+/// Returns the number of notifications queued.
+pub fn enqueue_warranty_expiring_notifications(
+	conn: &mut SqliteConnection,
+	entries: &[DeviceLifecycleEntry<'_>],
+) -> Result<usize, Error> {
+	// Uses
+	use schema::notification_preferences::dsl::*;
+
+	if entries.is_empty() {
+		return Ok(0);
+	}
+
+	let recipients = notification_preferences
+		.filter(warranty_expiring.eq(true))
+		.select(user_id)
+		.load::<i32>(conn)
+		.with_context("unable to load the users who want warranty notifications")?;
+
+	let mut queued_count = 0;
+	for entry in entries {
+		let Some(entry_warranty_ends_on) = entry.warranty_ends_on else {
+			continue;
+		};
+		for recipient in &recipients {
+			let queued = enqueue_notification(
+				conn,
+				*recipient,
+				NotificationKind::WarrantyExpiring,
+				&json!({
+					"device_id": entry.device_id,
+					"location": entry.location,
+					"warranty_ends_on": entry_warranty_ends_on.to_string(),
+				}),
+				Some(
+					format!(
+						"warranty_expiring:{}:{entry_warranty_ends_on}:{recipient}",
+						entry.device_id
+					)
+					.as_str(),
+				),
+			)?;
+			if queued {
+				queued_count += 1;
+			}
+		}
+	}
+
+	Ok(queued_count)
+}
+
+/// Describes the parts of a device that a change touched, in words.
+fn describe_changed_sections(diff: &DeviceDiff<'_>) -> Vec<&'static str> {
+	[
+		(diff.device_key_info.is_some(), "details"),
+		(diff.device_data.is_some(), "data"),
+		(diff.device_components.is_some(), "components"),
+		(diff.device_attachments.is_some(), "attachments"),
+		(diff.device_booking.is_some(), "bookings"),
+		(diff.device_parent.is_some(), "parent"),
+		(diff.component_promotion.is_some(), "components"),
+		(diff.device_lifecycle.is_some(), "lifecycle dates"),
+	]
+	.into_iter()
+	.filter_map(|(changed, section)| changed.then_some(section))
+	.fold(Vec::new(), |mut sections, section| {
+		if !sections.contains(&section) {
+			sections.push(section);
+		}
+		sections
+	})
+}
+
+/// Fills the `{placeholder}`s in a template with the given values.
+///
+/// Placeholders without a value are left as they are.
+fn render_template(template: &str, values: &JsonMap<String, JsonValue>) -> String {
+	let mut rendered = String::with_capacity(template.len());
+	let mut remaining = template;
+	while let Some(start) = remaining.find('{') {
+		rendered.push_str(&remaining[..start]);
+		let after_start = &remaining[start + 1..];
+		let value = after_start
+			.find('}')
+			.and_then(|end| Some((end, values.get(&after_start[..end])?)));
+		if let Some((end, value)) = value {
+			if let Some(text) = value.as_str() {
+				rendered.push_str(text);
+			} else if !value.is_null() {
+				rendered.push_str(value.to_string().as_str());
+			}
+			remaining = &after_start[end + 1..];
+		} else {
+			rendered.push('{');
+			remaining = after_start;
+		}
+	}
+	rendered.push_str(remaining);
+
+	rendered
+}
+
+/// The time to wait after a notification's `failed_attempts`th failed attempt
+/// before trying again.
+fn retry_delay(settings: &EmailSettings, failed_attempts: u32) -> ChronoDuration {
+	let doublings = failed_attempts.saturating_sub(1).min(MAX_BACKOFF_DOUBLINGS);
+	ChronoDuration::seconds(i64::from(settings.retry_delay_seconds) << doublings)
+}
+
+/// Loads the notifications that are due to be sent, oldest first.
+fn load_pending_notifications(
+	conn: &mut SqliteConnection,
+	now: NaiveDateTime,
+) -> Result<Vec<PendingNotification>, Error> {
+	// Uses
+	use schema::{
+		notifications::dsl::*,
+		user_info::dsl::{display_name, email, user_info},
+	};
+
+	Ok(notifications
+		.inner_join(user_info)
+		.filter(status.eq(STATUS_PENDING))
+		.filter(next_attempt_at.le(now))
+		.order_by(next_attempt_at)
+		.limit(MAILER_BATCH_SIZE)
+		.select((
+			(
+				schema::notifications::dsl::id,
+				user_id,
+				kind,
+				details,
+				dedupe_key,
+				created_at,
+				status,
+				attempts,
+				next_attempt_at,
+				finished_at,
+				last_error,
+			),
+			display_name,
+			email,
+		))
+		.load::<(Notification<'static>, String, Option<String>)>(conn)
+		.with_context("unable to load the pending notifications")?
+		.into_iter()
+		.map(
+			|(notification, recipient_display_name, recipient_email)| PendingNotification {
+				notification,
+				display_name: recipient_display_name,
+				email: recipient_email,
+			},
+		)
+		.collect())
+}
+
+/// Records the outcome of sending a notification.
+///
+/// `error` is `None` when the notification was sent or skipped.
+fn record_outcome(
+	conn: &mut SqliteConnection,
+	settings: &EmailSettings,
+	notification: &Notification<'_>,
+	outcome_status: &str,
+	error: Option<String>,
+) -> Result<(), Error> {
+	// Uses
+	use schema::notifications::dsl::*;
+
+	let now = Utc::now().naive_utc();
+	let target = notifications.filter(id.eq(notification.id));
+	if outcome_status == STATUS_SKIPPED {
+		update(target)
+			.set((status.eq(outcome_status), finished_at.eq(now)))
+			.execute(conn)
+	} else {
+		let failed_attempts = u32::try_from(notification.attempts + 1).unwrap_or(u32::MAX);
+		let new_status =
+			if outcome_status == STATUS_PENDING && failed_attempts >= settings.max_attempts {
+				STATUS_FAILED
+			} else {
+				outcome_status
+			};
+		update(target)
+			.set((
+				status.eq(new_status),
+				attempts.eq(attempts + 1),
+				next_attempt_at.eq(now + retry_delay(settings, failed_attempts)),
+				finished_at.eq((new_status != STATUS_PENDING).then_some(now)),
+				last_error.eq(error),
+			))
+			.execute(conn)
+	}
+	.with_context("unable to update notifications")?;
+
+	Ok(())
+}
+
+/// Builds the transport used to send emails.
+fn build_transport(settings: &EmailSettings) -> Result<SmtpTransport, String> {
+	let mut builder = match settings.smtp_security {
+		SmtpSecurity::None => SmtpTransport::builder_dangerous(settings.smtp_host.as_str()),
+		SmtpSecurity::StartTls => {
+			SmtpTransport::starttls_relay(settings.smtp_host.as_str()).map_err(|e| e.to_string())?
+		}
+		SmtpSecurity::Tls => {
+			SmtpTransport::relay(settings.smtp_host.as_str()).map_err(|e| e.to_string())?
+		}
+	}
+	.port(settings.smtp_port);
+	if let (Some(username), Some(password)) = (&settings.smtp_username, &settings.smtp_password) {
+		builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+	}
+
+	Ok(builder.build())
+}
+
+/// Builds the email for a notification, or returns why it shouldn't be sent.
+fn build_message(
+	settings: &EmailSettings,
+	public_url: Option<&str>,
+	pending: &PendingNotification,
+	to_address: &str,
+) -> Result<Message, String> {
+	let notification_kind = NotificationKind::from_name(&pending.notification.kind)
+		.ok_or_else(|| format!("unknown notification kind: {}", pending.notification.kind))?;
+	let mut values = from_json_str::<JsonMap<String, JsonValue>>(&pending.notification.details)
+		.map_err(|e| format!("unable to parse the notification details: {e}"))?;
+	values.insert("user".to_owned(), json!(pending.display_name));
+	let link = public_url
+		.zip(values.get("device_id").and_then(JsonValue::as_str))
+		.map(|(public_url, device)| device_link(Some(public_url), device))
+		.unwrap_or_default();
+	values.insert("link".to_owned(), json!(link));
+
+	let template = notification_kind.template(settings);
+	let from = settings
+		.from_address
+		.parse::<Mailbox>()
+		.map_err(|e| format!("invalid from address: {e}"))?;
+	let to = Mailbox::new(
+		Some(pending.display_name.clone()),
+		to_address
+			.parse()
+			.map_err(|e| format!("invalid email address: {e}"))?,
+	);
+
+	Message::builder()
+		.from(from)
+		.to(to)
+		.subject(render_template(&template.subject, &values))
+		.header(ContentType::TEXT_PLAIN)
+		.body(render_template(&template.body, &values))
+		.map_err(|e| format!("unable to build the email: {e}"))
+}
+
+/// Sends every notification in the queue that's due.
+async fn send_pending_notifications(
+	conn: &DbConn,
+	transport: Option<&SmtpTransport>,
+	settings: &EmailSettings,
+	public_url: Option<&str>,
+) -> Result<(), Error> {
+	let pending_notifications = conn
+		.run(|c| load_pending_notifications(c, Utc::now().naive_utc()))
+		.await?;
+
+	for pending in pending_notifications {
+		let user = pending.notification.user_id;
+		let preferences = conn
+			.run(move |c| load_notification_preferences(c, user))
+			.await?;
+		let wanted = NotificationKind::from_name(&pending.notification.kind)
+			.is_some_and(|notification_kind| preferences.allows(notification_kind));
+
+		let (outcome_status, error) = match (transport, &pending.email) {
+			(Some(transport), Some(to_address)) if wanted => {
+				match build_message(settings, public_url, &pending, to_address) {
+					Ok(message) => match transport.send(message).await {
+						Ok(_) => (STATUS_SENT, None),
+						Err(e) => (STATUS_PENDING, Some(e.to_string())),
+					},
+					// Messages that can't be built won't build on a retry either
+					Err(e) => (STATUS_FAILED, Some(e)),
+				}
+			}
+			_ => (STATUS_SKIPPED, None),
+		};
+
+		let outcome_settings = settings.clone();
+		conn.run(move |c| {
+			record_outcome(
+				c,
+				&outcome_settings,
+				&pending.notification,
+				outcome_status,
+				error,
+			)
+		})
+		.await?;
+	}
+
+	Ok(())
+}
+
+/// Starts sending the queued notifications in the background.
+///
+/// If email is disabled, queued notifications are marked as skipped so that
+/// they don't pile up.
+pub async fn start_mailer(rocket: &Rocket<Orbit>) {
+	let (Ok(settings), Ok(public_url)) = (
+		rocket.figment().extract_inner::<EmailSettings>("email"),
+		rocket
+			.figment()
+			.extract_inner::<Option<String>>("public_url"),
+	) else {
+		eprintln!("unable to load the email settings");
+		return;
+	};
+
+	let transport = if settings.enabled {
+		match build_transport(&settings) {
+			Ok(transport) => Some(transport),
+			Err(e) => {
+				eprintln!("unable to set up the email transport: {e}");
+				return;
+			}
+		}
+	} else {
+		None
+	};
+
+	let Some(pool) = DbPool::get_from(rocket) else {
+		eprintln!("the database connection pool isn't attached");
+		return;
+	};
+
+	spawn(async move {
+		let mut ticker = interval(MAILER_TICK);
+		loop {
+			ticker.tick().await;
+
+			let Some(conn) = pool.get_one().await else {
+				eprintln!("unable to get a database connection for the mailer");
+				continue;
+			};
+			if let Err(e) = send_pending_notifications(
+				&conn,
+				transport.as_ref(),
+				&settings,
+				public_url.as_deref(),
+			)
+			.await
+			{
+				eprintln!("unable to send the queued notifications: {e}");
+			}
+		}
+	});
+}
@@ -0,0 +1,403 @@
+//! Permanently removing deleted data, either on request or according to the
+//! retention settings.
+//!
+//! Devices and attachments are normally only flagged as deleted, so that they
+//! can be restored. Purging them removes them for good. Every purge is
+//! recorded in the audit log.
+
+// Uses
+use std::net::IpAddr;
+
+use chrono::{Days, NaiveDateTime};
+use diesel::{
+	delete,
+	dsl::not,
+	result::OptionalExtension,
+	update,
+	BoolExpressionMethods,
+	ExpressionMethods,
+	NullableExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::serde::json::{json, Value as JsonValue};
+
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	config::RetentionSettings,
+	db::{
+		change_chain::{ensure_change_chain_intact, rehash_change_chain_from},
+		schema,
+	},
+	error::{Context, Error, UserError},
+};
+
+/// A device that was purged.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgedDevice {
+	pub internal_id:      i32,
+	pub device_id:        String,
+	/// The number of changes removed from the device change log.
+	pub change_count:     usize,
+	pub attachment_count: usize,
+	/// The latest hash of the device change log after it was rewritten.
+	pub head_hash:        Option<String>,
+}
+
+/// An attachment that was purged.
+#[derive(Queryable, Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct PurgedAttachment {
+	pub internal_id:   i32,
+	pub device_id:     String,
+	pub attachment_id: String,
+	pub file_name:     String,
+}
+
+/// The changes that had references to removed users taken out.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct AnonymisedChanges {
+	pub user_ids:     Vec<i32>,
+	pub change_count: usize,
+	/// The latest hash of the device change log after it was rewritten.
+	pub head_hash:    Option<String>,
+}
+
+/// Permanently removes a deleted device, along with everything that belongs to
+/// it, including its changes in the device change log.
+///
+/// Since this rewrites the change log's hash chain, it's refused if the chain
+/// is already broken. This should be called in a transaction.
+pub fn purge_device(
+	conn: &mut SqliteConnection,
+	device: &str,
+	actor: Option<i32>,
+	client_ip: Option<IpAddr>,
+) -> Result<PurgedDevice, Error> {
+	// Uses
+	use schema::{
+		audit_scans,
+		device_attachments,
+		device_bookings,
+		device_changes,
+		device_component_data,
+		device_components,
+		device_data,
+		device_key_info,
+		device_lifecycles,
+		device_loans,
+		watches,
+		webhook_deliveries,
+		webhook_outbox,
+	};
+
+	let (internal_id, is_deleted) = device_key_info::table
+		.filter(device_key_info::device_id.eq(device))
+		.select((device_key_info::id, device_key_info::deleted))
+		.get_result::<(i32, bool)>(conn)
+		.optional()
+		.with_context("unable to query the database for device_key_info existence")?
+		.ok_or(UserError::BadRequest("Invalid device ID."))?;
+	if !is_deleted {
+		return Err(UserError::BadRequest("Only deleted devices can be purged.").into());
+	}
+	if device_key_info::table
+		.filter(device_key_info::parent_device_id.eq(internal_id))
+		.count()
+		.get_result::<i64>(conn)
+		.with_context("unable to count the devices attached to the device")?
+		> 0
+	{
+		return Err(UserError::BadRequest(
+			"The device still has other devices attached to it, which have to be detached first.",
+		)
+		.into());
+	}
+
+	let previous_head_hash = ensure_change_chain_intact(conn)?;
+	let first_change_id = device_changes::table
+		.filter(device_changes::device_key_info_id.eq(internal_id))
+		.select(device_changes::id)
+		.order_by(device_changes::id)
+		.first::<i32>(conn)
+		.optional()
+		.with_context("unable to load the first change to the device")?;
+
+	// Remove the device's changes, along with anything queued for them
+	let device_change_ids = device_changes::table
+		.filter(device_changes::device_key_info_id.eq(internal_id))
+		.select(device_changes::id);
+	let outbox_ids = webhook_outbox::table
+		.filter(webhook_outbox::device_change_id.eq_any(device_change_ids))
+		.select(webhook_outbox::id);
+	delete(
+		webhook_deliveries::table.filter(webhook_deliveries::webhook_outbox_id.eq_any(outbox_ids)),
+	)
+	.execute(conn)
+	.with_context("unable to delete from webhook_deliveries")?;
+	delete(
+		webhook_outbox::table.filter(webhook_outbox::device_change_id.eq_any(device_change_ids)),
+	)
+	.execute(conn)
+	.with_context("unable to delete from webhook_outbox")?;
+	let change_count =
+		delete(device_changes::table.filter(device_changes::device_key_info_id.eq(internal_id)))
+			.execute(conn)
+			.with_context("unable to delete from device_changes")?;
+
+	// Remove everything else that belongs to the device
+	let component_ids = device_components::table
+		.filter(device_components::device_key_info_id.eq(internal_id))
+		.select(device_components::id);
+	delete(
+		device_component_data::table
+			.filter(device_component_data::device_component_id.eq_any(component_ids)),
+	)
+	.execute(conn)
+	.with_context("unable to delete from device_component_data")?;
+	delete(device_components::table.filter(device_components::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_components")?;
+	delete(device_data::table.filter(device_data::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_data")?;
+	let attachment_count = delete(
+		device_attachments::table.filter(device_attachments::device_key_info_id.eq(internal_id)),
+	)
+	.execute(conn)
+	.with_context("unable to delete from device_attachments")?;
+	delete(device_loans::table.filter(device_loans::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_loans")?;
+	delete(device_bookings::table.filter(device_bookings::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_bookings")?;
+	delete(device_lifecycles::table.filter(device_lifecycles::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_lifecycles")?;
+	delete(watches::table.filter(watches::device_key_info_id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from watches")?;
+	// Audit scans are kept as a record of the audit, but no longer point at the
+	// device
+	update(audit_scans::table.filter(audit_scans::device_key_info_id.eq(internal_id)))
+		.set(audit_scans::device_key_info_id.eq(None::<i32>))
+		.execute(conn)
+		.with_context("unable to update audit_scans")?;
+	delete(device_key_info::table.filter(device_key_info::id.eq(internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_key_info")?;
+
+	// Re-link the hash chain over the gap left by the device's changes
+	let head_hash = match first_change_id {
+		Some(first_change_id) => rehash_change_chain_from(conn, first_change_id)?,
+		None => None,
+	};
+
+	let purged_device = PurgedDevice {
+		internal_id,
+		device_id: device.to_owned(),
+		change_count,
+		attachment_count,
+		head_hash,
+	};
+	record_audit_event(
+		conn,
+		AuditEvent {
+			actor,
+			action: AuditAction::DevicePurge,
+			target: AuditTarget::Device(internal_id),
+			before: Some(json!({
+				"deviceId": device,
+				"headHash": previous_head_hash,
+				"firstRewrittenChangeId": first_change_id,
+			})),
+			after: Some(json!(purged_device)),
+			client_ip,
+		},
+	)?;
+
+	Ok(purged_device)
+}
+
+/// Permanently removes an attachment, which has to have been deleted itself or
+/// belong to a deleted device.
+///
+/// This should be called in a transaction.
+pub fn purge_attachment(
+	conn: &mut SqliteConnection,
+	device: &str,
+	attachment: &str,
+	actor: Option<i32>,
+	client_ip: Option<IpAddr>,
+) -> Result<PurgedAttachment, Error> {
+	// Uses
+	use schema::{device_attachments, device_key_info};
+
+	let (attachment_to_purge, is_deleted) = device_attachments::table
+		.inner_join(device_key_info::table)
+		.filter(device_key_info::device_id.eq(device))
+		.filter(device_attachments::attachment_id.eq(attachment))
+		.select((
+			(
+				device_attachments::id,
+				device_key_info::device_id,
+				device_attachments::attachment_id,
+				device_attachments::file_name,
+			),
+			device_attachments::deleted.or(device_key_info::deleted),
+		))
+		.get_result::<(PurgedAttachment, bool)>(conn)
+		.optional()
+		.with_context("unable to load the attachment")?
+		.ok_or(UserError::NotFound("Attachment not found."))?;
+	if !is_deleted {
+		return Err(UserError::BadRequest("Only deleted attachments can be purged.").into());
+	}
+
+	delete_attachment(conn, attachment_to_purge, actor, client_ip)
+}
+
+/// Applies the retention settings, purging whatever has been kept for long
+/// enough. This is done by the `retention` job.
+///
+/// This should be called in a transaction.
+pub fn apply_retention_policy(
+	conn: &mut SqliteConnection,
+	settings: &RetentionSettings,
+	now: NaiveDateTime,
+) -> Result<JsonValue, Error> {
+	// Uses
+	use schema::{device_attachments, device_key_info};
+
+	let mut purged_attachments = Vec::new();
+	// A cutoff before the earliest representable date means nothing is old enough
+	let attachment_cutoff = settings
+		.deleted_attachment_days
+		.and_then(|deleted_attachment_days| {
+			now.checked_sub_days(Days::new(u64::from(deleted_attachment_days)))
+		});
+	if let Some(cutoff) = attachment_cutoff {
+		let expired_attachments = device_attachments::table
+			.inner_join(device_key_info::table)
+			.filter(device_attachments::deleted.eq(true))
+			.filter(device_attachments::deleted_at.le(cutoff))
+			.select((
+				device_attachments::id,
+				device_key_info::device_id,
+				device_attachments::attachment_id,
+				device_attachments::file_name,
+			))
+			.load::<PurgedAttachment>(conn)
+			.with_context("unable to load the expired attachments")?;
+		for expired_attachment in expired_attachments {
+			purged_attachments.push(delete_attachment(conn, expired_attachment, None, None)?);
+		}
+	}
+
+	let anonymised_changes = if settings.anonymise_removed_users {
+		Some(anonymise_removed_users(conn)?)
+	} else {
+		None
+	};
+
+	Ok(json!({
+		"purgedAttachments": purged_attachments,
+		"anonymisedChanges": anonymised_changes,
+	}))
+}
+
+/// Removes the references to users that no longer exist from the device
+/// change log.
+///
+/// Since this rewrites the change log's hash chain, it's refused if the chain
+/// is already broken.
+fn anonymise_removed_users(conn: &mut SqliteConnection) -> Result<AnonymisedChanges, Error> {
+	// Uses
+	use schema::{device_changes, user_info};
+
+	let removed_user_changes = device_changes::table
+		.filter(device_changes::user_id.is_not_null())
+		.filter(not(
+			device_changes::user_id.eq_any(user_info::table.select(user_info::id.nullable()))
+		));
+	let mut user_ids = removed_user_changes
+		.select(device_changes::user_id.assume_not_null())
+		.distinct()
+		.load::<i32>(conn)
+		.with_context("unable to load the removed users in the device change log")?;
+	user_ids.sort_unstable();
+	let Some(first_change_id) = removed_user_changes
+		.select(device_changes::id)
+		.order_by(device_changes::id)
+		.first::<i32>(conn)
+		.optional()
+		.with_context("unable to load the first change by a removed user")?
+	else {
+		return Ok(AnonymisedChanges {
+			user_ids,
+			change_count: 0,
+			head_hash: None,
+		});
+	};
+
+	let previous_head_hash = ensure_change_chain_intact(conn)?;
+	let change_count = update(removed_user_changes)
+		.set(device_changes::user_id.eq(None::<i32>))
+		.execute(conn)
+		.with_context("unable to update device_changes")?;
+	let head_hash = rehash_change_chain_from(conn, first_change_id)?;
+
+	let anonymised_changes = AnonymisedChanges {
+		user_ids,
+		change_count,
+		head_hash,
+	};
+	record_audit_event(
+		conn,
+		AuditEvent {
+			actor:     None,
+			action:    AuditAction::ChangeLogAnonymise,
+			target:    AuditTarget::Devices,
+			before:    Some(json!({
+				"headHash": previous_head_hash,
+				"firstRewrittenChangeId": first_change_id,
+			})),
+			after:     Some(json!(anonymised_changes)),
+			client_ip: None,
+		},
+	)?;
+
+	Ok(anonymised_changes)
+}
+
+/// Deletes an attachment that's been checked to be purgeable, and records it.
+fn delete_attachment(
+	conn: &mut SqliteConnection,
+	attachment: PurgedAttachment,
+	actor: Option<i32>,
+	client_ip: Option<IpAddr>,
+) -> Result<PurgedAttachment, Error> {
+	// Uses
+	use schema::device_attachments::dsl::*;
+
+	delete(device_attachments.filter(id.eq(attachment.internal_id)))
+		.execute(conn)
+		.with_context("unable to delete from device_attachments")?;
+	record_audit_event(
+		conn,
+		AuditEvent {
+			actor,
+			action: AuditAction::AttachmentPurge,
+			target: AuditTarget::Attachment(attachment.internal_id),
+			before: None,
+			after: Some(json!(attachment)),
+			client_ip,
+		},
+	)?;
+
+	Ok(attachment)
+}
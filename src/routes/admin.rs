@@ -1,11 +1,420 @@
 // Uses
-use rocket::{routes, Route};
+use std::{collections::HashMap, net::IpAddr};
+
+use diesel::{sqlite::Sqlite, Connection, ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Value as JsonValue},
+	Route,
+	State,
+};
 
 use super::Routable;
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	auth::AdminUser,
+	config::AppConfig,
+	db::{
+		change_chain::verify_change_chain,
+		models::{AuditLogEntry, JobRun, WebhookDelivery},
+		schema,
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+	jobs::{load_last_job_run, run_job, Job},
+	purge,
+};
+
+// Constants
+/// The number of runs to return when listing a job's history.
+const JOB_RUN_HISTORY_LIMIT: i64 = 100;
+/// The number of attempts to return when listing webhook deliveries.
+const WEBHOOK_DELIVERY_HISTORY_LIMIT: i64 = 200;
+/// The number of audit log entries to return on each page, if the request
+/// doesn't specify.
+const AUDIT_LOG_DEFAULT_PAGE_SIZE: u32 = 50;
+/// The most audit log entries that can be returned on one page.
+const AUDIT_LOG_MAX_PAGE_SIZE: u32 = 500;
 
 /// The route for this section.
 pub(super) struct AdminApi;
 impl Routable for AdminApi {
 	const PATH: &'static str = "/admin";
-	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| routes![];
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			list_jobs,
+			get_job_runs,
+			trigger_job,
+			list_webhooks,
+			get_webhook_deliveries,
+			get_audit_log,
+			verify_change_log,
+			purge_device,
+			purge_attachment
+		]
+	};
+}
+
+/// Lists the background jobs, with their schedules and most recent runs.
+#[get("/jobs")]
+pub async fn list_jobs(
+	config: &State<AppConfig>,
+	_user: &AdminUser,
+	conn: DbConn,
+) -> Result<JsonValue, Error> {
+	let job_settings = config.jobs.clone();
+	conn.run(move |c| {
+		let mut job_results = Vec::new();
+		for job in Job::ALL {
+			let schedule = job.schedule(&job_settings);
+			job_results.push(json!({
+				"name": job.name(),
+				"scheduled": job_settings.enabled && schedule.enabled,
+				"intervalMinutes": schedule.interval_minutes,
+				"lastRun": load_last_job_run(c, job)?,
+			}));
+		}
+
+		Ok(json!({ "jobs": job_results }))
+	})
+	.await
+}
+
+/// Lists the most recent runs of a job, newest first.
+#[get("/jobs/<job>/runs")]
+pub async fn get_job_runs(
+	_user: &AdminUser,
+	conn: DbConn,
+	job: String,
+) -> Result<JsonValue, Error> {
+	let job = Job::from_name(job.as_str()).ok_or(UserError::NotFound("Invalid job."))?;
+	conn.run(move |c| {
+		// Uses
+		use schema::job_runs::dsl::*;
+
+		let job_run_results = job_runs
+			.filter(job_name.eq(job.name()))
+			.order_by(started_at.desc())
+			.limit(JOB_RUN_HISTORY_LIMIT)
+			.load::<JobRun<'_>>(c)
+			.with_context("unable to load the job runs")?;
+
+		Ok(json!({ "name": job.name(), "runs": job_run_results }))
+	})
+	.await
+}
+
+/// Runs a job right away, regardless of its schedule.
+#[post("/jobs/<job>/run")]
+pub async fn trigger_job(
+	config: &State<AppConfig>,
+	user: &AdminUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	job: String,
+) -> Result<JsonValue, Error> {
+	let job = Job::from_name(job.as_str()).ok_or(UserError::NotFound("Invalid job."))?;
+	let lifecycle_settings = config.lifecycle.clone();
+	let retention_settings = config.retention.clone();
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		let job_run = run_job(
+			c,
+			job,
+			&lifecycle_settings,
+			&retention_settings,
+			Some(user_id_value),
+		)?;
+		record_audit_event(
+			c,
+			AuditEvent {
+				actor: Some(user_id_value),
+				action: AuditAction::JobRun,
+				target: AuditTarget::Job(job.name()),
+				before: None,
+				after: Some(json!({ "runId": job_run.id, "succeeded": job_run.succeeded })),
+				client_ip,
+			},
+		)?;
+
+		Ok(json!({ "run": job_run }))
+	})
+	.await
+}
+
+/// Lists the configured webhooks, and how many changes are still waiting to be
+/// delivered.
+#[get("/webhooks")]
+pub async fn list_webhooks(
+	config: &State<AppConfig>,
+	_user: &AdminUser,
+	conn: DbConn,
+) -> Result<JsonValue, Error> {
+	// The secrets are left out on purpose
+	let webhook_results = config
+		.webhooks
+		.endpoints
+		.iter()
+		.map(|endpoint| {
+			json!({
+				"name": endpoint.name,
+				"url": endpoint.url,
+				"events": endpoint.events,
+			})
+		})
+		.collect::<Vec<_>>();
+	conn.run(move |c| {
+		// Uses
+		use schema::webhook_outbox::dsl::*;
+
+		let pending_count = webhook_outbox
+			.filter(completed_at.is_null())
+			.count()
+			.get_result::<i64>(c)
+			.with_context("unable to count the pending webhook outbox entries")?;
+
+		Ok(json!({ "webhooks": webhook_results, "pendingCount": pending_count }))
+	})
+	.await
+}
+
+/// Lists the most recent attempts to deliver changes to the webhooks, newest
+/// first.
+///
+/// `webhook` limits the results to one webhook, and `failed` limits them to
+/// the attempts that failed.
+#[get("/webhooks/deliveries?<webhook>&<failed>")]
+pub async fn get_webhook_deliveries(
+	_user: &AdminUser,
+	conn: DbConn,
+	webhook: Option<String>,
+	failed: Option<bool>,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		// Uses
+		use schema::{
+			device_changes::dsl::device_changes,
+			device_key_info::dsl::{device_id, device_key_info},
+			webhook_deliveries::dsl::*,
+			webhook_outbox::dsl::{device_change_id, event, webhook_outbox},
+		};
+
+		let mut query = webhook_deliveries
+			.inner_join(webhook_outbox.inner_join(device_changes.inner_join(device_key_info)))
+			.select((
+				(
+					schema::webhook_deliveries::dsl::id,
+					webhook_outbox_id,
+					webhook_name,
+					attempt,
+					attempted_at,
+					status_code,
+					error,
+					succeeded,
+				),
+				event,
+				device_change_id,
+				device_id,
+			))
+			.order_by(attempted_at.desc())
+			.limit(WEBHOOK_DELIVERY_HISTORY_LIMIT)
+			.into_boxed();
+		if let Some(webhook_value) = webhook {
+			query = query.filter(webhook_name.eq(webhook_value));
+		}
+		if failed == Some(true) {
+			query = query.filter(succeeded.eq(false));
+		}
+
+		let delivery_results = query
+			.load::<(WebhookDelivery<'_>, String, i32, String)>(c)
+			.with_context("unable to load the webhook deliveries")?
+			.into_iter()
+			.map(
+				|(delivery, delivery_event, change_id, delivery_device_id)| {
+					json!({
+						"delivery": delivery,
+						"event": delivery_event,
+						"changeId": change_id,
+						"deviceId": delivery_device_id,
+					})
+				},
+			)
+			.collect::<Vec<_>>();
+
+		Ok(json!({ "deliveries": delivery_results }))
+	})
+	.await
+}
+
+/// The filters for browsing the audit log.
+struct AuditLogFilter {
+	actor:     Option<i32>,
+	action:    Option<AuditAction>,
+	target:    Option<String>,
+	target_id: Option<String>,
+}
+
+impl AuditLogFilter {
+	/// Builds a query for the audit log entries that match the filters.
+	fn query(&self) -> schema::audit_log::BoxedQuery<'static, Sqlite> {
+		// Uses
+		use schema::audit_log::dsl::*;
+
+		let mut query = audit_log.into_boxed();
+		if let Some(actor_value) = self.actor {
+			query = query.filter(actor_user_id.eq(actor_value));
+		}
+		if let Some(action_value) = self.action {
+			query = query.filter(action.eq(action_value.name()));
+		}
+		if let Some(target_value) = &self.target {
+			query = query.filter(target_type.eq(target_value.clone()));
+		}
+		if let Some(target_id_value) = &self.target_id {
+			query = query.filter(target_id.eq(target_id_value.clone()));
+		}
+
+		query
+	}
+}
+
+/// Lists the audit log, newest first, a page at a time.
+///
+/// `actor` limits the entries to the ones done by a user, `action` to one kind
+/// of action, and `target` and `id` to one kind of target (such as `location`)
+/// and one target of that kind. `page` starts from 1.
+#[get("/audit-log?<actor>&<action>&<target>&<id>&<page>&<limit>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_audit_log(
+	_user: &AdminUser,
+	conn: DbConn,
+	actor: Option<i32>,
+	action: Option<String>,
+	target: Option<String>,
+	id: Option<String>,
+	page: Option<u32>,
+	limit: Option<u32>,
+) -> Result<JsonValue, Error> {
+	let filter = AuditLogFilter {
+		actor,
+		action: action
+			.map(|action_name| {
+				AuditAction::from_name(action_name.as_str())
+					.ok_or(UserError::BadRequest("Invalid action."))
+			})
+			.transpose()?,
+		target,
+		target_id: id,
+	};
+	let page = page.unwrap_or(1).max(1);
+	let limit = limit
+		.unwrap_or(AUDIT_LOG_DEFAULT_PAGE_SIZE)
+		.clamp(1, AUDIT_LOG_MAX_PAGE_SIZE);
+	conn.run(move |c| {
+		// Uses
+		use schema::{audit_log, user_info};
+
+		let total_count = filter
+			.query()
+			.count()
+			.get_result::<i64>(c)
+			.with_context("unable to count the audit log entries")?;
+		let entry_results = filter
+			.query()
+			.order_by(audit_log::id.desc())
+			.offset(i64::from(page - 1) * i64::from(limit))
+			.limit(i64::from(limit))
+			.load::<AuditLogEntry<'_>>(c)
+			.with_context("unable to load the audit log")?;
+
+		// Include the names of the users involved, so they don't have to be looked up
+		let actor_ids = entry_results
+			.iter()
+			.filter_map(|entry| entry.actor_user_id)
+			.collect::<Vec<_>>();
+		let actor_names = user_info::table
+			.filter(user_info::id.eq_any(actor_ids))
+			.select((user_info::id, user_info::display_name))
+			.load::<(i32, String)>(c)
+			.with_context("unable to load the names of the users in the audit log")?
+			.into_iter()
+			.collect::<HashMap<_, _>>();
+
+		Ok(json!({
+			"entries": entry_results
+				.into_iter()
+				.map(|entry| {
+					let actor_name = entry
+						.actor_user_id
+						.and_then(|actor_id| actor_names.get(&actor_id));
+					json!({ "entry": entry, "actor": actor_name })
+				})
+				.collect::<Vec<_>>(),
+			"page": page,
+			"limit": limit,
+			"totalCount": total_count,
+		}))
+	})
+	.await
+}
+
+/// Verifies the hash chain over the device change log, reporting the first
+/// change at which it's broken, if any.
+///
+/// The returned `headHash` is worth recording somewhere outside the database,
+/// since the chain can't show that it was rewritten or cut short on its own.
+#[get("/change-log/verify")]
+pub async fn verify_change_log(_user: &AdminUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| Ok(json!({ "verification": verify_change_chain(c)? })))
+		.await
+}
+
+/// Permanently removes a deleted device, along with everything that belongs to
+/// it, including its history.
+#[post("/purge/device/<device>")]
+pub async fn purge_device(
+	user: &AdminUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	device: String,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		let purged_device = c.transaction::<_, Error, _>(|tc| {
+			purge::purge_device(tc, device.as_str(), Some(user_id_value), client_ip)
+		})?;
+
+		Ok(json!({ "device": purged_device }))
+	})
+	.await
+}
+
+/// Permanently removes a deleted attachment, or an attachment of a deleted
+/// device.
+#[post("/purge/attachment/<device>/<attachment>")]
+pub async fn purge_attachment(
+	user: &AdminUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	device: String,
+	attachment: String,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		let purged_attachment = c.transaction::<_, Error, _>(|tc| {
+			purge::purge_attachment(
+				tc,
+				device.as_str(),
+				attachment.as_str(),
+				Some(user_id_value),
+				client_ip,
+			)
+		})?;
+
+		Ok(json!({ "attachment": purged_attachment }))
+	})
+	.await
 }
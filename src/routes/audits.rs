@@ -0,0 +1,464 @@
+// Uses
+use std::borrow::Cow;
+
+use chrono::Utc;
+use diesel::{
+	dsl::exists,
+	insert_into,
+	result::OptionalExtension,
+	select,
+	sql_query,
+	sql_types::Integer,
+	update,
+	BoolExpressionMethods,
+	Connection,
+	ExpressionMethods,
+	JoinOnDsl,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+};
+use serde_json::{from_str as from_json_str, to_string as to_json_string};
+
+use super::Routable;
+use crate::{
+	auth::AuthedUser,
+	db::{
+		change_log::{log_automatic_change, DeviceDiff, DeviceKeyInfoDiff, DeviceKeyInfoDiffData},
+		models::{
+			AuditDeviceEntry,
+			AuditScanNew,
+			AuditSession,
+			AuditSessionDisplay,
+			AuditSessionNew,
+			AUDIT_SESSION,
+		},
+		schema,
+		util::{fetch_new_rowid_on, location_is_within},
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+	labels::resolve_scanned_code,
+};
+
+/// The route for this section.
+pub(super) struct AuditsApi;
+impl Routable for AuditsApi {
+	const PATH: &'static str = "/audits";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			start_audit,
+			get_audits,
+			get_audit,
+			scan_devices,
+			close_audit
+		]
+	};
+}
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditStartInfo {
+	location_id: i32,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditScanInfo {
+	/// Device IDs, component IDs or label URLs, exactly as they were scanned.
+	scanned_values: Vec<String>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditCloseInfo {
+	/// Whether to move the devices that were found in the audited location, but
+	/// are recorded as being somewhere else.
+	#[serde(default)]
+	move_found_devices: bool,
+}
+
+/// The outcome of a single scan.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+enum ScanStatus {
+	/// The device is recorded as being in the audited location.
+	Expected,
+	/// The device is recorded as being somewhere else.
+	Elsewhere,
+	/// The scanned value doesn't match any device.
+	Unknown,
+	/// The device (or value) was already scanned during this audit.
+	AlreadyScanned,
+}
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ScanResult<'a> {
+	scanned_value: &'a str,
+	device_id:     Option<String>,
+	status:        ScanStatus,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AuditReport<'a> {
+	/// Devices that were scanned and are recorded as being in the audited
+	/// location.
+	found:            Vec<AuditReportDevice<'a>>,
+	/// Devices that are recorded as being in the audited location, but weren't
+	/// scanned.
+	missing:          Vec<AuditReportDevice<'a>>,
+	/// Devices that were scanned, but are recorded as being somewhere else.
+	found_elsewhere:  Vec<AuditReportDevice<'a>>,
+	/// Scanned values that didn't match any device.
+	unknown:          Vec<String>,
+	/// Devices that were moved into the audited location when the audit was
+	/// closed.
+	moved_device_ids: Vec<String>,
+}
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AuditReportDevice<'a> {
+	device_id:   Cow<'a, str>,
+	location_id: i32,
+	location:    Cow<'a, str>,
+}
+impl<'a> From<AuditDeviceEntry<'a>> for AuditReportDevice<'a> {
+	fn from(entry: AuditDeviceEntry<'a>) -> Self {
+		Self {
+			device_id:   entry.device_id,
+			location_id: entry.location_id,
+			location:    entry.location,
+		}
+	}
+}
+
+/// Starts an audit of a location (including all of the locations within it).
+#[post("/start", data = "<start_info>")]
+pub async fn start_audit(
+	user: &AuthedUser,
+	conn: DbConn,
+	start_info: Json<AuditStartInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{audit_sessions::dsl::*, locations::dsl::locations};
+
+		c.transaction::<_, Error, _>(|tc| {
+			if !select(exists(
+				locations.filter(schema::locations::dsl::id.eq(start_info.location_id)),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for location existence")?
+			{
+				return Err(UserError::BadRequest("Invalid location.").into());
+			}
+			if select(exists(
+				audit_sessions
+					.filter(location_id.eq(start_info.location_id))
+					.filter(closed_at.is_null()),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for open audits")?
+			{
+				return Err(UserError::BadRequest(
+					"An audit of that location is already in progress.",
+				)
+				.into());
+			}
+
+			insert_into(audit_sessions)
+				.values(AuditSessionNew {
+					location_id: start_info.location_id,
+					started_by:  user_id_value,
+					started_at:  Utc::now().naive_utc(),
+				})
+				.execute(tc)
+				.with_context("unable to insert into audit_sessions")?;
+			let new_audit_id =
+				fetch_new_rowid_on(tc).with_context("unable to get the ID of the new audit")?;
+
+			Ok(json!({ "auditId": new_audit_id }))
+		})
+	})
+	.await
+}
+
+/// Lists all audits, most recent first.
+#[get("/list")]
+pub async fn get_audits(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		// Uses
+		use schema::{audit_sessions::dsl::*, location_paths::dsl::location_paths, user_info};
+
+		let audit_results = audit_sessions
+			.inner_join(location_paths)
+			.inner_join(user_info::table.on(user_info::id.eq(started_by)))
+			.order_by(started_at.desc())
+			.select(AUDIT_SESSION)
+			.load::<AuditSessionDisplay<'_>>(c)
+			.with_context("unable to load the audits")?;
+
+		Ok(json!({ "audits": audit_results }))
+	})
+	.await
+}
+
+/// Fetches the report for an audit - the final one if it's been closed, or one
+/// reflecting the scans so far if it hasn't.
+#[get("/get/<audit>")]
+pub async fn get_audit(_user: &AuthedUser, conn: DbConn, audit: i32) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let audit_result = load_audit_session(c, audit)?;
+
+		let report = if let Some(stored_report) = audit_result.report.as_deref() {
+			from_json_str::<JsonValue>(stored_report)
+				.with_context("unable to deserialise the stored audit report")?
+		} else {
+			json!(build_audit_report(c, &audit_result)?)
+		};
+
+		Ok(json!({ "audit": audit_result, "report": report }))
+	})
+	.await
+}
+
+/// Records devices as found during an audit.
+#[post("/scan/<audit>", data = "<scan_info>")]
+pub async fn scan_devices(
+	user: &AuthedUser,
+	conn: DbConn,
+	audit: i32,
+	scan_info: Json<AuditScanInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{audit_scans::dsl::*, device_key_info::dsl::*};
+
+		c.transaction::<_, Error, _>(|tc| {
+			let audit_result = load_open_audit_session(tc, audit)?;
+
+			let mut scan_results = Vec::with_capacity(scan_info.scanned_values.len());
+			for submitted_value in &scan_info.scanned_values {
+				let submitted_value = submitted_value.trim();
+				if submitted_value.is_empty() {
+					continue;
+				}
+
+				// Work out which device was scanned
+				let found_device = match resolve_scanned_code(tc, submitted_value)? {
+					Some(found_device_id) => Some(
+						device_key_info
+							.filter(device_id.eq(found_device_id.as_str()))
+							.select((schema::device_key_info::dsl::id, device_id, location_id))
+							.get_result::<(i32, String, i32)>(tc)
+							.with_context("unable to load the scanned device")?,
+					),
+					None => None,
+				};
+
+				// Ignore anything that's already been scanned
+				let mut already_scanned_query = audit_scans
+					.filter(audit_session_id.eq(audit))
+					.filter(scanned_value.eq(submitted_value))
+					.into_boxed();
+				if let Some((found_internal_id, ..)) = found_device {
+					already_scanned_query =
+						already_scanned_query.or_filter(audit_session_id.eq(audit).and(
+							schema::audit_scans::dsl::device_key_info_id.eq(found_internal_id),
+						));
+				}
+				let already_scanned = select(exists(already_scanned_query))
+					.get_result::<bool>(tc)
+					.with_context("unable to query the database for existing scans")?;
+
+				let status = if already_scanned {
+					ScanStatus::AlreadyScanned
+				} else {
+					insert_into(audit_scans)
+						.values(AuditScanNew {
+							audit_session_id:   audit,
+							scanned_value:      Cow::from(submitted_value),
+							device_key_info_id: found_device.as_ref().map(|device| device.0),
+							scanned_by:         user_id_value,
+							scanned_at:         Utc::now().naive_utc(),
+						})
+						.execute(tc)
+						.with_context("unable to insert into audit_scans")?;
+
+					match &found_device {
+						Some((_, _, found_location_id)) => {
+							if location_is_within(tc, *found_location_id, audit_result.location_id)?
+							{
+								ScanStatus::Expected
+							} else {
+								ScanStatus::Elsewhere
+							}
+						}
+						None => ScanStatus::Unknown,
+					}
+				};
+
+				scan_results.push(ScanResult {
+					scanned_value: submitted_value,
+					device_id: found_device.map(|device| device.1),
+					status,
+				});
+			}
+
+			Ok(json!({ "results": scan_results }))
+		})
+	})
+	.await
+}
+
+/// Closes an audit, producing its final report.
+///
+/// Devices that were found but are recorded as being somewhere else can
+/// optionally be moved into the audited location. These moves are logged as
+/// automatic changes.
+#[post("/close/<audit>", data = "<close_info>")]
+pub async fn close_audit(
+	user: &AuthedUser,
+	conn: DbConn,
+	audit: i32,
+	close_info: Json<AuditCloseInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{audit_sessions::dsl::*, device_key_info::dsl::*};
+
+		c.transaction::<_, Error, _>(|tc| {
+			let audit_result = load_open_audit_session(tc, audit)?;
+			let mut audit_report = build_audit_report(tc, &audit_result)?;
+
+			if close_info.move_found_devices {
+				for found_device in &audit_report.found_elsewhere {
+					let found_internal_id = device_key_info
+						.filter(device_id.eq(found_device.device_id.as_ref()))
+						.select(schema::device_key_info::dsl::id)
+						.get_result::<i32>(tc)
+						.with_context("unable to load the found device")?;
+
+					update(
+						device_key_info
+							.filter(schema::device_key_info::dsl::id.eq(found_internal_id)),
+					)
+					.set(schema::device_key_info::dsl::location_id.eq(audit_result.location_id))
+					.execute(tc)
+					.with_context("unable to update device_key_info")?;
+
+					let diff = DeviceDiff {
+						device_key_info: Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+							location_id: Some(audit_result.location_id),
+						})),
+						..Default::default()
+					};
+					log_automatic_change(tc, found_internal_id, Some(user_id_value), &diff)
+						.with_context("unable to log device change")?;
+
+					audit_report
+						.moved_device_ids
+						.push(found_device.device_id.to_string());
+				}
+			}
+
+			let serialised_report = to_json_string(&audit_report)
+				.with_context("unable to serialise the audit report to JSON")?;
+			update(audit_sessions.filter(schema::audit_sessions::dsl::id.eq(audit)))
+				.set((
+					closed_by.eq(user_id_value),
+					closed_at.eq(Utc::now().naive_utc()),
+					schema::audit_sessions::dsl::report.eq(serialised_report),
+				))
+				.execute(tc)
+				.with_context("unable to update audit_sessions")?;
+
+			Ok(json!({ "auditId": audit, "report": audit_report }))
+		})
+	})
+	.await
+}
+
+fn load_audit_session<'a>(
+	conn: &mut SqliteConnection,
+	audit: i32,
+) -> Result<AuditSession<'a>, Error> {
+	// Uses
+	use schema::audit_sessions::dsl::*;
+
+	audit_sessions
+		.filter(id.eq(audit))
+		.get_result::<AuditSession<'_>>(conn)
+		.optional()
+		.with_context("unable to query the database for audit existence")?
+		.ok_or(UserError::NotFound("Invalid audit ID.").into())
+}
+
+fn load_open_audit_session<'a>(
+	conn: &mut SqliteConnection,
+	audit: i32,
+) -> Result<AuditSession<'a>, Error> {
+	let audit_result = load_audit_session(conn, audit)?;
+	if audit_result.closed_at.is_some() {
+		return Err(UserError::BadRequest("The audit has already been closed.").into());
+	}
+
+	Ok(audit_result)
+}
+
+/// Compares the scans so far against the devices recorded in the audited
+/// location.
+fn build_audit_report<'a>(
+	conn: &mut SqliteConnection,
+	audit_result: &AuditSession<'_>,
+) -> Result<AuditReport<'a>, Error> {
+	// Uses
+	use schema::audit_scans::dsl::*;
+
+	let device_results = sql_query(include_str!(concat!(
+		env!("CARGO_MANIFEST_DIR"),
+		"/src/sql/audit_devices.sql"
+	)))
+	.bind::<Integer, _>(audit_result.location_id)
+	.bind::<Integer, _>(audit_result.id)
+	.bind::<Integer, _>(audit_result.location_id)
+	.bind::<Integer, _>(audit_result.id)
+	.load::<AuditDeviceEntry<'_>>(conn)
+	.with_context("unable to load the audited devices")?;
+
+	let unknown_results = audit_scans
+		.filter(audit_session_id.eq(audit_result.id))
+		.filter(device_key_info_id.is_null())
+		.order_by(scanned_at)
+		.select(scanned_value)
+		.load::<String>(conn)
+		.with_context("unable to load the unknown scans")?;
+
+	let mut report = AuditReport {
+		found:            Vec::new(),
+		missing:          Vec::new(),
+		found_elsewhere:  Vec::new(),
+		unknown:          unknown_results,
+		moved_device_ids: Vec::new(),
+	};
+	for device_result in device_results {
+		match (device_result.expected, device_result.scanned) {
+			(true, true) => report.found.push(device_result.into()),
+			(true, false) => report.missing.push(device_result.into()),
+			(false, true) => report.found_elsewhere.push(device_result.into()),
+			(false, false) => {}
+		}
+	}
+
+	Ok(report)
+}
@@ -1,9 +1,11 @@
 // Uses
+use std::net::IpAddr;
+
 use rocket::{
 	http::{Cookie, CookieJar},
 	post,
 	routes,
-	serde::json::Json,
+	serde::json::{json, Json},
 	time::OffsetDateTime,
 	Route,
 	State,
@@ -11,6 +13,7 @@ use rocket::{
 
 use super::Routable;
 use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
 	auth::{
 		create_user_if_new,
 		generate_token_for_user,
@@ -46,6 +49,7 @@ pub async fn authenticate(
 	authenticator: &State<Option<LdapAuthenticator>>,
 	cookie_jar: &CookieJar<'_>,
 	conn: DbConn,
+	client_ip: Option<IpAddr>,
 	auth_data: Json<AuthData>,
 ) -> Result<Json<()>, Error> {
 	// Authenticate the credentials with the server
@@ -60,21 +64,67 @@ pub async fn authenticate(
 		.await
 		.with_context("something went wrong when attempting to authenticate a user")?;
 
+	let username = auth_data.username.trim().to_owned();
 	if auth_result.is_none() {
+		conn.run(move |c| {
+			record_audit_event(
+				c,
+				AuditEvent {
+					actor: None,
+					action: AuditAction::LoginFailed,
+					target: AuditTarget::User(None),
+					before: None,
+					after: Some(json!({ "username": username })),
+					client_ip,
+				},
+			)
+		})
+		.await?;
 		return Err(UserError::BadRequest("Invalid credentials.").into());
 	}
 	let authed_user = auth_result.unwrap();
 
 	// Fetch the user ID and create the new user if necessary
+	let login_username = username.clone();
 	let user_id = conn
-		.run(move |c| create_user_if_new(c, authed_user.into()))
+		.run(move |c| {
+			let user_id = create_user_if_new(c, authed_user.into())?;
+			record_audit_event(
+				c,
+				AuditEvent {
+					actor: Some(user_id),
+					action: AuditAction::Login,
+					target: AuditTarget::User(Some(user_id)),
+					before: None,
+					after: Some(json!({ "username": login_username })),
+					client_ip,
+				},
+			)?;
+
+			Ok::<_, Error>(user_id)
+		})
 		.await
 		.with_context("failed to get (new) user information")?;
 
 	// If auth was successful, generate the new token and set a cookie for the user
 	let token_valid_days = config.token_valid_days;
 	let new_token = conn
-		.run(move |c| generate_token_for_user(c, user_id, token_valid_days))
+		.run(move |c| {
+			let new_token = generate_token_for_user(c, user_id, token_valid_days)?;
+			record_audit_event(
+				c,
+				AuditEvent {
+					actor: Some(user_id),
+					action: AuditAction::TokenCreate,
+					target: AuditTarget::User(Some(user_id)),
+					before: None,
+					after: Some(json!({ "validDays": token_valid_days })),
+					client_ip,
+				},
+			)?;
+
+			Ok::<_, Error>(new_token)
+		})
 		.await
 		.with_context("failed to generate the new token")?;
 	let mut new_cookie = Cookie::new(COOKIE_NAME, new_token);
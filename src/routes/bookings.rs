@@ -0,0 +1,436 @@
+// Uses
+use std::{borrow::Cow, net::IpAddr};
+
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use diesel::{
+	dsl::exists,
+	insert_into,
+	result::OptionalExtension,
+	select,
+	update,
+	BoolExpressionMethods,
+	Connection,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+	State,
+};
+
+use super::Routable;
+use crate::{
+	auth::{
+		get_calendar_feed_token,
+		load_calendar_feed_token_user,
+		rotate_calendar_feed_token,
+		AuthedUser,
+	},
+	calendar::{render_calendar, CalendarEvent},
+	config::AppConfig,
+	db::{
+		change_log::{log_change, DeviceBookingDiff, DeviceDiff},
+		models::{
+			DeviceBooking,
+			DeviceBookingDisplay,
+			DeviceBookingNew,
+			DeviceKeyInfo,
+			DEVICE_BOOKING,
+		},
+		schema,
+		util::fetch_new_rowid_on,
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+	routes::file_from_memory::FileFromMemory,
+};
+
+/// The route for this section.
+pub(super) struct BookingsApi;
+impl Routable for BookingsApi {
+	const PATH: &'static str = "/bookings";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			create_booking,
+			cancel_booking,
+			get_device_bookings,
+			get_user_bookings,
+			get_calendar_feeds,
+			rotate_calendar_feeds,
+			get_device_calendar_feed,
+			get_user_calendar_feed
+		]
+	};
+}
+
+// Constants
+/// How far back calendar feeds include past bookings.
+const FEED_HISTORY_DAYS: i64 = 30;
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookingInfo {
+	device_id: String,
+	starts_at: NaiveDateTime,
+	ends_at:   NaiveDateTime,
+	#[serde(default)]
+	purpose:   String,
+}
+
+/// Which bookings to list.
+enum BookingFilter {
+	Device(String),
+	User(i32),
+}
+
+/// Books a device over a time range.
+///
+/// Bookings can't overlap with other bookings of the same device, or with a
+/// loan that isn't due back before the booking starts.
+#[post("/create", data = "<booking_info>")]
+pub async fn create_booking(
+	user: &AuthedUser,
+	conn: DbConn,
+	booking_info: Json<BookingInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{device_bookings::dsl::*, device_key_info::dsl::*, device_loans::dsl::*};
+
+		// Verify the submitted values
+		if booking_info.starts_at >= booking_info.ends_at {
+			return Err(UserError::BadRequest("The booking must end after it starts.").into());
+		}
+		if booking_info.ends_at <= Utc::now().naive_utc() {
+			return Err(UserError::BadRequest("The booking must end in the future.").into());
+		}
+
+		c.transaction::<_, Error, _>(|tc| {
+			let device_key_info_result = device_key_info
+				.filter(device_id.eq(booking_info.device_id.as_str()))
+				.get_result::<DeviceKeyInfo<'_>>(tc)
+				.optional()
+				.with_context("unable to query the database for device_key_info existence")?;
+			let Some(device_key_info_result) = device_key_info_result else {
+				return Err(UserError::BadRequest("Invalid device ID.").into());
+			};
+
+			// Ensure that deleted devices aren't modified
+			if device_key_info_result.deleted {
+				return Err(UserError::BadRequest(
+					"The device has been deleted. It cannot be modified.",
+				)
+				.into());
+			}
+
+			// Check for conflicts
+			if select(exists(
+				device_bookings
+					.filter(
+						schema::device_bookings::dsl::device_key_info_id
+							.eq(device_key_info_result.id),
+					)
+					.filter(cancelled.eq(false))
+					.filter(starts_at.lt(booking_info.ends_at))
+					.filter(ends_at.gt(booking_info.starts_at)),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for conflicting bookings")?
+			{
+				return Err(UserError::BadRequest(
+					"The device is already booked during that time.",
+				)
+				.into());
+			}
+			if select(exists(
+				device_loans
+					.filter(
+						schema::device_loans::dsl::device_key_info_id.eq(device_key_info_result.id),
+					)
+					.filter(returned_at.is_null())
+					.filter(due_at.is_null().or(due_at.gt(booking_info.starts_at))),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for conflicting loans")?
+			{
+				return Err(
+					UserError::BadRequest("The device is out on loan during that time.").into(),
+				);
+			}
+
+			// Create the booking
+			insert_into(device_bookings)
+				.values(DeviceBookingNew {
+					device_key_info_id: device_key_info_result.id,
+					user_id:            user_id_value,
+					starts_at:          booking_info.starts_at,
+					ends_at:            booking_info.ends_at,
+					purpose:            Cow::from(booking_info.purpose.as_str()),
+				})
+				.execute(tc)
+				.with_context("unable to insert into device_bookings")?;
+			let new_booking_id =
+				fetch_new_rowid_on(tc).with_context("unable to get the ID of the new booking")?;
+
+			// Log the change in the database
+			let diff = DeviceDiff {
+				device_booking: Some(DeviceBookingDiff::Add {
+					booking_id: new_booking_id,
+					starts_at:  booking_info.starts_at,
+					ends_at:    booking_info.ends_at,
+					purpose:    Cow::from(booking_info.purpose.as_str()),
+				}),
+				..Default::default()
+			};
+			log_change(tc, device_key_info_result.id, user_id_value, &diff)
+				.with_context("unable to log device change")?;
+
+			Ok(json!({ "bookingId": new_booking_id }))
+		})
+	})
+	.await
+}
+
+/// Cancels a booking.
+#[post("/cancel/<booking>")]
+pub async fn cancel_booking(
+	user: &AuthedUser,
+	conn: DbConn,
+	booking: i32,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::device_bookings::dsl::*;
+
+		c.transaction::<_, Error, _>(|tc| {
+			let booking_result = device_bookings
+				.filter(id.eq(booking))
+				.get_result::<DeviceBooking<'_>>(tc)
+				.optional()
+				.with_context("unable to query the database for booking existence")?;
+			let Some(booking_result) = booking_result else {
+				return Err(UserError::NotFound("Invalid booking ID.").into());
+			};
+			if booking_result.cancelled {
+				return Err(
+					UserError::BadRequest("The booking has already been cancelled.").into(),
+				);
+			}
+
+			update(device_bookings.filter(id.eq(booking)))
+				.set(cancelled.eq(true))
+				.execute(tc)
+				.with_context("unable to update device_bookings")?;
+
+			// Log the change in the database
+			let diff = DeviceDiff {
+				device_booking: Some(DeviceBookingDiff::Cancel {
+					booking_id: booking,
+				}),
+				..Default::default()
+			};
+			log_change(tc, booking_result.device_key_info_id, user_id_value, &diff)
+				.with_context("unable to log device change")?;
+
+			Ok(json!({ "bookingId": booking }))
+		})
+	})
+	.await
+}
+
+/// Lists the upcoming bookings of a device.
+#[get("/device/<device>")]
+pub async fn get_device_bookings(
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let booking_results =
+			load_bookings(c, BookingFilter::Device(device), Utc::now().naive_utc())?;
+
+		Ok(json!({ "bookings": booking_results }))
+	})
+	.await
+}
+
+/// Lists the upcoming bookings made by a user.
+#[get("/user/<user>")]
+pub async fn get_user_bookings(
+	_user: &AuthedUser,
+	conn: DbConn,
+	user: i32,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let booking_results = load_bookings(c, BookingFilter::User(user), Utc::now().naive_utc())?;
+
+		Ok(json!({ "bookings": booking_results }))
+	})
+	.await
+}
+
+/// Fetches the current user's calendar feed token, along with the URL of their
+/// own bookings feed.
+///
+/// Feeds for individual devices use the same token, at
+/// `/api/bookings/ics/device/<device>?token=<token>`.
+#[get("/feeds")]
+pub async fn get_calendar_feeds(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	let token = conn
+		.run(move |c| get_calendar_feed_token(c, user_id_value, client_ip))
+		.await?;
+
+	Ok(calendar_feeds_json(config, user_id_value, token.as_str()))
+}
+
+/// Replaces the current user's calendar feed token, so that the feed URLs they
+/// have shared stop working, and returns the new one like
+/// [`get_calendar_feeds`] does.
+#[post("/feeds/rotate")]
+pub async fn rotate_calendar_feeds(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	let token = conn
+		.run(move |c| rotate_calendar_feed_token(c, user_id_value, client_ip))
+		.await?;
+
+	Ok(calendar_feeds_json(config, user_id_value, token.as_str()))
+}
+
+fn calendar_feeds_json(config: &AppConfig, user_id_value: i32, token: &str) -> JsonValue {
+	let user_feed = format!(
+		"{}/api/bookings/ics/user/{user_id_value}?token={token}",
+		config
+			.public_url
+			.as_deref()
+			.unwrap_or_default()
+			.trim_end_matches('/')
+	);
+
+	json!({ "token": token, "userFeed": user_feed })
+}
+
+/// The calendar feed of a device's bookings.
+#[get("/ics/device/<device>?<token>")]
+pub async fn get_device_calendar_feed(
+	conn: DbConn,
+	device: String,
+	token: String,
+) -> Result<FileFromMemory, Error> {
+	let file_name = format!("{device}.ics");
+	let calendar_name = format!("Bookings for {device}");
+	let calendar = conn
+		.run(move |c| {
+			render_calendar_feed(
+				c,
+				token.as_str(),
+				BookingFilter::Device(device),
+				calendar_name.as_str(),
+			)
+		})
+		.await?;
+
+	Ok(FileFromMemory::new(
+		file_name.as_str(),
+		calendar.into_bytes(),
+	))
+}
+
+/// The calendar feed of the bookings made by a user.
+#[get("/ics/user/<user>?<token>")]
+pub async fn get_user_calendar_feed(
+	conn: DbConn,
+	user: i32,
+	token: String,
+) -> Result<FileFromMemory, Error> {
+	let calendar = conn
+		.run(move |c| {
+			render_calendar_feed(
+				c,
+				token.as_str(),
+				BookingFilter::User(user),
+				"Device Bookings",
+			)
+		})
+		.await?;
+
+	Ok(FileFromMemory::new(
+		format!("bookings-{user}.ics").as_str(),
+		calendar.into_bytes(),
+	))
+}
+
+fn render_calendar_feed(
+	conn: &mut SqliteConnection,
+	token: &str,
+	filter: BookingFilter,
+	calendar_name: &str,
+) -> Result<String, Error> {
+	let token_user_id = load_calendar_feed_token_user(conn, token)?
+		.ok_or(UserError::NotFound("Invalid calendar feed."))?;
+	// A token only grants access to its own user's bookings feed
+	if matches!(filter, BookingFilter::User(feed_user_id) if feed_user_id != token_user_id) {
+		return Err(UserError::Forbidden("This calendar feed belongs to another user.").into());
+	}
+
+	let since = Utc::now().naive_utc() - ChronoDuration::days(FEED_HISTORY_DAYS);
+	let events = load_bookings(conn, filter, since)?
+		.into_iter()
+		.map(|booking| CalendarEvent {
+			uid:         format!("booking-{}@pecan", booking.id),
+			starts_at:   booking.starts_at,
+			ends_at:     booking.ends_at,
+			summary:     format!("{} - {}", booking.device_id, booking.user),
+			description: booking.purpose.into_owned(),
+		})
+		.collect::<Vec<_>>();
+
+	Ok(render_calendar(calendar_name, events.as_slice()))
+}
+
+/// Loads the bookings that haven't been cancelled and end after `since`, in
+/// chronological order.
+fn load_bookings<'a>(
+	conn: &mut SqliteConnection,
+	filter: BookingFilter,
+	since: NaiveDateTime,
+) -> Result<Vec<DeviceBookingDisplay<'a>>, Error> {
+	// Uses
+	use schema::{device_bookings::dsl::*, device_key_info::dsl::*, user_info::dsl::user_info};
+
+	let mut bookings_query = device_bookings
+		.inner_join(device_key_info)
+		.inner_join(user_info)
+		.filter(cancelled.eq(false))
+		.filter(ends_at.gt(since))
+		.into_boxed();
+	bookings_query = match filter {
+		BookingFilter::Device(device) => bookings_query.filter(device_id.eq(device)),
+		BookingFilter::User(user) => bookings_query.filter(user_id.eq(user)),
+	};
+
+	bookings_query
+		.order_by(starts_at)
+		.select(DEVICE_BOOKING)
+		.load::<DeviceBookingDisplay<'_>>(conn)
+		.with_context("unable to load the device bookings")
+}
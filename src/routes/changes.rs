@@ -0,0 +1,397 @@
+// Uses
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use diesel::{
+	query_builder::{BoxedSqlQuery, SqlQuery},
+	sql_query,
+	sql_types::{BigInt, Integer, Text, Timestamp},
+	sqlite::Sqlite,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	response::stream::{Event, EventStream},
+	routes,
+	serde::json::{json, Value as JsonValue},
+	tokio::{select, sync::broadcast::error::RecvError},
+	Route,
+	Shutdown,
+	State,
+};
+use serde_json::to_string as to_json_string;
+
+use super::{file_from_memory::FileFromMemory, Routable};
+use crate::{
+	auth::AuthedUser,
+	db::{
+		models::{DeviceChangeHistoryEntry, RowCount},
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+	live_changes::LiveChanges,
+	webhooks::WebhookEvent,
+};
+
+// Constants
+/// The number of changes to return per page, if not specified.
+const CHANGE_HISTORY_DEFAULT_PAGE_SIZE: u32 = 50;
+/// The most changes that can be returned per page.
+const CHANGE_HISTORY_MAX_PAGE_SIZE: u32 = 500;
+/// The most changes that can be exported at once, to keep the export from
+/// taking up too much memory.
+const CHANGE_EXPORT_MAX_ROWS: usize = 100_000;
+/// The columns of a CSV export, in order.
+const CHANGE_EXPORT_CSV_HEADER: &str = "id,timestamp,device_id,location_id,location,user_id,user,\
+                                        done_automatically,event,batch_id,change";
+
+/// The route for this section.
+pub(super) struct ChangesApi;
+impl Routable for ChangesApi {
+	const PATH: &'static str = "/changes";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> =
+		&|| routes![stream_changes, get_change_history, export_change_history];
+}
+
+// Type Definitions
+/// The conditions to filter the change history by.
+#[derive(Debug)]
+struct ChangeHistoryFilter {
+	user:     Option<i32>,
+	/// Inclusive.
+	since:    Option<NaiveDateTime>,
+	/// Exclusive.
+	until:    Option<NaiveDateTime>,
+	/// Limits the changes to devices currently in this location or any of its
+	/// descendants.
+	location: Option<i32>,
+	/// Limits the changes to ones that touched this column's data.
+	column:   Option<i32>,
+	event:    Option<WebhookEvent>,
+}
+
+impl ChangeHistoryFilter {
+	fn new(
+		user: Option<i32>,
+		since: Option<&str>,
+		until: Option<&str>,
+		location: Option<i32>,
+		column: Option<i32>,
+		event: Option<&str>,
+	) -> Result<Self, Error> {
+		Ok(Self {
+			user,
+			since: since.map(parse_query_timestamp).transpose()?,
+			until: until.map(parse_query_timestamp).transpose()?,
+			location,
+			column,
+			event: event
+				.map(|event_name| {
+					WebhookEvent::from_name(event_name)
+						.ok_or(UserError::BadRequest("Invalid event."))
+				})
+				.transpose()?,
+		})
+	}
+
+	/// Builds a query from `sql`, which selects from the change history, with
+	/// the filter conditions appended and bound.
+	fn query<'f>(&self, sql: &str) -> BoxedSqlQuery<'f, Sqlite, SqlQuery> {
+		let mut query = sql_query(sql).into_boxed();
+		let mut keyword = "WHERE";
+		let mut condition = |query: BoxedSqlQuery<'f, Sqlite, SqlQuery>, sql: &str| {
+			let query = query.sql(format!("{keyword} {sql}\n"));
+			keyword = "AND";
+			query
+		};
+
+		if let Some(user) = self.user {
+			query = condition(query, "ch.user_id = ?").bind::<Integer, _>(user);
+		}
+		if let Some(since) = self.since {
+			query = condition(query, "ch.timestamp >= ?").bind::<Timestamp, _>(since);
+		}
+		if let Some(until) = self.until {
+			query = condition(query, "ch.timestamp < ?").bind::<Timestamp, _>(until);
+		}
+		if let Some(location) = self.location {
+			query = condition(
+				query,
+				"dki.location_id IN (SELECT ls.location_id FROM location_subtrees AS ls WHERE \
+				 ls.ancestor_id = ?)",
+			)
+			.bind::<Integer, _>(location);
+		}
+		if let Some(column) = self.column {
+			query = condition(
+				query,
+				"EXISTS (SELECT 1 FROM json_each(ch.change, '$.deviceData') AS dd WHERE \
+				 json_extract(dd.value, '$.columnDefinitionId') = ?)",
+			)
+			.bind::<Integer, _>(column);
+		}
+		if let Some(event) = self.event {
+			query = condition(query, "ch.event = ?").bind::<Text, _>(event.name());
+		}
+
+		query
+	}
+}
+
+/// Streams device changes as Server-Sent Events, as they're committed.
+///
+/// `location` limits the changes to devices in that location or any of its
+/// descendants, and `device` limits them to a single device.
+///
+/// Each change is sent as a `change` event. If the client falls too far behind
+/// and some changes are skipped, a `lagged` event is sent so that it knows to
+/// reload.
+#[get("/stream?<location>&<device>")]
+pub fn stream_changes(
+	_user: &AuthedUser,
+	live_changes: &State<LiveChanges>,
+	mut shutdown: Shutdown,
+	location: Option<i32>,
+	device: Option<String>,
+) -> EventStream![] {
+	let mut receiver = live_changes.subscribe();
+	EventStream! {
+		loop {
+			let live_change = select! {
+				message = receiver.recv() => match message {
+					Ok(live_change) => live_change,
+					Err(RecvError::Closed) => break,
+					Err(RecvError::Lagged(_)) => {
+						yield Event::empty().event("lagged");
+						continue;
+					}
+				},
+				() = &mut shutdown => break,
+			};
+
+			if live_change.matches(location, device.as_deref()) {
+				yield Event::json(&live_change)
+					.event("change")
+					.id(live_change.change_id.to_string());
+			}
+		}
+	}
+}
+
+/// Lists the changes made to every device, newest first, a page at a time.
+///
+/// `user` limits the changes to the ones made by a user, `since` and `until`
+/// to a time range (either `YYYY-MM-DD` or `YYYY-MM-DDTHH:MM:SS`, with `until`
+/// being exclusive), `location` to devices currently in that location or any
+/// of its descendants, `column` to changes to that column's data, and `event`
+/// to one kind of change, as named for webhooks. `page` starts from 1.
+#[get("/history?<user>&<since>&<until>&<location>&<column>&<event>&<page>&<limit>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn get_change_history(
+	_user: &AuthedUser,
+	conn: DbConn,
+	user: Option<i32>,
+	since: Option<String>,
+	until: Option<String>,
+	location: Option<i32>,
+	column: Option<i32>,
+	event: Option<String>,
+	page: Option<u32>,
+	limit: Option<u32>,
+) -> Result<JsonValue, Error> {
+	let filter = ChangeHistoryFilter::new(
+		user,
+		since.as_deref(),
+		until.as_deref(),
+		location,
+		column,
+		event.as_deref(),
+	)?;
+	let page = page.unwrap_or(1).max(1);
+	let limit = limit
+		.unwrap_or(CHANGE_HISTORY_DEFAULT_PAGE_SIZE)
+		.clamp(1, CHANGE_HISTORY_MAX_PAGE_SIZE);
+	conn.run(move |c| {
+		let history_sql = include_str!(concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/src/sql/change_history.sql"
+		));
+
+		let total_count = filter
+			.query(format!("SELECT COUNT(*) AS count FROM (\n{history_sql}").as_str())
+			.sql(")")
+			.get_result::<RowCount>(c)
+			.with_context("unable to count the device changes")?
+			.count;
+		let change_results = filter
+			.query(history_sql)
+			.sql("ORDER BY ch.id DESC\nLIMIT ? OFFSET ?")
+			.bind::<BigInt, _>(i64::from(limit))
+			.bind::<BigInt, _>(i64::from(page - 1) * i64::from(limit))
+			.load::<DeviceChangeHistoryEntry<'_>>(c)
+			.with_context("unable to load the device change history")?;
+
+		Ok(json!({
+			"changes": change_results,
+			"page": page,
+			"limit": limit,
+			"totalCount": total_count,
+		}))
+	})
+	.await
+}
+
+/// Exports the changes made to every device, oldest first, as either `csv` or
+/// `jsonl` (JSON Lines).
+///
+/// This takes the same filters as [`get_change_history`], but isn't paged.
+/// Exports of more than [`CHANGE_EXPORT_MAX_ROWS`] changes are refused.
+#[get("/export/<format>?<user>&<since>&<until>&<location>&<column>&<event>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn export_change_history(
+	_user: &AuthedUser,
+	conn: DbConn,
+	format: &str,
+	user: Option<i32>,
+	since: Option<String>,
+	until: Option<String>,
+	location: Option<i32>,
+	column: Option<i32>,
+	event: Option<String>,
+) -> Result<FileFromMemory, Error> {
+	let as_csv = match format {
+		"csv" => true,
+		"jsonl" => false,
+		_ => return Err(UserError::BadRequest("Invalid export format.").into()),
+	};
+	let filter = ChangeHistoryFilter::new(
+		user,
+		since.as_deref(),
+		until.as_deref(),
+		location,
+		column,
+		event.as_deref(),
+	)?;
+	let change_results = conn
+		.run(move |c| load_change_history(c, &filter, CHANGE_EXPORT_MAX_ROWS + 1))
+		.await?;
+	if change_results.len() > CHANGE_EXPORT_MAX_ROWS {
+		return Err(UserError::BadRequest(
+			"There are too many changes to export at once. Please narrow down the filters.",
+		)
+		.into());
+	}
+
+	let mut contents = String::new();
+	if as_csv {
+		contents.push_str(CHANGE_EXPORT_CSV_HEADER);
+		contents.push_str("\r\n");
+		for entry in &change_results {
+			let fields = [
+				entry.id.to_string(),
+				entry.timestamp.to_string(),
+				entry.device_id.to_string(),
+				entry.location_id.to_string(),
+				entry.location.to_string(),
+				entry.user_id.map(|id| id.to_string()).unwrap_or_default(),
+				entry.user.as_deref().unwrap_or_default().to_owned(),
+				entry.done_automatically.to_string(),
+				entry.event.to_string(),
+				entry.batch_id.map(|id| id.to_string()).unwrap_or_default(),
+				entry.change.to_string(),
+			];
+			let row = fields
+				.iter()
+				.map(|field| csv_field(field))
+				.collect::<Vec<_>>()
+				.join(",");
+			contents.push_str(row.as_str());
+			contents.push_str("\r\n");
+		}
+	} else {
+		for entry in &change_results {
+			contents.push_str(
+				to_json_string(entry)
+					.with_context("unable to serialise a device change to JSON")?
+					.as_str(),
+			);
+			contents.push('\n');
+		}
+	}
+
+	let file_name = if as_csv {
+		"changes.csv"
+	} else {
+		"changes.jsonl"
+	};
+	Ok(FileFromMemory::new(file_name, contents.into_bytes()))
+}
+
+/// Loads up to `limit` changes that match the filter, oldest first.
+fn load_change_history<'a>(
+	conn: &mut SqliteConnection,
+	filter: &ChangeHistoryFilter,
+	limit: usize,
+) -> Result<Vec<DeviceChangeHistoryEntry<'a>>, Error> {
+	filter
+		.query(include_str!(concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/src/sql/change_history.sql"
+		)))
+		.sql("ORDER BY ch.id\nLIMIT ?")
+		.bind::<BigInt, _>(limit as i64)
+		.load::<DeviceChangeHistoryEntry<'_>>(conn)
+		.with_context("unable to load the device change history")
+}
+
+/// Parses a timestamp given in a query string, which can either be a date
+/// (meaning the start of that day) or a date and time, in UTC.
+fn parse_query_timestamp(value: &str) -> Result<NaiveDateTime, Error> {
+	NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+		.or_else(|_| {
+			NaiveDate::parse_from_str(value, "%Y-%m-%d").map(|date| date.and_time(NaiveTime::MIN))
+		})
+		.map_err(|_| UserError::BadRequest("Invalid timestamp.").into())
+}
+
+/// Quotes a CSV field if it needs to be.
+///
+/// Fields that a spreadsheet would treat as a formula are prefixed with `'`,
+/// so that opening the export can't run anything.
+fn csv_field(value: &str) -> String {
+	let value = if value.starts_with(['=', '+', '-', '@']) {
+		format!("'{value}")
+	} else {
+		value.to_owned()
+	};
+
+	if value.contains([',', '"', '\r', '\n']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use super::*;
+
+	#[test]
+	fn csv_fields_are_quoted_when_needed() {
+		assert_eq!(csv_field("plain"), "plain");
+		assert_eq!(csv_field("a,b"), "\"a,b\"");
+		assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+	}
+
+	#[test]
+	fn csv_formulas_are_neutralised() {
+		assert_eq!(csv_field("=1+1"), "'=1+1");
+		assert_eq!(csv_field("+1"), "'+1");
+		assert_eq!(csv_field("-1"), "'-1");
+		assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+		assert_eq!(
+			csv_field("=HYPERLINK(\"x\",\"y\")"),
+			"\"'=HYPERLINK(\"\"x\"\",\"\"y\"\")\""
+		);
+	}
+}
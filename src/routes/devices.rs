@@ -1,15 +1,17 @@
 // Uses
-use std::borrow::Cow;
+use std::{borrow::Cow, collections::HashSet};
 
 use base64::{engine::general_purpose::STANDARD as BASE64_STANDARD, Engine};
+use chrono::{NaiveDateTime, Utc};
 use diesel::{
-	dsl::exists,
+	delete,
+	dsl::{exists, sql},
 	insert_into,
 	query_builder::{BoxedSqlQuery, SqlQuery},
 	result::OptionalExtension,
 	select,
 	sql_query,
-	sql_types::{Integer, Nullable, Text},
+	sql_types::{Bool, Integer, Nullable, Text, Timestamp},
 	sqlite::Sqlite,
 	update,
 	upsert::excluded,
@@ -33,16 +35,38 @@ use rocket::{
 	State,
 };
 
-use super::Routable;
+use super::{if_match::IfMatch, Routable};
 use crate::{
 	auth::AuthedUser,
-	config::AppConfig,
-	db::{change_log::*, models::*, schema, util::data_value_exists, DbConn},
+	config::{AppConfig, DeviceIdSettings},
+	db::{
+		change_log::*,
+		models::*,
+		schema,
+		util::{component_data_value_exists, data_value_exists, fetch_new_rowid_on},
+		DbConn,
+	},
 	error::{Context, Error, UserError},
+	lifecycle::load_device_lifecycle,
 	routes::file_from_memory::FileFromMemory,
-	util::{gen_new_attachment_id, gen_new_component_id, gen_new_device_id},
+	util::{
+		device_id_matches_format,
+		gen_new_attachment_id,
+		gen_new_component_id,
+		gen_new_device_id,
+		verify_device_id,
+	},
 };
 
+// Constants
+/// Matches the device changes that touch the sections the edit form overwrites
+/// (key info, data, components and attachments), which are the ones that make
+/// up a device's version.
+const EDIT_FORM_CHANGE_FILTER: &str = "(json_extract(change, '$.deviceKeyInfo') IS NOT NULL OR \
+                                       json_extract(change, '$.deviceData') IS NOT NULL OR \
+                                       json_extract(change, '$.deviceComponents') IS NOT NULL OR \
+                                       json_extract(change, '$.deviceAttachments') IS NOT NULL)";
+
 /// The route for this section.
 pub(super) struct DevicesApi;
 impl Routable for DevicesApi {
@@ -54,13 +78,22 @@ impl Routable for DevicesApi {
 			search_devices,
 			get_device,
 			checkout_device,
+			set_device_parent,
+			promote_component,
 			create_device,
 			update_device,
 			delete_device,
 			restore_device,
+			get_deleted_devices,
+			get_deleted_device,
+			bulk_edit_devices,
 			get_attachment,
 			get_device_exists,
-			get_data_value_exists
+			get_data_value_exists,
+			get_component_data_value_exists,
+			get_reserved_device_ids,
+			reserve_device_ids,
+			release_device_ids
 		]
 	};
 }
@@ -69,6 +102,14 @@ impl Routable for DevicesApi {
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdatedDeviceInfo {
+	/// An explicit ID for a new device, such as one from a pre-printed or
+	/// reserved label. This can't be used to change an existing device's ID.
+	#[serde(default)]
+	device_id:   Option<String>,
+	/// The version of the device that the changes were made to, as returned by
+	/// [`get_device`]. This can also be provided in the `If-Match` header.
+	#[serde(default)]
+	version:     Option<i32>,
 	location_id: i32,
 	column_data: Vec<SubmittedColumnData>,
 	components:  Vec<UpdatedDeviceComponent>,
@@ -80,6 +121,8 @@ pub struct UpdatedDeviceComponent {
 	component_id:   Option<String>,
 	deleted:        bool,
 	component_type: String,
+	#[serde(default)]
+	column_data:    Vec<SubmittedComponentData>,
 }
 #[derive(Deserialize)]
 #[serde(untagged)]
@@ -100,9 +143,45 @@ pub enum UpdatedDeviceAttachment {
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SubmittedSearchQuery {
-	device_id:   String,
-	location_id: Option<i32>,
-	column_data: Vec<SubmittedColumnData>,
+	device_id:        String,
+	/// Matches devices in the location or any of its descendants.
+	location_id:      Option<i32>,
+	column_data:      Vec<SubmittedColumnData>,
+	/// Matches devices with a component that has all of the given values.
+	#[serde(default)]
+	component_data:   Vec<SubmittedComponentData>,
+	/// Limits the results to devices that are free for the whole time range -
+	/// not booked, and not out on a loan that's due back after it starts.
+	#[serde(default)]
+	available_during: Option<TimeRange>,
+}
+impl SubmittedSearchQuery {
+	/// Checks the parts of the query that deserialising it can't.
+	pub(super) fn validate(&self) -> Result<(), Error> {
+		if let Some(time_range) = &self.available_during {
+			if time_range.starts_at >= time_range.ends_at {
+				return Err(
+					UserError::BadRequest("The time range must end after it starts.").into(),
+				);
+			}
+		}
+
+		Ok(())
+	}
+}
+/// A set of devices to operate on at once - either an explicit list, or all of
+/// the results of a search.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeviceSelection {
+	DeviceIds(Vec<String>),
+	SearchQuery(SubmittedSearchQuery),
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeRange {
+	starts_at: NaiveDateTime,
+	ends_at:   NaiveDateTime,
 }
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -112,9 +191,79 @@ pub struct SubmittedColumnData {
 }
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
+pub struct SubmittedComponentData {
+	component_column_definition_id: i32,
+	data_value:                     String,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CheckoutInfo {
-	device_id:   String,
+	device_id:     String,
+	location_id:   i32,
+	/// Whether to move all of the devices attached beneath this one as well.
+	#[serde(default)]
+	move_children: bool,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceParentInfo {
+	device_id:        String,
+	/// The device to attach this one to, or nothing to detach it.
+	#[serde(default)]
+	parent_device_id: Option<String>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_field_names)]
+pub struct ComponentPromotionInfo {
+	device_id:            String,
+	component_id:         String,
+	/// The column to copy the component's type into on the new device.
+	column_definition_id: i32,
+	/// The location of the new device. Defaults to the original device's
+	/// location.
+	#[serde(default)]
+	location_id:          Option<i32>,
+}
+/// A device attached beneath another one, along with everything attached
+/// beneath it.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceTreeNode<'a> {
+	device_id:   Cow<'a, str>,
 	location_id: i32,
+	location:    Cow<'a, str>,
+	children:    Vec<DeviceTreeNode<'a>>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkEditInfo {
+	devices:     DeviceSelection,
+	#[serde(default)]
+	column_data: Vec<SubmittedColumnData>,
+	#[serde(default)]
+	location_id: Option<i32>,
+	#[serde(default)]
+	deletion:    Option<BulkDeletionChange>,
+	/// Only calculates the changes that would be made, without making them.
+	#[serde(default)]
+	preview:     bool,
+}
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum BulkDeletionChange {
+	Delete,
+	Restore,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservationInfo {
+	count: u32,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReservationReleaseInfo {
+	device_ids: Vec<String>,
 }
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -122,8 +271,16 @@ pub struct ValueExistsQuery {
 	device_id: Option<String>,
 	value:     String,
 }
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComponentValueExistsQuery {
+	device_id:    Option<String>,
+	component_id: Option<String>,
+	value:        String,
+}
 
-/// Fetches the column definitions and locations.
+/// Fetches the column definitions (for both devices and components) and
+/// locations.
 #[get("/definitions")]
 pub async fn get_definitions(
 	config: &State<AppConfig>,
@@ -137,7 +294,9 @@ pub async fn get_definitions(
 		use schema::{
 			column_definitions::dsl::*,
 			column_possible_values::dsl::*,
-			locations::dsl::*,
+			component_column_definitions::dsl::component_column_definitions,
+			component_column_possible_values::dsl::component_column_possible_values,
+			location_paths::dsl::*,
 		};
 
 		// Load the data
@@ -163,33 +322,70 @@ pub async fn get_definitions(
 			.zip(possible_values_results)
 			.collect::<Vec<_>>();
 
-		let location_results = locations
-			.order_by(schema::locations::dsl::name)
-			.load::<LocationDefinition<'_>>(c)
+		let component_column_definition_results = component_column_definitions
+			.left_join(
+				component_column_possible_values.on(schema::component_column_definitions::dsl::default_value_id
+					.eq(schema::component_column_possible_values::dsl::id.nullable())),
+			)
+			.order_by(schema::component_column_definitions::dsl::ordering_key)
+			.then_order_by(schema::component_column_definitions::dsl::id)
+			.select(COMPONENT_COLUMN_DEFINITION())
+			.load::<ComponentColumnDefinitionSelected<'_>>(c)
+			.with_context("unable to load the component column definitions")?;
+
+		let component_possible_values_results =
+			ComponentColumnPossibleValue::belonging_to(&component_column_definition_results)
+				.order_by(schema::component_column_possible_values::dsl::value)
+				.load::<ComponentColumnPossibleValue<'_>>(c)
+				.with_context("unable to load the component column possible values")?
+				.grouped_by(&component_column_definition_results);
+
+		let component_column_results = component_column_definition_results
+			.into_iter()
+			.zip(component_possible_values_results)
+			.collect::<Vec<_>>();
+
+		let location_results = location_paths
+			.order_by(path)
+			.load::<LocationPath<'_>>(c)
 			.with_context("unable to load the locations")?;
 
 		Ok(
-			json!({ "currentUser": user_clone, "columnDefinitions": column_results, "locations": location_results, "maxAttachmentSize": max_attachment_size }),
+			json!({ "currentUser": user_clone, "columnDefinitions": column_results, "componentColumnDefinitions": component_column_results, "locations": location_results, "maxAttachmentSize": max_attachment_size }),
 		)
 	})
 	.await
 }
 
 /// Fetches a device by ID.
+///
+/// The `version` in the results has to be provided when updating the device.
 #[get("/get/<device>")]
 pub async fn get_device(
+	config: &State<AppConfig>,
 	_user: &AuthedUser,
 	conn: DbConn,
 	device: String,
 ) -> Result<JsonValue, Error> {
+	let device_id_settings = config.device_ids.clone();
 	conn.run(move |c| {
+		verify_device_id(c, &device_id_settings, device.as_str())?;
+
 		let (
 			device_key_info_results,
 			device_data_results,
 			device_component_results,
 			device_attachment_results,
 			device_change_results,
-		) = load_device_info(c, device.as_str())?;
+		) = load_device_info(c, device.as_str(), false)?;
+		let device_parent_results = load_device_parents(c, device_key_info_results.id)?;
+		let device_descendant_results = load_device_descendants(c, device_key_info_results.id)?;
+		let device_lifecycle_result = load_device_lifecycle(c, device_key_info_results.id)?;
+		let device_version = load_device_version(c, device_key_info_results.id)?;
+		let device_children = build_device_tree(
+			device_descendant_results.as_slice(),
+			device_key_info_results.id,
+		);
 
 		// Return the results
 		// This odd return format is to match how the data is returned in search
@@ -199,46 +395,103 @@ pub async fn get_device(
 			"deviceComponents": device_component_results,
 			"deviceAttachments": device_attachment_results,
 			"deviceChanges": device_change_results,
+			"deviceParents": device_parent_results,
+			"deviceChildren": device_children,
+			"deviceLifecycle": device_lifecycle_result,
+			"version": device_version,
 		}))
 	})
 	.await
 }
 
+/// Fetches the current version of a device, which is the ID of its most recent
+/// change to the sections the edit form overwrites. (or 0 if they've never been
+/// changed)
+///
+/// Bookings, loans, lifecycle changes and the like don't count, since an edit
+/// made in the meantime doesn't touch them.
+pub fn load_device_version(conn: &mut SqliteConnection, internal_id: i32) -> Result<i32, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	device_changes
+		.filter(device_key_info_id.eq(internal_id))
+		.filter(sql::<Bool>(EDIT_FORM_CHANGE_FILTER))
+		.select(id)
+		.order_by(id.desc())
+		.first::<i32>(conn)
+		.optional()
+		.with_context("unable to load the device version")
+		.map(Option::unwrap_or_default)
+}
+
+/// Fetches the changes made to the sections the edit form overwrites after the
+/// given version, oldest first.
+fn load_device_changes_since<'a>(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+	version: i32,
+) -> Result<Vec<DeviceChangeDisplay<'a>>, Error> {
+	// Uses
+	use schema::{device_changes::dsl::*, user_info::dsl::user_info};
+
+	device_changes
+		.left_join(user_info)
+		.filter(device_key_info_id.eq(internal_id))
+		.filter(schema::device_changes::dsl::id.gt(version))
+		.filter(sql::<Bool>(EDIT_FORM_CHANGE_FILTER))
+		.order_by(schema::device_changes::dsl::id)
+		.select(DEVICE_CHANGE())
+		.get_results::<DeviceChangeDisplay<'_>>(conn)
+		.with_context("unable to load the device changes")
+}
+
 pub type CompleteDeviceInfo<'a> = (
 	DeviceInfo<'a>,
 	Vec<DeviceData<'a>>,
-	Vec<DeviceComponent<'a>>,
+	Vec<DeviceComponentWithData<'a>>,
 	Vec<DeviceAttachmentMetadata<'a>>,
 	Vec<DeviceChangeDisplay<'a>>,
 );
 /// Fetches a device by ID.
+///
+/// Deleted devices are treated as not existing unless `include_deleted` is
+/// set.
 pub fn load_device_info<'a>(
 	conn: &mut SqliteConnection,
 	device: &str,
+	include_deleted: bool,
 ) -> Result<CompleteDeviceInfo<'a>, Error> {
 	// Uses
 	use schema::{
 		column_definitions::dsl::*,
+		component_column_definitions::dsl::component_column_definitions,
 		device_attachments::dsl::*,
 		device_changes::dsl::*,
+		device_component_data::dsl::*,
 		device_components::dsl::*,
 		device_data::dsl::*,
 		device_key_info::dsl::*,
-		locations::dsl::*,
+		location_paths::dsl::*,
 		user_info::dsl::*,
 	};
 
 	// Load from the database
+	let allowed_deletion_statuses = if include_deleted {
+		[false, true].as_slice()
+	} else {
+		[false].as_slice()
+	};
 	let device_key_info_result = device_key_info
-		.filter(schema::device_key_info::dsl::deleted.eq(false))
+		.filter(schema::device_key_info::dsl::deleted.eq_any(allowed_deletion_statuses))
 		.filter(device_id.eq(device))
-		.inner_join(locations)
+		.inner_join(location_paths)
 		.select((
 			schema::device_key_info::dsl::id,
 			device_id,
 			schema::device_key_info::dsl::deleted,
 			location_id,
-			schema::locations::dsl::name,
+			path,
 			device_changes
 				.select(timestamp)
 				.filter(
@@ -271,6 +524,23 @@ pub fn load_device_info<'a>(
 		.order_by(component_type)
 		.get_results::<DeviceComponent<'_>>(conn)
 		.with_context("unable to load the device components")?;
+	let device_component_data_results =
+		DeviceComponentData::belonging_to(&device_component_results)
+			.inner_join(component_column_definitions)
+			.order_by(schema::component_column_definitions::dsl::ordering_key)
+			.then_order_by(component_column_definition_id)
+			.select(DEVICE_COMPONENT_DATA)
+			.load::<DeviceComponentData<'_>>(conn)
+			.with_context("unable to load the device component data")?
+			.grouped_by(&device_component_results);
+	let device_component_results = device_component_results
+		.into_iter()
+		.zip(device_component_data_results)
+		.map(|(component, column_data)| DeviceComponentWithData {
+			component,
+			column_data,
+		})
+		.collect::<Vec<_>>();
 
 	let device_attachment_results = DeviceAttachmentMetadata::belonging_to(&device_key_info_result)
 		.filter(schema::device_attachments::dsl::deleted.eq(false))
@@ -296,6 +566,93 @@ pub fn load_device_info<'a>(
 	))
 }
 
+/// Fetches the IDs of the devices that a device is attached beneath, starting
+/// with its direct parent.
+fn load_device_parents(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+) -> Result<Vec<String>, Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	let mut parent_device_ids = Vec::new();
+	let mut current_parent_id = device_key_info
+		.filter(id.eq(internal_id))
+		.select(parent_device_id)
+		.get_result::<Option<i32>>(conn)
+		.with_context("unable to query the database for a device's parent")?;
+	while let Some(current_id) = current_parent_id {
+		let (current_device_id, next_parent_id) = device_key_info
+			.filter(id.eq(current_id))
+			.select((device_id, parent_device_id))
+			.get_result::<(String, Option<i32>)>(conn)
+			.with_context("unable to query the database for a device's parent")?;
+		parent_device_ids.push(current_device_id);
+		current_parent_id = next_parent_id;
+	}
+
+	Ok(parent_device_ids)
+}
+
+/// Fetches every device attached beneath a device, at any depth.
+fn load_device_descendants<'a>(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+) -> Result<Vec<DeviceTreeEntry<'a>>, Error> {
+	sql_query(include_str!(concat!(
+		env!("CARGO_MANIFEST_DIR"),
+		"/src/sql/device_descendants.sql"
+	)))
+	.bind::<Integer, _>(internal_id)
+	.load::<DeviceTreeEntry<'_>>(conn)
+	.with_context("unable to load the attached devices")
+}
+
+/// Nests the flat list of descendants under their parents, starting with the
+/// direct children of `parent`.
+fn build_device_tree<'a>(
+	descendants: &[DeviceTreeEntry<'a>],
+	parent: i32,
+) -> Vec<DeviceTreeNode<'a>> {
+	descendants
+		.iter()
+		.filter(|entry| entry.parent_device_id == parent)
+		.map(|entry| DeviceTreeNode {
+			device_id:   entry.device_id.clone(),
+			location_id: entry.location_id,
+			location:    entry.location.clone(),
+			children:    build_device_tree(descendants, entry.id),
+		})
+		.collect()
+}
+
+/// Checks whether a device is the same as, or attached beneath, another one,
+/// by walking up its parents.
+fn device_is_within(
+	conn: &mut SqliteConnection,
+	device: i32,
+	ancestor: i32,
+) -> Result<bool, Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	let mut current = Some(device);
+	while let Some(current_id) = current {
+		if current_id == ancestor {
+			return Ok(true);
+		}
+		current = device_key_info
+			.filter(id.eq(current_id))
+			.select(parent_device_id)
+			.get_result::<Option<i32>>(conn)
+			.optional()
+			.with_context("unable to query the database for a device's parent")?
+			.flatten();
+	}
+
+	Ok(false)
+}
+
 fn perform_search(
 	conn: &mut SqliteConnection,
 	device_key_info_query: BoxedSqlQuery<'_, Sqlite, SqlQuery>,
@@ -348,92 +705,206 @@ pub async fn search_devices_default(_user: &AuthedUser, conn: DbConn) -> Result<
 /// Performs a search query for a user.
 #[post("/search", data = "<search_query>")]
 pub async fn search_devices(
+	config: &State<AppConfig>,
 	_user: &AuthedUser,
 	conn: DbConn,
 	search_query: Json<SubmittedSearchQuery>,
 ) -> Result<JsonValue, Error> {
+	let device_id_settings = config.device_ids.clone();
+	conn.run(move |c| {
+		verify_device_id(c, &device_id_settings, search_query.device_id.as_str())?;
+		search_query.validate()?;
+
+		let device_key_info_query = build_search_query(&search_query);
+
+		perform_search(c, device_key_info_query)
+	})
+	.await
+}
+
+/// Builds the query for the device key info of all devices matching a search
+/// query.
+pub(super) fn build_search_query(
+	search_query: &SubmittedSearchQuery,
+) -> BoxedSqlQuery<'static, Sqlite, SqlQuery> {
 	// Check if any column values were specified for searching
 	let search_column_data_is_present = search_query
 		.column_data
 		.iter()
 		.any(|column| !column.data_value.is_empty());
+	let search_component_data_is_present = search_query
+		.component_data
+		.iter()
+		.any(|column| !column.data_value.is_empty());
 
-	conn.run(move |c| {
-		// Search the device key info
-		// This whole thing is *extremely* ugly. This is because Diesel doesn't support
-		// boxed sub-queries, because the boxing operation can't know that it will only
-		// be used in a sub-query where the referenced parent column is valid.
-		// The boxing operation is required because we dynamically add filter conditions
-		// to the query.
-		let mut search_sql = String::from(
-			"SELECT
-				dki.id,
-				dki.device_id,
-				dki.deleted,
-				dki.location_id,
-				l.name AS location,
-				(
-					SELECT
-						dc.timestamp
-					FROM device_changes AS dc
-					WHERE
-						dc.device_key_info_id = dki.id AND
-						dc.done_automatically = 0
-					ORDER BY dc.timestamp DESC
-					LIMIT 1
-				) AS last_updated
-			FROM device_key_info AS dki
-			INNER JOIN locations AS l ON l.id = dki.location_id
-			WHERE
-				dki.device_id LIKE ?
-				AND dki.deleted = 0
-				AND (? IS NULL OR dki.location_id = ?)\n",
+	// Search the device key info
+	// This whole thing is *extremely* ugly. This is because Diesel doesn't support
+	// boxed sub-queries, because the boxing operation can't know that it will only
+	// be used in a sub-query where the referenced parent column is valid.
+	// The boxing operation is required because we dynamically add filter conditions
+	// to the query.
+	let mut search_sql = String::from(
+		"SELECT
+			dki.id,
+			dki.device_id,
+			dki.deleted,
+			dki.location_id,
+			l.path AS location,
+			(
+				SELECT
+					dc.timestamp
+				FROM device_changes AS dc
+				WHERE
+					dc.device_key_info_id = dki.id AND
+					dc.done_automatically = 0
+				ORDER BY dc.timestamp DESC
+				LIMIT 1
+			) AS last_updated
+		FROM device_key_info AS dki
+		INNER JOIN location_paths AS l ON l.id = dki.location_id
+		WHERE
+			dki.device_id LIKE ?
+			AND dki.deleted = 0
+			AND (? IS NULL OR dki.location_id IN (SELECT
+					ls.location_id
+				FROM location_subtrees AS ls
+				WHERE ls.ancestor_id = ?))\n",
+	);
+	let mut bind_params = Vec::new();
+	if search_column_data_is_present {
+		search_sql.push_str(
+			"AND ? = (SELECT
+					COUNT(dd.id)
+				FROM device_data AS dd
+				WHERE dd.device_key_info_id = dki.id AND (",
 		);
-		let mut bind_params = Vec::new();
-		if search_column_data_is_present {
-			search_sql.push_str(
-				"AND ? = (SELECT
-						COUNT(dd.id)
-					FROM device_data AS dd
-					WHERE dd.device_key_info_id = dki.id AND (",
-			);
-			let mut first_entry = true;
-			for column_query in &search_query.column_data {
-				if column_query.data_value.is_empty() {
-					continue;
-				}
-				if !first_entry {
-					search_sql.push_str(" OR ");
-				}
-				search_sql.push_str("(dd.column_definition_id = ? AND dd.data_value LIKE ?)");
-				bind_params.push((
-					column_query.column_definition_id,
-					format!("%{}%", column_query.data_value.as_str()),
-				));
-				first_entry = false;
-			}
-			search_sql.push_str("))\n");
-		}
-		search_sql.push_str("ORDER BY last_updated DESC");
-		// dbg!(&search_sql);
-		let mut device_key_info_query = sql_query(search_sql)
-			.into_boxed()
-			.bind::<Text, _>(format!("%{}%", search_query.device_id.as_str()))
-			.bind::<Nullable<Integer>, _>(search_query.location_id)
-			.bind::<Nullable<Integer>, _>(search_query.location_id);
-		if search_column_data_is_present {
-			device_key_info_query =
-				device_key_info_query.bind::<Integer, _>(bind_params.len() as i32);
+		let mut first_entry = true;
+		for column_query in &search_query.column_data {
+			if column_query.data_value.is_empty() {
+				continue;
+			}
+			if !first_entry {
+				search_sql.push_str(" OR ");
+			}
+			search_sql.push_str("(dd.column_definition_id = ? AND dd.data_value LIKE ?)");
+			bind_params.push((
+				column_query.column_definition_id,
+				format!("%{}%", column_query.data_value.as_str()),
+			));
+			first_entry = false;
 		}
-		for (bind_column_definition_id, bind_data_value_search) in bind_params {
-			device_key_info_query = device_key_info_query
-				.bind::<Integer, _>(bind_column_definition_id)
-				.bind::<Text, _>(bind_data_value_search);
+		search_sql.push_str("))\n");
+	}
+	let mut component_bind_params = Vec::new();
+	if search_component_data_is_present {
+		search_sql.push_str(
+			"AND EXISTS (SELECT
+					dc.id
+				FROM device_components AS dc
+				WHERE
+					dc.device_key_info_id = dki.id
+					AND dc.deleted = 0
+					AND ? = (SELECT
+						COUNT(dcd.id)
+					FROM device_component_data AS dcd
+					WHERE dcd.device_component_id = dc.id AND (",
+		);
+		let mut first_entry = true;
+		for column_query in &search_query.component_data {
+			if column_query.data_value.is_empty() {
+				continue;
+			}
+			if !first_entry {
+				search_sql.push_str(" OR ");
+			}
+			search_sql
+				.push_str("(dcd.component_column_definition_id = ? AND dcd.data_value LIKE ?)");
+			component_bind_params.push((
+				column_query.component_column_definition_id,
+				format!("%{}%", column_query.data_value.as_str()),
+			));
+			first_entry = false;
 		}
+		search_sql.push_str(")))\n");
+	}
+	if search_query.available_during.is_some() {
+		search_sql.push_str(
+			"AND NOT EXISTS (SELECT
+					db.id
+				FROM device_bookings AS db
+				WHERE
+					db.device_key_info_id = dki.id
+					AND db.cancelled = 0
+					AND db.starts_at < ?
+					AND db.ends_at > ?)
+			AND NOT EXISTS (SELECT
+					dl.id
+				FROM device_loans AS dl
+				WHERE
+					dl.device_key_info_id = dki.id
+					AND dl.returned_at IS NULL
+					AND (dl.due_at IS NULL OR dl.due_at > ?))\n",
+		);
+	}
+	search_sql.push_str("ORDER BY last_updated DESC");
+	// dbg!(&search_sql);
+	let mut device_key_info_query = sql_query(search_sql)
+		.into_boxed()
+		.bind::<Text, _>(format!("%{}%", search_query.device_id.as_str()))
+		.bind::<Nullable<Integer>, _>(search_query.location_id)
+		.bind::<Nullable<Integer>, _>(search_query.location_id);
+	if search_column_data_is_present {
+		device_key_info_query = device_key_info_query.bind::<Integer, _>(bind_params.len() as i32);
+	}
+	for (bind_column_definition_id, bind_data_value_search) in bind_params {
+		device_key_info_query = device_key_info_query
+			.bind::<Integer, _>(bind_column_definition_id)
+			.bind::<Text, _>(bind_data_value_search);
+	}
+	if search_component_data_is_present {
+		device_key_info_query =
+			device_key_info_query.bind::<Integer, _>(component_bind_params.len() as i32);
+	}
+	for (bind_component_column_definition_id, bind_data_value_search) in component_bind_params {
+		device_key_info_query = device_key_info_query
+			.bind::<Integer, _>(bind_component_column_definition_id)
+			.bind::<Text, _>(bind_data_value_search);
+	}
+	if let Some(time_range) = &search_query.available_during {
+		device_key_info_query = device_key_info_query
+			.bind::<Timestamp, _>(time_range.ends_at)
+			.bind::<Timestamp, _>(time_range.starts_at)
+			.bind::<Timestamp, _>(time_range.starts_at);
+	}
 
-		perform_search(c, device_key_info_query)
-	})
-	.await
+	device_key_info_query
+}
+
+/// Fetches the IDs of all devices matching a search query, in the same order as
+/// the search results.
+pub(super) fn search_device_ids(
+	conn: &mut SqliteConnection,
+	search_query: &SubmittedSearchQuery,
+) -> Result<Vec<String>, Error> {
+	search_query.validate()?;
+
+	Ok(build_search_query(search_query)
+		.load::<DeviceInfoByName<'_>>(conn)
+		.with_context("unable to load device info")?
+		.into_iter()
+		.map(|device_info| device_info.device_id.into_owned())
+		.collect())
+}
+
+/// Fetches the IDs of all devices in a selection.
+pub(super) fn select_device_ids(
+	conn: &mut SqliteConnection,
+	selection: &DeviceSelection,
+) -> Result<Vec<String>, Error> {
+	match selection {
+		DeviceSelection::DeviceIds(device_ids) => Ok(device_ids.clone()),
+		DeviceSelection::SearchQuery(search_query) => search_device_ids(conn, search_query),
+	}
 }
 
 #[post("/checkout", data = "<checkout_info>")]
@@ -445,12 +916,12 @@ pub async fn checkout_device(
 	let user_id_value = user.0.id;
 	conn.run(move |c| {
 		// Uses
-		use schema::{device_key_info::dsl::*, locations::dsl::*};
+		use schema::{device_key_info::dsl::*, location_paths::dsl::*};
 
 		// Verify the new location
-		let location_name = locations
-			.filter(schema::locations::dsl::id.eq(checkout_info.location_id))
-			.select(schema::locations::dsl::name)
+		let location_name = location_paths
+			.filter(schema::location_paths::dsl::id.eq(checkout_info.location_id))
+			.select(path)
 			.get_result::<String>(c)
 			.optional()
 			.with_context("unable to query the database for location existence")?;
@@ -458,56 +929,382 @@ pub async fn checkout_device(
 			return Err(UserError::BadRequest("Invalid location.").into());
 		};
 
+		let moved_child_device_ids = c
+			.transaction::<_, Error, _>(|tc| {
+				// Get the old value
+				let old_device_key_info = device_key_info
+					.filter(device_id.eq(checkout_info.device_id.as_str()))
+					.get_result::<DeviceKeyInfo<'_>>(tc)
+					.optional()
+					.with_context("unable to query the database for device_key_info existence")?;
+				let Some(old_device_key_info) = old_device_key_info else {
+					return Err(UserError::BadRequest("Invalid device ID.").into());
+				};
+
+				// Ensure that deleted devices aren't modified
+				if old_device_key_info.deleted {
+					return Err(UserError::BadRequest(
+						"The device has been deleted. It cannot be modified.",
+					)
+					.into());
+				}
+
+				// Update the device entry
+				update(
+					device_key_info
+						.filter(schema::device_key_info::dsl::id.eq(old_device_key_info.id)),
+				)
+				.set(location_id.eq(checkout_info.location_id))
+				.execute(tc)
+				.with_context("unable to update device_key_info")?;
+
+				// Log the change in the database
+				if old_device_key_info.location_id != checkout_info.location_id {
+					let diff = DeviceDiff {
+						device_key_info: Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+							location_id: Some(checkout_info.location_id),
+						})),
+						..Default::default()
+					};
+
+					log_change(tc, old_device_key_info.id, user_id_value, &diff)
+						.with_context("unable to log device change")?;
+				}
+
+				// Move the attached devices along with it
+				let mut moved_child_device_ids = Vec::new();
+				if checkout_info.move_children {
+					for descendant in load_device_descendants(tc, old_device_key_info.id)? {
+						if descendant.location_id == checkout_info.location_id {
+							continue;
+						}
+
+						update(
+							device_key_info
+								.filter(schema::device_key_info::dsl::id.eq(descendant.id)),
+						)
+						.set(location_id.eq(checkout_info.location_id))
+						.execute(tc)
+						.with_context("unable to update device_key_info")?;
+
+						let diff = DeviceDiff {
+							device_key_info: Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+								location_id: Some(checkout_info.location_id),
+							})),
+							..Default::default()
+						};
+						log_change(tc, descendant.id, user_id_value, &diff)
+							.with_context("unable to log device change")?;
+
+						moved_child_device_ids.push(descendant.device_id.into_owned());
+					}
+				}
+
+				Ok(moved_child_device_ids)
+			})
+			.with_context("unable to update the device entry")?;
+
+		// Return the results
+		Ok(json!({
+			"deviceId": checkout_info.device_id.clone(),
+			"locationId": checkout_info.location_id,
+			"locationName": location_name,
+			"movedChildDeviceIds": moved_child_device_ids,
+		}))
+	})
+	.await
+}
+
+/// Attaches a device beneath another one, or detaches it from its current
+/// parent.
+///
+/// The change is logged on the device itself, and on both the old and new
+/// parent devices.
+#[post("/parent", data = "<parent_info>")]
+pub async fn set_device_parent(
+	user: &AuthedUser,
+	conn: DbConn,
+	parent_info: Json<DeviceParentInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::device_key_info::dsl::*;
+
 		c.transaction::<_, Error, _>(|tc| {
-			// Get the old value
-			let old_device_key_info = device_key_info
-				.filter(device_id.eq(checkout_info.device_id.as_str()))
-				.get_result::<DeviceKeyInfo<'_>>(tc)
+			let child_result = load_device_key_info(tc, parent_info.device_id.as_str())?;
+			let new_parent_result = match parent_info.parent_device_id.as_deref() {
+				Some(new_parent_device_id) => {
+					let new_parent_result = load_device_key_info(tc, new_parent_device_id)?;
+					if device_is_within(tc, new_parent_result.id, child_result.id)? {
+						return Err(UserError::BadRequest(
+							"A device can't be attached to itself or to a device attached beneath \
+							 it.",
+						)
+						.into());
+					}
+					Some(new_parent_result)
+				}
+				None => None,
+			};
+			let new_parent_id = new_parent_result.as_ref().map(|parent| parent.id);
+			if new_parent_id == child_result.parent_device_id {
+				return Err(UserError::BadRequest("The device is already attached there.").into());
+			}
+			let old_parent_device_id = match child_result.parent_device_id {
+				Some(old_parent_id) => Some(
+					device_key_info
+						.filter(id.eq(old_parent_id))
+						.select(device_id)
+						.get_result::<String>(tc)
+						.with_context("unable to query the database for a device's parent")?,
+				),
+				None => None,
+			};
+
+			update(device_key_info.filter(id.eq(child_result.id)))
+				.set(parent_device_id.eq(new_parent_id))
+				.execute(tc)
+				.with_context("unable to update device_key_info")?;
+
+			// Log the change on every device involved
+			let child_diff = DeviceDiff {
+				device_parent: Some(DeviceParentDiff::SetParent {
+					old_parent_device_id: old_parent_device_id.as_deref().map(Cow::from),
+					new_parent_device_id: parent_info.parent_device_id.as_deref().map(Cow::from),
+				}),
+				..Default::default()
+			};
+			log_change(tc, child_result.id, user_id_value, &child_diff)
+				.with_context("unable to log device change")?;
+			if let Some(old_parent_id) = child_result.parent_device_id {
+				let old_parent_diff = DeviceDiff {
+					device_parent: Some(DeviceParentDiff::RemoveChild {
+						child_device_id: child_result.device_id.clone(),
+					}),
+					..Default::default()
+				};
+				log_change(tc, old_parent_id, user_id_value, &old_parent_diff)
+					.with_context("unable to log device change")?;
+			}
+			if let Some(new_parent_id) = new_parent_id {
+				let new_parent_diff = DeviceDiff {
+					device_parent: Some(DeviceParentDiff::AddChild {
+						child_device_id: child_result.device_id.clone(),
+					}),
+					..Default::default()
+				};
+				log_change(tc, new_parent_id, user_id_value, &new_parent_diff)
+					.with_context("unable to log device change")?;
+			}
+
+			Ok(json!({
+				"deviceId": parent_info.device_id.clone(),
+				"parentDeviceId": parent_info.parent_device_id.clone(),
+			}))
+		})
+	})
+	.await
+}
+
+/// Loads the key info of a device that can be modified.
+pub(super) fn load_device_key_info<'a>(
+	conn: &mut SqliteConnection,
+	device: &str,
+) -> Result<DeviceKeyInfo<'a>, Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	let device_key_info_result = device_key_info
+		.filter(device_id.eq(device))
+		.get_result::<DeviceKeyInfo<'_>>(conn)
+		.optional()
+		.with_context("unable to query the database for device_key_info existence")?;
+	let Some(device_key_info_result) = device_key_info_result else {
+		return Err(UserError::BadRequest("Invalid device ID.").into());
+	};
+
+	// Ensure that deleted devices aren't modified
+	if device_key_info_result.deleted {
+		return Err(
+			UserError::BadRequest("The device has been deleted. It cannot be modified.").into(),
+		);
+	}
+
+	Ok(device_key_info_result)
+}
+
+/// Converts a component into a standalone device, with a newly-generated ID.
+///
+/// The component's type is copied into a column of the new device, and the
+/// component is deleted from the original device. Both devices log a change
+/// that refers to the other.
+#[post("/component/promote", data = "<promotion_info>")]
+pub async fn promote_component(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+	promotion_info: Json<ComponentPromotionInfo>,
+) -> Result<JsonValue, Error> {
+	let device_id_settings = config.device_ids.clone();
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{
+			column_definitions::dsl::*,
+			column_possible_values::dsl::*,
+			device_components::dsl::*,
+			device_data::dsl::*,
+			device_key_info::dsl::*,
+			locations::dsl::*,
+		};
+
+		let new_device_id = gen_new_device_id(c, &device_id_settings)
+			.with_context("unable to generate a new device ID")?;
+
+		c.transaction::<_, Error, _>(|tc| {
+			let original_result = load_device_key_info(tc, promotion_info.device_id.as_str())?;
+			let component_result = device_components
+				.filter(schema::device_components::dsl::device_key_info_id.eq(original_result.id))
+				.filter(component_id.eq(promotion_info.component_id.as_str()))
+				.filter(schema::device_components::dsl::deleted.eq(false))
+				.get_result::<DeviceComponent<'_>>(tc)
 				.optional()
-				.with_context("unable to query the database for device_key_info existence")?;
-			let Some(old_device_key_info) = old_device_key_info else {
-				return Err(UserError::BadRequest("Invalid device ID.").into());
+				.with_context("unable to query the database for component existence")?;
+			let Some(component_result) = component_result else {
+				return Err(UserError::BadRequest("Invalid component ID.").into());
 			};
 
-			// Ensure that deleted devices aren't modified
-			if old_device_key_info.deleted {
+			// Verify the new location
+			let new_location_id = promotion_info
+				.location_id
+				.unwrap_or(original_result.location_id);
+			if !select(exists(
+				locations.filter(schema::locations::dsl::id.eq(new_location_id)),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for location existence")?
+			{
+				return Err(UserError::BadRequest("Invalid location.").into());
+			}
+
+			// Ensure the component type is a valid value for the column
+			let column_result = column_definitions
+				.filter(schema::column_definitions::dsl::id.eq(promotion_info.column_definition_id))
+				.select((unique_values, exclusively_possible_values))
+				.get_result::<(bool, bool)>(tc)
+				.optional()
+				.with_context("unable to query the database for column definition existence")?;
+			let Some((column_unique_values, column_exclusively_possible_values)) = column_result
+			else {
+				return Err(UserError::BadRequest("Invalid column.").into());
+			};
+			if column_unique_values
+				&& data_value_exists(
+					tc,
+					promotion_info.column_definition_id,
+					None,
+					component_result.component_type.as_ref(),
+				)? {
 				return Err(UserError::BadRequest(
-					"The device has been deleted. It cannot be modified.",
+					"Another device already has the component's type as its value in that column.",
 				)
 				.into());
 			}
+			if column_exclusively_possible_values
+				&& !select(exists(
+					column_possible_values
+						.filter(
+							schema::column_possible_values::dsl::column_definition_id
+								.eq(promotion_info.column_definition_id),
+						)
+						.filter(value.eq(component_result.component_type.as_ref())),
+				))
+				.get_result::<bool>(tc)
+				.with_context("unable to query the database for possible value existence")?
+			{
+				return Err(UserError::BadRequest(
+					"The component's type isn't one of the possible values for that column.",
+				)
+				.into());
+			}
+
+			// Create the new device
+			if select(exists(
+				device_key_info.filter(device_id.eq(new_device_id.as_str())),
+			))
+			.get_result::<bool>(tc)
+			.with_context("unable to query the database for device existence")?
+			{
+				return Err(UserError::BadRequest("The device ID is already in use.").into());
+			}
+			let insertable_device_key_info = DeviceKeyInfoNew {
+				device_id:   Cow::from(new_device_id.as_str()),
+				location_id: new_location_id,
+			};
+			insert_into(device_key_info)
+				.values(&insertable_device_key_info)
+				.execute(tc)
+				.with_context("unable to insert into device_key_info")?;
+			let new_internal_id =
+				fetch_new_rowid_on(tc).with_context("unable to get the ID of the new device")?;
+			let insertable_device_data = vec![DeviceDataNew {
+				device_key_info_id:   new_internal_id,
+				column_definition_id: promotion_info.column_definition_id,
+				data_value:           component_result.component_type.clone(),
+			}];
+			insert_into(device_data)
+				.values(&insertable_device_data)
+				.execute(tc)
+				.with_context("unable to insert into device_data")?;
 
-			// Update the device entry
+			// Delete the component from the original device
 			update(
-				device_key_info.filter(schema::device_key_info::dsl::id.eq(old_device_key_info.id)),
+				device_components
+					.filter(schema::device_components::dsl::id.eq(component_result.id)),
 			)
-			.set(location_id.eq(checkout_info.location_id))
+			.set(schema::device_components::dsl::deleted.eq(true))
 			.execute(tc)
-			.with_context("unable to update device_key_info")?;
-
-			// Log the change in the database
-			if old_device_key_info.location_id != checkout_info.location_id {
-				let diff = DeviceDiff {
-					device_key_info: Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
-						location_id: Some(checkout_info.location_id),
-					})),
-					..Default::default()
-				};
+			.with_context("unable to update device_components")?;
+
+			// Log the change on both devices
+			let mut new_device_diff = DeviceDiff::from(&(
+				insertable_device_key_info,
+				insertable_device_data,
+				Vec::new(),
+				Vec::new(),
+			));
+			new_device_diff.component_promotion = Some(ComponentPromotionDiff::PromotedFrom {
+				device_id:    original_result.device_id.clone(),
+				component_id: component_result.component_id.clone(),
+			});
+			log_change(tc, new_internal_id, user_id_value, &new_device_diff)
+				.with_context("unable to log device change")?;
 
-				log_change(tc, old_device_key_info.id, user_id_value, &diff)
-					.with_context("unable to log device change")?;
-			}
+			let original_device_diff = DeviceDiff {
+				device_components: DeviceComponentsDiff::calculate_diff(
+					&vec![DeviceComponentWithData {
+						component:   component_result.clone(),
+						column_data: Vec::new(),
+					}],
+					&vec![DeviceComponentUpsert::Delete(
+						component_result.component_id.clone(),
+					)],
+				),
+				component_promotion: Some(ComponentPromotionDiff::PromotedTo {
+					component_id: component_result.component_id.clone(),
+					device_id:    Cow::from(new_device_id.as_str()),
+				}),
+				..Default::default()
+			};
+			log_change(tc, original_result.id, user_id_value, &original_device_diff)
+				.with_context("unable to log device change")?;
 
 			Ok(())
-		})
-		.with_context("unable to update the device entry")?;
+		})?;
 
 		// Return the results
-		Ok(json!({
-			"deviceId": checkout_info.device_id.clone(),
-			"locationId": checkout_info.location_id,
-			"locationName": location_name
-		}))
+		Ok(json!({ "deviceId": new_device_id }))
 	})
 	.await
 }
@@ -522,8 +1319,10 @@ pub async fn create_device<'a>(
 ) -> Result<JsonValue, Error> {
 	upsert_device(
 		config.max_attachment_size,
+		config.device_ids.clone(),
 		conn,
 		None,
+		None,
 		device_info,
 		user.0.id,
 	)
@@ -531,18 +1330,32 @@ pub async fn create_device<'a>(
 }
 
 /// Updates a device's data.
+///
+/// The version of the device that the changes were made to has to be provided,
+/// either in the `If-Match` header or the request body. If the device has been
+/// changed since, the update is rejected with the changes made in the meantime.
 #[post("/update/<device>", data = "<device_info>")]
 pub async fn update_device(
 	config: &State<AppConfig>,
 	user: &AuthedUser,
 	conn: DbConn,
+	if_match: IfMatch,
 	device: String,
 	device_info: Json<UpdatedDeviceInfo>,
 ) -> Result<JsonValue, Error> {
+	let expected_version = if_match
+		.0
+		.or(device_info.version)
+		.ok_or(UserError::BadRequest(
+			"The version of the device being updated is required.",
+		))?;
+
 	upsert_device(
 		config.max_attachment_size,
+		config.device_ids.clone(),
 		conn,
 		Some(device),
+		Some(expected_version),
 		device_info,
 		user.0.id,
 	)
@@ -552,10 +1365,15 @@ pub async fn update_device(
 /// Inserts or updates device information, depending on if it's already present
 /// in the database. This is the implementation for [`create_device`] and
 /// [`update_device`].
+///
+/// If `expected_version` is provided, the update only goes ahead if the device
+/// is still at that version.
 async fn upsert_device(
 	max_attachment_size: ByteUnit,
+	device_id_settings: DeviceIdSettings,
 	conn: DbConn,
 	device: Option<String>,
+	expected_version: Option<i32>,
 	device_info: Json<UpdatedDeviceInfo>,
 	user_id_value: i32,
 ) -> Result<JsonValue, Error> {
@@ -563,10 +1381,16 @@ async fn upsert_device(
 		// Uses
 		use schema::{
 			device_attachments::dsl::*,
+			device_component_data::dsl::{
+				component_column_definition_id,
+				device_component_data,
+				device_component_id,
+			},
 			device_components::dsl::*,
 			device_data::dsl::*,
 			device_key_info::dsl::*,
 			locations::dsl::*,
+			reserved_device_ids::dsl::reserved_device_ids,
 		};
 
 		// Verify the new location
@@ -579,13 +1403,30 @@ async fn upsert_device(
 			return Err(UserError::NotFound("Invalid location.").into());
 		}
 
-		// Generate a new device ID if one wasn't provided
+		// Use the requested device ID for new devices, or generate one if it wasn't
+		// provided
 		let (is_new, prepared_device_id) = if let Some(provided_device_id) = device {
+			if device_info
+				.device_id
+				.as_ref()
+				.is_some_and(|requested_device_id| *requested_device_id != provided_device_id)
+			{
+				return Err(UserError::BadRequest("Device IDs can't be changed.").into());
+			}
 			(false, provided_device_id)
+		} else if let Some(requested_device_id) = device_info.device_id.clone() {
+			if !device_id_matches_format(&device_id_settings, requested_device_id.as_str()) {
+				return Err(UserError::BadRequest(
+					"The device ID doesn't match the configured format.",
+				)
+				.into());
+			}
+			(true, requested_device_id)
 		} else {
 			(
 				true,
-				gen_new_device_id(c).with_context("unable to generate a new device ID")?,
+				gen_new_device_id(c, &device_id_settings)
+					.with_context("unable to generate a new device ID")?,
 			)
 		};
 
@@ -595,8 +1436,24 @@ async fn upsert_device(
 		// The same goes for not-null values
 
 		// Begin the transaction
-		c.transaction::<_, Error, _>(|tc| {
+		let upsert_result = c.transaction::<_, Error, _>(|tc| {
 			let old_values = if is_new {
+				// Ensure that requested device IDs aren't already in use, and claim them if
+				// they were reserved
+				if select(exists(
+					device_key_info.filter(device_id.eq(prepared_device_id.as_str())),
+				))
+				.get_result::<bool>(tc)
+				.with_context("unable to query the database for device existence")?
+				{
+					return Err(UserError::BadRequest("The device ID is already in use.").into());
+				}
+				delete(reserved_device_ids.filter(
+					schema::reserved_device_ids::dsl::device_id.eq(prepared_device_id.as_str()),
+				))
+				.execute(tc)
+				.with_context("unable to claim the reserved device ID")?;
+
 				None
 			} else {
 				// Pull the existing values
@@ -606,7 +1463,7 @@ async fn upsert_device(
 					device_component_results,
 					device_attachment_results,
 					_,
-				) = load_device_info(tc, prepared_device_id.as_str())?;
+				) = load_device_info(tc, prepared_device_id.as_str(), true)?;
 
 				// Ensure that deleted devices aren't modified
 				if device_key_info_result.deleted {
@@ -615,6 +1472,27 @@ async fn upsert_device(
 					)));
 				}
 
+				// Ensure that nobody else has changed the device since the user loaded it, so
+				// that their changes aren't silently reverted
+				if let Some(expected_version_value) = expected_version {
+					let current_version = load_device_version(tc, device_key_info_result.id)?;
+					if current_version != expected_version_value {
+						let intervening_changes = load_device_changes_since(
+							tc,
+							device_key_info_result.id,
+							expected_version_value,
+						)?;
+						return Err(UserError::Conflict(
+							"The device has been changed since it was loaded.",
+							json!({
+								"version": current_version,
+								"changes": intervening_changes,
+							}),
+						)
+						.into());
+					}
+				}
+
 				Some((
 					device_key_info_result,
 					device_data_results,
@@ -692,17 +1570,30 @@ async fn upsert_device(
 				upsertable_device_components.push(if component.deleted {
 					DeviceComponentUpsert::Delete(Cow::from(prepared_component_id))
 				} else {
-					DeviceComponentUpsert::NewExisting(DeviceComponentNew {
-						device_key_info_id: internal_id,
-						component_id:       Cow::from(prepared_component_id),
-						component_type:     Cow::from(component.component_type.as_str()),
-					})
+					DeviceComponentUpsert::NewExisting(
+						DeviceComponentNew {
+							device_key_info_id: internal_id,
+							component_id:       Cow::from(prepared_component_id),
+							component_type:     Cow::from(component.component_type.as_str()),
+						},
+						component
+							.column_data
+							.iter()
+							.map(|column| DeviceComponentDataValue {
+								component_column_definition_id: column
+									.component_column_definition_id,
+								data_value:                     Cow::from(
+									column.data_value.as_str(),
+								),
+							})
+							.collect(),
+					)
 				});
 			}
 
 			for upsertable_record in &upsertable_device_components {
 				match upsertable_record {
-					DeviceComponentUpsert::NewExisting(new_record) => {
+					DeviceComponentUpsert::NewExisting(new_record, new_data_values) => {
 						// Upsert the component
 						insert_into(device_components)
 							.values(new_record)
@@ -714,6 +1605,38 @@ async fn upsert_device(
 							.set(component_type.eq(excluded(component_type)))
 							.execute(tc)
 							.with_context("unable to upsert into device_components")?;
+
+						// Upsert the component data
+						let internal_component_id = device_components
+							.filter(
+								schema::device_components::dsl::device_key_info_id.eq(internal_id),
+							)
+							.filter(component_id.eq(new_record.component_id.as_ref()))
+							.select(schema::device_components::dsl::id)
+							.get_result::<i32>(tc)
+							.with_context(
+								"unable to get the internal ID associated with the component ID",
+							)?;
+						for new_data_value in new_data_values {
+							insert_into(device_component_data)
+								.values(DeviceComponentDataNew {
+									device_component_id:            internal_component_id,
+									component_column_definition_id: new_data_value
+										.component_column_definition_id,
+									data_value:                     new_data_value
+										.data_value
+										.clone(),
+								})
+								.on_conflict((device_component_id, component_column_definition_id))
+								.do_update()
+								.set(
+									schema::device_component_data::dsl::data_value.eq(excluded(
+										schema::device_component_data::dsl::data_value,
+									)),
+								)
+								.execute(tc)
+								.with_context("unable to upsert into device_component_data")?;
+						}
 					}
 					DeviceComponentUpsert::Delete(provided_component_id) => {
 						// Delete the component
@@ -831,7 +1754,10 @@ async fn upsert_device(
 								)
 								.filter(attachment_id.eq(provided_attachment_id.as_ref())),
 						)
-						.set(schema::device_attachments::dsl::deleted.eq(true))
+						.set((
+							schema::device_attachments::dsl::deleted.eq(true),
+							deleted_at.eq(Utc::now().naive_utc()),
+						))
 						.execute(tc)
 						.with_context("unable to update device_attachments")?;
 					}
@@ -863,12 +1789,12 @@ async fn upsert_device(
 					.with_context("unable to log device change")?;
 			}
 
-			Ok(())
-		})
-		.with_context("unable to update the device entry")?;
+			load_device_version(tc, internal_id)
+		});
+		let new_version = upsert_result.with_context("unable to update the device entry")?;
 
 		// Return the results
-		Ok(json!({ "deviceId": prepared_device_id }))
+		Ok(json!({ "deviceId": prepared_device_id, "version": new_version }))
 	})
 	.await
 }
@@ -893,6 +1819,60 @@ pub async fn restore_device(
 	set_device_deletion_status(conn, device, false, user.0.id).await
 }
 
+/// Lists the deleted devices, with when they were deleted and by whom, most
+/// recently deleted first.
+#[get("/deleted")]
+pub async fn get_deleted_devices(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let deleted_device_results = sql_query(include_str!(concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/src/sql/deleted_devices.sql"
+		)))
+		.load::<DeletedDeviceEntry<'_>>(c)
+		.with_context("unable to load the deleted devices")?;
+
+		Ok(json!({ "devices": deleted_device_results }))
+	})
+	.await
+}
+
+/// Fetches a deleted device by ID, so that it can be looked over before it's
+/// restored.
+///
+/// Unlike [`get_device`], there's no `version` in the results, since deleted
+/// devices can't be modified.
+#[get("/deleted/<device>")]
+pub async fn get_deleted_device(
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let (
+			device_key_info_results,
+			device_data_results,
+			device_component_results,
+			device_attachment_results,
+			device_change_results,
+		) = load_device_info(c, device.as_str(), true)?;
+		if !device_key_info_results.deleted {
+			return Err(UserError::BadRequest("The device hasn't been deleted.").into());
+		}
+		let device_lifecycle_result = load_device_lifecycle(c, device_key_info_results.id)?;
+
+		// Return the results
+		// This matches the format of `get_device`.
+		Ok(json!({
+			"deviceResults": (device_key_info_results, device_data_results),
+			"deviceComponents": device_component_results,
+			"deviceAttachments": device_attachment_results,
+			"deviceChanges": device_change_results,
+			"deviceLifecycle": device_lifecycle_result,
+		}))
+	})
+	.await
+}
+
 /// Deletes or restores a device.
 async fn set_device_deletion_status(
 	conn: DbConn,
@@ -908,7 +1888,7 @@ async fn set_device_deletion_status(
 		c.transaction::<_, Error, _>(|tc| {
 			// Pull the existing values - this is horribly inefficient, but this operation
 			// shouldn't happen often
-			let (device_key_info_result, ..) = load_device_info(tc, device.as_str())?;
+			let (device_key_info_result, ..) = load_device_info(tc, device.as_str(), true)?;
 
 			// Ensure that there's something to do
 			if device_key_info_result.deleted == new_deletion_status {
@@ -936,6 +1916,18 @@ async fn set_device_deletion_status(
 			log_change(tc, device_key_info_result.id, user_id_value, &change_diff)
 				.with_context("unable to log device change")?;
 
+			// Detach anything attached to a deleted device, so that it isn't left hanging
+			// beneath something that can't be seen
+			if new_deletion_status {
+				detach_child_devices(
+					tc,
+					device_key_info_result.id,
+					device.as_str(),
+					user_id_value,
+					None,
+				)?;
+			}
+
 			Ok(())
 		})
 		.with_context("unable to update the device entry")?;
@@ -946,6 +1938,371 @@ async fn set_device_deletion_status(
 	.await
 }
 
+/// Applies the same changes to many devices at once.
+///
+/// Everything is validated before any changes are made, and either all of the
+/// devices are changed or none of them are. Each device gets its own change
+/// record, tagged with a batch ID shared by the whole bulk edit.
+#[post("/bulk", data = "<bulk_info>")]
+pub async fn bulk_edit_devices(
+	user: &AuthedUser,
+	conn: DbConn,
+	bulk_info: Json<BulkEditInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{
+			device_change_batches::dsl::device_change_batches,
+			device_data::dsl::*,
+			device_key_info::dsl::*,
+			locations::dsl::locations,
+		};
+
+		if bulk_info.column_data.is_empty()
+			&& bulk_info.location_id.is_none()
+			&& bulk_info.deletion.is_none()
+		{
+			return Err(UserError::BadRequest("No changes were provided.").into());
+		}
+		if bulk_info.deletion.is_some()
+			&& (!bulk_info.column_data.is_empty() || bulk_info.location_id.is_some())
+		{
+			return Err(UserError::BadRequest(
+				"Deleting or restoring devices can't be combined with other changes.",
+			)
+			.into());
+		}
+
+		c.transaction::<_, Error, _>(|tc| {
+			let mut selected_device_ids = select_device_ids(tc, &bulk_info.devices)?;
+			let mut seen_device_ids = HashSet::with_capacity(selected_device_ids.len());
+			selected_device_ids
+				.retain(|selected_device_id| seen_device_ids.insert(selected_device_id.clone()));
+			if selected_device_ids.is_empty() {
+				return Err(UserError::BadRequest("No devices were provided.").into());
+			}
+
+			// Validate everything up front
+			if let Some(new_location_id) = bulk_info.location_id {
+				if !select(exists(
+					locations.filter(schema::locations::dsl::id.eq(new_location_id)),
+				))
+				.get_result::<bool>(tc)
+				.with_context("unable to query the database for location existence")?
+				{
+					return Err(UserError::BadRequest("Invalid location.").into());
+				}
+			}
+			verify_bulk_column_data(tc, &bulk_info.column_data, &selected_device_ids)?;
+
+			let mut device_results = Vec::with_capacity(selected_device_ids.len());
+			for selected_device_id in &selected_device_ids {
+				let device_result = device_key_info
+					.filter(device_id.eq(selected_device_id.as_str()))
+					.select((
+						schema::device_key_info::dsl::id,
+						device_id,
+						deleted,
+						location_id,
+					))
+					.get_result::<(i32, String, bool, i32)>(tc)
+					.optional()
+					.with_context("unable to load device info")?
+					.ok_or(UserError::NotFound("Invalid device ID."))?;
+				if device_result.2 && bulk_info.deletion != Some(BulkDeletionChange::Restore) {
+					return Err(UserError::BadRequest(
+						"The selection includes deleted devices, which can't be modified.",
+					)
+					.into());
+				}
+				device_results.push(device_result);
+			}
+
+			// Work out what would change for each device
+			let submitted_column_ids = bulk_info
+				.column_data
+				.iter()
+				.map(|column| column.column_definition_id)
+				.collect::<Vec<_>>();
+			let mut planned_changes = Vec::with_capacity(device_results.len());
+			let mut unchanged_device_ids = Vec::new();
+			for (internal_id, current_device_id, currently_deleted, current_location_id) in
+				device_results
+			{
+				let device_key_info_diff = match bulk_info.deletion {
+					Some(BulkDeletionChange::Delete) => {
+						(!currently_deleted).then_some(DeviceKeyInfoDiff::Delete)
+					}
+					Some(BulkDeletionChange::Restore) => {
+						currently_deleted.then_some(DeviceKeyInfoDiff::Restore)
+					}
+					None => bulk_info
+						.location_id
+						.filter(|new_location_id| *new_location_id != current_location_id)
+						.map(|new_location_id| {
+							DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+								location_id: Some(new_location_id),
+							})
+						}),
+				};
+
+				let insertable_device_data = bulk_info
+					.column_data
+					.iter()
+					.map(|column| DeviceDataNew {
+						device_key_info_id:   internal_id,
+						column_definition_id: column.column_definition_id,
+						data_value:           Cow::from(column.data_value.as_str()),
+					})
+					.collect::<Vec<_>>();
+				let device_data_diff = if insertable_device_data.is_empty() {
+					None
+				} else {
+					let device_data_results = device_data
+						.filter(device_key_info_id.eq(internal_id))
+						.filter(column_definition_id.eq_any(&submitted_column_ids))
+						.select(DEVICE_DATA)
+						.load::<DeviceData<'_>>(tc)
+						.with_context("unable to load the device data")?;
+					DeviceDataDiff::calculate_diff(&device_data_results, &insertable_device_data)
+				};
+
+				let change_diff = DeviceDiff {
+					device_key_info: device_key_info_diff,
+					device_data: device_data_diff,
+					..Default::default()
+				};
+				if change_diff.is_empty() {
+					unchanged_device_ids.push(current_device_id);
+				} else {
+					planned_changes.push((
+						internal_id,
+						current_device_id,
+						insertable_device_data,
+						change_diff,
+					));
+				}
+			}
+
+			// Apply the changes
+			let mut new_batch_id = None;
+			if !bulk_info.preview && !planned_changes.is_empty() {
+				insert_into(device_change_batches)
+					.values(DeviceChangeBatchNew {
+						user_id:    user_id_value,
+						created_at: Utc::now().naive_utc(),
+					})
+					.execute(tc)
+					.with_context("unable to insert into device_change_batches")?;
+				let batch =
+					fetch_new_rowid_on(tc).with_context("unable to get the ID of the new batch")?;
+				new_batch_id = Some(batch);
+
+				for (internal_id, planned_device_id, insertable_device_data, change_diff) in
+					&planned_changes
+				{
+					match change_diff.device_key_info {
+						Some(DeviceKeyInfoDiff::Delete | DeviceKeyInfoDiff::Restore) => {
+							let new_deletion_status =
+								bulk_info.deletion == Some(BulkDeletionChange::Delete);
+							update(
+								device_key_info
+									.filter(schema::device_key_info::dsl::id.eq(internal_id)),
+							)
+							.set(deleted.eq(new_deletion_status))
+							.execute(tc)
+							.with_context("unable to update device_key_info")?;
+						}
+						Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+							location_id: Some(new_location_id),
+						})) => {
+							update(
+								device_key_info
+									.filter(schema::device_key_info::dsl::id.eq(internal_id)),
+							)
+							.set(location_id.eq(new_location_id))
+							.execute(tc)
+							.with_context("unable to update device_key_info")?;
+						}
+						_ => {}
+					}
+
+					if change_diff.device_data.is_some() {
+						for insertable_record in insertable_device_data {
+							insert_into(device_data)
+								.values(insertable_record)
+								.on_conflict((device_key_info_id, column_definition_id))
+								.do_update()
+								.set(data_value.eq(excluded(data_value)))
+								.execute(tc)
+								.with_context("unable to upsert into device_data")?;
+						}
+					}
+
+					log_batch_change(tc, *internal_id, user_id_value, change_diff, batch)
+						.with_context("unable to log device change")?;
+
+					if matches!(change_diff.device_key_info, Some(DeviceKeyInfoDiff::Delete)) {
+						detach_child_devices(
+							tc,
+							*internal_id,
+							planned_device_id.as_str(),
+							user_id_value,
+							Some(batch),
+						)?;
+					}
+				}
+			}
+
+			Ok(json!({
+				"preview": bulk_info.preview,
+				"batchId": new_batch_id,
+				"changes": planned_changes
+					.iter()
+					.map(|(_, planned_device_id, _, change_diff)| {
+						json!({ "deviceId": planned_device_id, "change": change_diff })
+					})
+					.collect::<Vec<_>>(),
+				"unchangedDeviceIds": unchanged_device_ids,
+			}))
+		})
+	})
+	.await
+}
+
+/// Ensures that column values being applied to many devices at once are valid
+/// for their columns.
+///
+/// Single-device edits leave this to the frontend, but there's no form to
+/// catch mistakes here.
+fn verify_bulk_column_data(
+	conn: &mut SqliteConnection,
+	submitted_column_data: &[SubmittedColumnData],
+	selected_device_ids: &[String],
+) -> Result<(), Error> {
+	// Uses
+	use schema::{
+		column_definitions::dsl::{
+			column_definitions,
+			exclusively_possible_values,
+			not_null,
+			unique_values,
+		},
+		column_possible_values::dsl::{column_possible_values, value},
+	};
+
+	let mut seen_column_ids = HashSet::with_capacity(submitted_column_data.len());
+	for column in submitted_column_data {
+		if !seen_column_ids.insert(column.column_definition_id) {
+			return Err(UserError::BadRequest("Each column can only be changed once.").into());
+		}
+
+		let (column_not_null, column_unique_values, column_exclusively_possible_values) =
+			column_definitions
+				.filter(schema::column_definitions::dsl::id.eq(column.column_definition_id))
+				.select((not_null, unique_values, exclusively_possible_values))
+				.get_result::<(bool, bool, bool)>(conn)
+				.optional()
+				.with_context("unable to load the column definition")?
+				.ok_or(UserError::BadRequest("Invalid column definition."))?;
+
+		if column.data_value.is_empty() {
+			if column_not_null {
+				return Err(UserError::BadRequest("Required columns can't be left empty.").into());
+			}
+			continue;
+		}
+		if column_unique_values {
+			if selected_device_ids.len() > 1 {
+				return Err(UserError::BadRequest(
+					"Columns with unique values can't be set to the same value on multiple \
+					 devices.",
+				)
+				.into());
+			}
+			if data_value_exists(
+				conn,
+				column.column_definition_id,
+				selected_device_ids.first().map(String::as_str),
+				column.data_value.as_str(),
+			)? {
+				return Err(UserError::BadRequest(
+					"The value is already in use by another device.",
+				)
+				.into());
+			}
+		}
+		if column_exclusively_possible_values
+			&& !select(exists(
+				column_possible_values
+					.filter(
+						schema::column_possible_values::dsl::column_definition_id
+							.eq(column.column_definition_id),
+					)
+					.filter(value.eq(column.data_value.as_str())),
+			))
+			.get_result::<bool>(conn)
+			.with_context("unable to query the database for possible value existence")?
+		{
+			return Err(UserError::BadRequest(
+				"The value isn't one of the column's possible values.",
+			)
+			.into());
+		}
+	}
+
+	Ok(())
+}
+
+/// Detaches everything attached directly beneath a device, logging the change
+/// on both sides.
+fn detach_child_devices(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+	device: &str,
+	user_id_value: i32,
+	batch: Option<i32>,
+) -> Result<(), Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	let log = |conn: &mut SqliteConnection, logged_id: i32, diff: &DeviceDiff<'_>| match batch {
+		Some(batch) => log_batch_change(conn, logged_id, user_id_value, diff, batch),
+		None => log_change(conn, logged_id, user_id_value, diff),
+	};
+
+	let child_results = device_key_info
+		.filter(parent_device_id.eq(internal_id))
+		.select((id, device_id))
+		.load::<(i32, String)>(conn)
+		.with_context("unable to load the attached devices")?;
+	for (child_id, child_device_id) in child_results {
+		update(device_key_info.filter(id.eq(child_id)))
+			.set(parent_device_id.eq(None::<i32>))
+			.execute(conn)
+			.with_context("unable to update device_key_info")?;
+
+		let child_diff = DeviceDiff {
+			device_parent: Some(DeviceParentDiff::SetParent {
+				old_parent_device_id: Some(Cow::from(device)),
+				new_parent_device_id: None,
+			}),
+			..Default::default()
+		};
+		log(conn, child_id, &child_diff).with_context("unable to log device change")?;
+		let parent_diff = DeviceDiff {
+			device_parent: Some(DeviceParentDiff::RemoveChild {
+				child_device_id: Cow::from(child_device_id),
+			}),
+			..Default::default()
+		};
+		log(conn, internal_id, &parent_diff).with_context("unable to log device change")?;
+	}
+
+	Ok(())
+}
+
 #[get("/attachment/<device>/<attachment>")]
 pub async fn get_attachment(
 	_user: &AuthedUser,
@@ -1015,3 +2372,190 @@ pub async fn get_data_value_exists(
 	})
 	.await
 }
+
+#[post("/componentValueExists/<column_id>", data = "<query>")]
+pub async fn get_component_data_value_exists(
+	_user: &AuthedUser,
+	conn: DbConn,
+	column_id: i32,
+	query: Json<ComponentValueExistsQuery>,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let result = component_data_value_exists(
+			c,
+			column_id,
+			query
+				.device_id
+				.as_deref()
+				.zip(query.component_id.as_deref()),
+			query.value.as_str(),
+		)?;
+
+		Ok(json!({ "exists": result }))
+	})
+	.await
+}
+
+/// Lists all reserved device IDs that haven't been claimed yet.
+#[get("/reserved")]
+pub async fn get_reserved_device_ids(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		// Uses
+		use schema::{reserved_device_ids::dsl::*, user_info::dsl::user_info};
+
+		let reserved_results = reserved_device_ids
+			.inner_join(user_info)
+			.order_by(reserved_at.desc())
+			.then_order_by(device_id)
+			.select(RESERVED_DEVICE_ID)
+			.load::<ReservedDeviceIdDisplay<'_>>(c)
+			.with_context("unable to load the reserved device IDs")?;
+
+		Ok(json!({ "reservedDeviceIds": reserved_results }))
+	})
+	.await
+}
+
+/// Reserves a block of new device IDs, so that labels can be printed for them
+/// before the devices are added.
+///
+/// The IDs are claimed by providing them when creating devices.
+#[post("/reserve", data = "<reservation_info>")]
+pub async fn reserve_device_ids(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+	reservation_info: Json<ReservationInfo>,
+) -> Result<JsonValue, Error> {
+	const MAX_RESERVATION_COUNT: u32 = 1000;
+
+	if reservation_info.count < 1 || reservation_info.count > MAX_RESERVATION_COUNT {
+		return Err(UserError::BadRequest(
+			"Between 1 and 1000 device IDs can be reserved at a time.",
+		)
+		.into());
+	}
+
+	let device_id_settings = config.device_ids.clone();
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::reserved_device_ids::dsl::*;
+
+		c.transaction::<_, Error, _>(|tc| {
+			let now = Utc::now().naive_utc();
+			let mut new_device_ids = Vec::with_capacity(reservation_info.count as usize);
+			for _ in 0..reservation_info.count {
+				// Each ID is inserted before the next is generated, so that they can't collide
+				let new_device_id = gen_new_device_id(tc, &device_id_settings)
+					.with_context("unable to generate a new device ID")?;
+				insert_into(reserved_device_ids)
+					.values(ReservedDeviceIdNew {
+						device_id:   Cow::from(new_device_id.as_str()),
+						reserved_by: user_id_value,
+						reserved_at: now,
+					})
+					.execute(tc)
+					.with_context("unable to insert into reserved_device_ids")?;
+				new_device_ids.push(new_device_id);
+			}
+
+			Ok(json!({ "deviceIds": new_device_ids }))
+		})
+	})
+	.await
+}
+
+/// Releases reserved device IDs that are no longer needed.
+///
+/// IDs that aren't reserved are ignored.
+#[post("/reserved/release", data = "<release_info>")]
+pub async fn release_device_ids(
+	_user: &AuthedUser,
+	conn: DbConn,
+	release_info: Json<ReservationReleaseInfo>,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		// Uses
+		use schema::reserved_device_ids::dsl::*;
+
+		let released_count =
+			delete(reserved_device_ids.filter(device_id.eq_any(&release_info.device_ids)))
+				.execute(c)
+				.with_context("unable to delete from reserved_device_ids")?;
+
+		Ok(json!({ "releasedCount": released_count }))
+	})
+	.await
+}
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use chrono::NaiveDate;
+	use diesel_migrations::MigrationHarness;
+
+	use super::*;
+	use crate::db::{models::DeviceChangeNew, util::fetch_new_rowid_on, MIGRATIONS};
+
+	/// Sets up an in-memory database without any changes.
+	fn empty_database() -> SqliteConnection {
+		// Foreign key constraints are left off, so that the changes don't need
+		// devices or users to exist
+		let mut conn = SqliteConnection::establish(":memory:").unwrap();
+		sql_query("PRAGMA foreign_keys = OFF;")
+			.execute(&mut conn)
+			.unwrap();
+		conn.run_pending_migrations(MIGRATIONS).unwrap();
+
+		conn
+	}
+
+	/// Logs a change to device 1, returning its ID.
+	fn add_change(conn: &mut SqliteConnection, change_value: &str) -> i32 {
+		// Uses
+		use schema::device_changes::dsl::*;
+
+		insert_into(device_changes)
+			.values(DeviceChangeNew {
+				device_key_info_id: 1,
+				timestamp:          NaiveDate::from_ymd_opt(2026, 10, 19)
+					.unwrap()
+					.and_hms_opt(12, 0, 0)
+					.unwrap(),
+				done_automatically: false,
+				user_id:            Some(1),
+				change:             Cow::from(change_value.to_owned()),
+				batch_id:           None,
+			})
+			.execute(conn)
+			.unwrap();
+		fetch_new_rowid_on(conn).unwrap()
+	}
+
+	#[test]
+	fn edits_change_the_version() {
+		let mut conn = empty_database();
+		assert_eq!(load_device_version(&mut conn, 1).unwrap(), 0);
+
+		let edit_id = add_change(&mut conn, r#"{"deviceKeyInfo":"delete"}"#);
+		assert_eq!(load_device_version(&mut conn, 1).unwrap(), edit_id);
+
+		let data_edit_id = add_change(&mut conn, r#"{"deviceData":[]}"#);
+		assert_eq!(load_device_version(&mut conn, 1).unwrap(), data_edit_id);
+		assert_eq!(load_device_version(&mut conn, 2).unwrap(), 0);
+	}
+
+	#[test]
+	fn bookings_dont_change_the_version() {
+		let mut conn = empty_database();
+		let edit_id = add_change(&mut conn, r#"{"deviceAttachments":[]}"#);
+
+		add_change(&mut conn, r#"{"deviceBooking":{"create":{}}}"#);
+		add_change(&mut conn, r#"{"deviceLoan":{"checkIn":{}}}"#);
+		assert_eq!(load_device_version(&mut conn, 1).unwrap(), edit_id);
+		assert!(load_device_changes_since(&mut conn, 1, edit_id)
+			.unwrap()
+			.is_empty());
+	}
+}
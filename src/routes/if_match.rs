@@ -0,0 +1,34 @@
+// Uses
+use rocket::{
+	http::Status,
+	request::{FromRequest, Outcome},
+	Request,
+};
+
+/// The request guard for the `If-Match` header, holding the version of a
+/// resource that the client expects to be changing.
+///
+/// The header is optional, but if it's present it must be a single version
+/// number, optionally quoted like an entity tag (`"42"`).
+pub struct IfMatch(pub Option<i32>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for IfMatch {
+	type Error = ();
+
+	async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+		let Some(header_value) = request.headers().get_one("If-Match") else {
+			return Outcome::Success(Self(None));
+		};
+
+		let trimmed = header_value.trim();
+		let unquoted = trimmed
+			.strip_prefix('"')
+			.and_then(|value| value.strip_suffix('"'))
+			.unwrap_or(trimmed);
+		match unquoted.parse::<i32>() {
+			Ok(version) => Outcome::Success(Self(Some(version))),
+			Err(_) => Outcome::Failure((Status::BadRequest, ())),
+		}
+	}
+}
@@ -0,0 +1,196 @@
+// Uses
+use diesel::{dsl::exists, select, ExpressionMethods, QueryDsl, RunQueryDsl};
+use rocket::{get, post, routes, serde::json::Json, Route, State};
+
+use super::Routable;
+use crate::{
+	auth::AuthedUser,
+	config::AppConfig,
+	db::{schema, DbConn},
+	error::{Context, Error, UserError},
+	labels::{device_link, load_label_data, render_labels, CodeMatrix, ImageFormat, LabelFormat},
+	routes::{
+		devices::{select_device_ids, DeviceSelection},
+		file_from_memory::FileFromMemory,
+	},
+};
+
+/// The route for this section.
+pub(super) struct LabelsApi;
+impl Routable for LabelsApi {
+	const PATH: &'static str = "/labels";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> =
+		&|| routes![get_label, get_labels_bulk, get_barcode, get_qr_code];
+}
+
+// Constants
+const BARCODE_IMAGE_HEIGHT: usize = 40;
+const PNG_MODULE_SIZE: usize = 4;
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkLabelQuery {
+	devices:    DeviceSelection,
+	sheet:      Option<String>,
+	#[serde(default)]
+	skip_slots: usize,
+}
+
+/// Renders the label for a device, in the requested format.
+#[get("/<device>/<format>?<sheet>")]
+pub async fn get_label(
+	config: &State<AppConfig>,
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+	format: LabelFormat,
+	sheet: Option<String>,
+) -> Result<FileFromMemory, Error> {
+	let sheet_settings = config
+		.labels
+		.find_sheet(sheet.as_deref())
+		.ok_or(UserError::BadRequest("Invalid sheet format."))?;
+
+	let public_url = config.public_url.clone();
+	let label_data = conn
+		.run(move |c| load_label_data(c, public_url.as_deref(), device.as_str()))
+		.await?;
+
+	let file_name = format!("{}.{}", label_data.device_id, format.extension());
+	let contents = render_labels(&config.labels, sheet_settings, format, &[label_data], 0)?;
+
+	Ok(FileFromMemory::new(file_name.as_str(), contents))
+}
+
+/// Renders the labels for many devices at once, in the requested format.
+///
+/// The devices can be provided either as a list of device IDs, or as a search
+/// query, in which case all matching devices are included.
+#[post("/bulk/<format>", data = "<query>")]
+pub async fn get_labels_bulk(
+	config: &State<AppConfig>,
+	_user: &AuthedUser,
+	conn: DbConn,
+	format: LabelFormat,
+	query: Json<BulkLabelQuery>,
+) -> Result<FileFromMemory, Error> {
+	let query = query.into_inner();
+	let sheet_settings = config
+		.labels
+		.find_sheet(query.sheet.as_deref())
+		.ok_or(UserError::BadRequest("Invalid sheet format."))?;
+	if query.skip_slots >= sheet_settings.labels_per_page() {
+		return Err(UserError::BadRequest(
+			"Only slots on the first page of the sheet can be skipped.",
+		)
+		.into());
+	}
+
+	let public_url = config.public_url.clone();
+	let label_data = conn
+		.run(move |c| {
+			let device_ids = select_device_ids(c, &query.devices)?;
+
+			device_ids
+				.iter()
+				.map(|device| load_label_data(c, public_url.as_deref(), device.as_str()))
+				.collect::<Result<Vec<_>, _>>()
+		})
+		.await?;
+	if label_data.is_empty() {
+		return Err(UserError::BadRequest("No devices were provided.").into());
+	}
+
+	let file_name = format!("labels.{}", format.extension());
+	let contents = render_labels(
+		&config.labels,
+		sheet_settings,
+		format,
+		label_data.as_slice(),
+		query.skip_slots,
+	)?;
+
+	Ok(FileFromMemory::new(file_name.as_str(), contents))
+}
+
+/// Renders a device's barcode as a standalone image.
+#[get("/barcode/<device>/<format>")]
+pub async fn get_barcode(
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+	format: ImageFormat,
+) -> Result<FileFromMemory, Error> {
+	let device = ensure_device_exists(conn, device).await?;
+
+	let code = CodeMatrix::barcode(device.as_str(), BARCODE_IMAGE_HEIGHT).ok_or(
+		UserError::BadRequest("The device ID can't be encoded as a barcode."),
+	)?;
+
+	Ok(render_code_image(
+		&code,
+		format!("{device}-barcode").as_str(),
+		format,
+	))
+}
+
+/// Renders a device's QR code as a standalone image.
+///
+/// The QR code contains the same link as the ones printed on labels.
+#[get("/qr/<device>/<format>")]
+pub async fn get_qr_code(
+	config: &State<AppConfig>,
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+	format: ImageFormat,
+) -> Result<FileFromMemory, Error> {
+	let device = ensure_device_exists(conn, device).await?;
+
+	let code =
+		CodeMatrix::qr_code(device_link(config.public_url.as_deref(), device.as_str()).as_str())
+			.ok_or(UserError::BadRequest(
+				"The device link can't be encoded as a QR code.",
+			))?;
+
+	Ok(render_code_image(
+		&code,
+		format!("{device}-qr").as_str(),
+		format,
+	))
+}
+
+/// Returns the device ID back if the device exists, so codes aren't generated
+/// for devices that don't.
+async fn ensure_device_exists(conn: DbConn, device: String) -> Result<String, Error> {
+	conn.run(move |c| {
+		use schema::device_key_info::dsl::*;
+
+		let device_exists = select(exists(
+			device_key_info
+				.filter(deleted.eq(false))
+				.filter(device_id.eq(device.as_str())),
+		))
+		.get_result::<bool>(c)
+		.with_context("unable to query the database for device existence")?;
+		if !device_exists {
+			return Err(UserError::NotFound("Invalid device ID.").into());
+		}
+
+		Ok(device)
+	})
+	.await
+}
+
+fn render_code_image(code: &CodeMatrix, file_stem: &str, format: ImageFormat) -> FileFromMemory {
+	let contents = match format {
+		ImageFormat::Svg => code.render_svg().into_bytes(),
+		ImageFormat::Png => code.render_png(PNG_MODULE_SIZE),
+	};
+
+	FileFromMemory::new(
+		format!("{file_stem}.{}", format.extension()).as_str(),
+		contents,
+	)
+}
@@ -0,0 +1,174 @@
+// Uses
+use std::net::IpAddr;
+
+use chrono::{NaiveDate, Utc};
+use diesel::{insert_into, upsert::excluded, Connection, ExpressionMethods, RunQueryDsl};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+	State,
+};
+
+use super::{devices::load_device_key_info, Routable};
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	auth::{AdminUser, AuthedUser},
+	config::AppConfig,
+	db::{
+		change_log::{log_change, DeviceDiff, DeviceLifecycleDiff, Diff},
+		models::DeviceLifecycleNew,
+		schema,
+		DbConn,
+	},
+	error::{Context, Error},
+	lifecycle::{apply_warranty_expiry_transitions, load_device_lifecycle, load_expiry_report},
+};
+
+/// The route for this section.
+pub(super) struct LifecycleApi;
+impl Routable for LifecycleApi {
+	const PATH: &'static str = "/lifecycle";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> =
+		&|| routes![set_device_lifecycle, get_expiry_report, apply_transitions];
+}
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[allow(clippy::struct_field_names)]
+pub struct LifecycleInfo {
+	#[serde(default)]
+	purchased_on:     Option<NaiveDate>,
+	#[serde(default)]
+	warranty_ends_on: Option<NaiveDate>,
+	/// The date the device is planned to be replaced.
+	#[serde(default)]
+	end_of_life_on:   Option<NaiveDate>,
+}
+
+/// Sets the lifecycle dates of a device. Any dates that aren't provided are
+/// cleared.
+#[post("/set/<device>", data = "<lifecycle_info>")]
+pub async fn set_device_lifecycle(
+	user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+	lifecycle_info: Json<LifecycleInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::device_lifecycles::dsl::*;
+
+		c.transaction::<_, Error, _>(|tc| {
+			let device_key_info_result = load_device_key_info(tc, device.as_str())?;
+			let device_lifecycle_result = load_device_lifecycle(tc, device_key_info_result.id)?;
+
+			// A new warranty end date needs to be processed again once it passes
+			let insertable_device_lifecycle = DeviceLifecycleNew {
+				device_key_info_id:        device_key_info_result.id,
+				purchased_on:              lifecycle_info.purchased_on,
+				warranty_ends_on:          lifecycle_info.warranty_ends_on,
+				end_of_life_on:            lifecycle_info.end_of_life_on,
+				warranty_expiry_processed: device_lifecycle_result.as_ref().is_some_and(
+					|existing| {
+						existing.warranty_expiry_processed
+							&& existing.warranty_ends_on == lifecycle_info.warranty_ends_on
+					},
+				),
+			};
+
+			let Some(lifecycle_diff) = DeviceLifecycleDiff::calculate_diff(
+				&device_lifecycle_result,
+				&insertable_device_lifecycle,
+			) else {
+				return Ok(json!({ "deviceId": device }));
+			};
+
+			insert_into(device_lifecycles)
+				.values(&insertable_device_lifecycle)
+				.on_conflict(device_key_info_id)
+				.do_update()
+				.set((
+					purchased_on.eq(excluded(purchased_on)),
+					warranty_ends_on.eq(excluded(warranty_ends_on)),
+					end_of_life_on.eq(excluded(end_of_life_on)),
+					warranty_expiry_processed.eq(excluded(warranty_expiry_processed)),
+				))
+				.execute(tc)
+				.with_context("unable to upsert into device_lifecycles")?;
+
+			let diff = DeviceDiff {
+				device_lifecycle: Some(lifecycle_diff),
+				..Default::default()
+			};
+			log_change(tc, device_key_info_result.id, user_id_value, &diff)
+				.with_context("unable to log device change")?;
+
+			Ok(json!({ "deviceId": device }))
+		})
+	})
+	.await
+}
+
+/// Lists the devices with warranties expiring in the next `days` days (or the
+/// configured default), and the devices that are past their end of life.
+#[get("/expiring?<days>")]
+pub async fn get_expiry_report(
+	config: &State<AppConfig>,
+	_user: &AuthedUser,
+	conn: DbConn,
+	days: Option<u32>,
+) -> Result<JsonValue, Error> {
+	let days = days.unwrap_or(config.lifecycle.expiry_warning_days);
+	conn.run(move |c| {
+		let today = Utc::now().date_naive();
+		let report = load_expiry_report(c, today, days)?;
+
+		Ok(json!({ "today": today, "days": days, "report": report }))
+	})
+	.await
+}
+
+/// Makes the configured change to the devices with expired warranties. This
+/// also runs on a schedule, as the `warranty_expiry` job.
+#[post("/transitions/apply")]
+pub async fn apply_transitions(
+	config: &State<AppConfig>,
+	user: &AdminUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+) -> Result<JsonValue, Error> {
+	let lifecycle_settings = config.lifecycle.clone();
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		let today = Utc::now().date_naive();
+		let transitioned_device_ids = c.transaction::<_, Error, _>(|tc| {
+			let transitioned_device_ids = apply_warranty_expiry_transitions(
+				tc,
+				&lifecycle_settings,
+				today,
+				Some(user_id_value),
+			)?;
+			record_audit_event(
+				tc,
+				AuditEvent {
+					actor: Some(user_id_value),
+					action: AuditAction::WarrantyTransitionsApply,
+					target: AuditTarget::Devices,
+					before: None,
+					after: Some(json!({ "deviceIds": transitioned_device_ids })),
+					client_ip,
+				},
+			)?;
+
+			Ok(transitioned_device_ids)
+		})?;
+
+		Ok(json!({ "deviceIds": transitioned_device_ids }))
+	})
+	.await
+}
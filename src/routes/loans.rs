@@ -0,0 +1,422 @@
+// Uses
+use std::borrow::Cow;
+
+use chrono::{NaiveDateTime, Utc};
+use diesel::{
+	dsl::exists,
+	insert_into,
+	result::OptionalExtension,
+	select,
+	sql_query,
+	sql_types::{Integer, Text, Timestamp},
+	update,
+	Connection,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+};
+
+use super::Routable;
+use crate::{
+	auth::AuthedUser,
+	db::{
+		change_log::{
+			log_change,
+			DeviceDiff,
+			DeviceKeyInfoDiff,
+			DeviceKeyInfoDiffData,
+			DeviceLoanDiff,
+		},
+		models::{DeviceKeyInfo, DeviceLoan, DeviceLoanDisplay, DeviceLoanNew},
+		schema,
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+};
+
+/// The route for this section.
+pub(super) struct LoansApi;
+impl Routable for LoansApi {
+	const PATH: &'static str = "/loans";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			check_out_device,
+			check_in_device,
+			get_active_loans,
+			get_overdue_loans,
+			get_device_loans,
+			get_user_loans
+		]
+	};
+}
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoanCheckOutInfo {
+	device_id:   String,
+	borrower:    Borrower,
+	#[serde(default)]
+	due_at:      Option<NaiveDateTime>,
+	#[serde(default)]
+	notes:       String,
+	/// The location to move the device to, if it's changing.
+	#[serde(default)]
+	location_id: Option<i32>,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoanCheckInInfo {
+	device_id:   String,
+	#[serde(default)]
+	notes:       String,
+	/// The location to move the device to, if it's changing.
+	#[serde(default)]
+	location_id: Option<i32>,
+}
+/// The person borrowing a device - either a user of the app, or anyone else by
+/// name.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Borrower {
+	User(i32),
+	Name(String),
+}
+
+/// Which loans to list.
+enum LoanFilter {
+	/// Loans that haven't been returned yet.
+	Active,
+	/// Loans that haven't been returned yet and are past their due date.
+	Overdue(NaiveDateTime),
+	/// All loans of a device.
+	Device(String),
+	/// All loans to a user.
+	Borrower(i32),
+}
+
+/// Loans a device out to someone.
+#[post("/checkout", data = "<checkout_info>")]
+pub async fn check_out_device(
+	user: &AuthedUser,
+	conn: DbConn,
+	checkout_info: Json<LoanCheckOutInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{device_loans::dsl::*, user_info::dsl::user_info};
+
+		let now = Utc::now().naive_utc();
+
+		// Verify the submitted values
+		let (borrower_user_id_value, borrower_name_value) = match &checkout_info.borrower {
+			Borrower::User(provided_user_id) => {
+				if !select(exists(
+					user_info.filter(schema::user_info::dsl::id.eq(provided_user_id)),
+				))
+				.get_result::<bool>(c)
+				.with_context("unable to query the database for user existence")?
+				{
+					return Err(UserError::BadRequest("Invalid borrower.").into());
+				}
+				(Some(*provided_user_id), None)
+			}
+			Borrower::Name(provided_name) => {
+				let provided_name = provided_name.trim();
+				if provided_name.is_empty() {
+					return Err(UserError::BadRequest("The borrower's name can't be empty.").into());
+				}
+				(None, Some(provided_name.to_owned()))
+			}
+		};
+		if checkout_info
+			.due_at
+			.is_some_and(|provided_due_at| provided_due_at <= now)
+		{
+			return Err(UserError::BadRequest("The due date must be in the future.").into());
+		}
+		if let Some(new_location_id) = checkout_info.location_id {
+			verify_location(c, new_location_id)?;
+		}
+
+		c.transaction::<_, Error, _>(|tc| {
+			let device_key_info_result =
+				load_device_key_info(tc, checkout_info.device_id.as_str())?;
+
+			// Ensure that deleted devices aren't modified
+			if device_key_info_result.deleted {
+				return Err(UserError::BadRequest(
+					"The device has been deleted. It cannot be modified.",
+				)
+				.into());
+			}
+
+			if load_open_loan(tc, device_key_info_result.id)?.is_some() {
+				return Err(UserError::BadRequest(
+					"The device is already out on loan. It has to be checked in first.",
+				)
+				.into());
+			}
+
+			// Create the loan
+			insert_into(device_loans)
+				.values(DeviceLoanNew {
+					device_key_info_id: device_key_info_result.id,
+					borrower_user_id:   borrower_user_id_value,
+					borrower_name:      borrower_name_value.as_deref().map(Cow::from),
+					loaned_by:          user_id_value,
+					loaned_at:          now,
+					due_at:             checkout_info.due_at,
+					notes:              Cow::from(checkout_info.notes.as_str()),
+				})
+				.execute(tc)
+				.with_context("unable to insert into device_loans")?;
+			let new_loan_id = device_loans
+				.filter(device_key_info_id.eq(device_key_info_result.id))
+				.filter(returned_at.is_null())
+				.select(id)
+				.get_result::<i32>(tc)
+				.with_context("unable to get the ID of the new loan")?;
+
+			// Log the change in the database
+			let diff = DeviceDiff {
+				device_key_info: move_device(
+					tc,
+					&device_key_info_result,
+					checkout_info.location_id,
+				)?,
+				device_loan: Some(DeviceLoanDiff::CheckOut {
+					loan_id:          new_loan_id,
+					borrower_user_id: borrower_user_id_value,
+					borrower_name:    borrower_name_value.as_deref().map(Cow::from),
+					due_at:           checkout_info.due_at,
+					notes:            Cow::from(checkout_info.notes.as_str()),
+				}),
+				..Default::default()
+			};
+			log_change(tc, device_key_info_result.id, user_id_value, &diff)
+				.with_context("unable to log device change")?;
+
+			Ok(json!({ "loanId": new_loan_id }))
+		})
+	})
+	.await
+}
+
+/// Checks a device back in, closing its open loan.
+#[post("/checkin", data = "<checkin_info>")]
+pub async fn check_in_device(
+	user: &AuthedUser,
+	conn: DbConn,
+	checkin_info: Json<LoanCheckInInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::device_loans::dsl::*;
+
+		if let Some(new_location_id) = checkin_info.location_id {
+			verify_location(c, new_location_id)?;
+		}
+
+		c.transaction::<_, Error, _>(|tc| {
+			let device_key_info_result = load_device_key_info(tc, checkin_info.device_id.as_str())?;
+
+			let Some(open_loan) = load_open_loan(tc, device_key_info_result.id)? else {
+				return Err(UserError::BadRequest("The device isn't out on loan.").into());
+			};
+
+			// Close the loan
+			update(device_loans.filter(id.eq(open_loan.id)))
+				.set((
+					returned_at.eq(Utc::now().naive_utc()),
+					returned_to.eq(user_id_value),
+					return_notes.eq(checkin_info.notes.as_str()),
+				))
+				.execute(tc)
+				.with_context("unable to update device_loans")?;
+
+			// Log the change in the database
+			let diff = DeviceDiff {
+				device_key_info: move_device(
+					tc,
+					&device_key_info_result,
+					checkin_info.location_id,
+				)?,
+				device_loan: Some(DeviceLoanDiff::CheckIn {
+					loan_id: open_loan.id,
+					notes:   Cow::from(checkin_info.notes.as_str()),
+				}),
+				..Default::default()
+			};
+			log_change(tc, device_key_info_result.id, user_id_value, &diff)
+				.with_context("unable to log device change")?;
+
+			Ok(json!({ "loanId": open_loan.id }))
+		})
+	})
+	.await
+}
+
+/// Lists all loans that haven't been returned yet.
+#[get("/active")]
+pub async fn get_active_loans(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let loan_results = load_loans(c, LoanFilter::Active)?;
+
+		Ok(json!({ "loans": loan_results }))
+	})
+	.await
+}
+
+/// Lists all loans that are past their due date and haven't been returned yet.
+#[get("/overdue")]
+pub async fn get_overdue_loans(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let loan_results = load_loans(c, LoanFilter::Overdue(Utc::now().naive_utc()))?;
+
+		Ok(json!({ "loans": loan_results }))
+	})
+	.await
+}
+
+/// Lists the loan history of a device.
+#[get("/device/<device>")]
+pub async fn get_device_loans(
+	_user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let loan_results = load_loans(c, LoanFilter::Device(device))?;
+
+		Ok(json!({ "loans": loan_results }))
+	})
+	.await
+}
+
+/// Lists the loan history of a user, as the borrower.
+#[get("/user/<user>")]
+pub async fn get_user_loans(
+	_user: &AuthedUser,
+	conn: DbConn,
+	user: i32,
+) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let loan_results = load_loans(c, LoanFilter::Borrower(user))?;
+
+		Ok(json!({ "loans": loan_results }))
+	})
+	.await
+}
+
+/// Loads loans with the names of everyone involved, most recent first.
+fn load_loans<'a>(
+	conn: &mut SqliteConnection,
+	filter: LoanFilter,
+) -> Result<Vec<DeviceLoanDisplay<'a>>, Error> {
+	let mut loans_sql = String::from(include_str!(concat!(
+		env!("CARGO_MANIFEST_DIR"),
+		"/src/sql/device_loans.sql"
+	)));
+	loans_sql.push_str(match filter {
+		LoanFilter::Active => "WHERE dl.returned_at IS NULL\n",
+		LoanFilter::Overdue(_) => "WHERE dl.returned_at IS NULL AND dl.due_at < ?\n",
+		LoanFilter::Device(_) => "WHERE dki.device_id = ?\n",
+		LoanFilter::Borrower(_) => "WHERE dl.borrower_user_id = ?\n",
+	});
+	loans_sql.push_str("ORDER BY dl.loaned_at DESC");
+
+	let loans_query = sql_query(loans_sql).into_boxed();
+	let loans_query = match filter {
+		LoanFilter::Active => loans_query,
+		LoanFilter::Overdue(now) => loans_query.bind::<Timestamp, _>(now),
+		LoanFilter::Device(device) => loans_query.bind::<Text, _>(device),
+		LoanFilter::Borrower(borrower) => loans_query.bind::<Integer, _>(borrower),
+	};
+
+	loans_query
+		.load::<DeviceLoanDisplay<'_>>(conn)
+		.with_context("unable to load the device loans")
+}
+
+/// Loads the open loan of a device, if there is one.
+fn load_open_loan<'a>(
+	conn: &mut SqliteConnection,
+	internal_id: i32,
+) -> Result<Option<DeviceLoan<'a>>, Error> {
+	// Uses
+	use schema::device_loans::dsl::*;
+
+	device_loans
+		.filter(device_key_info_id.eq(internal_id))
+		.filter(returned_at.is_null())
+		.get_result::<DeviceLoan<'_>>(conn)
+		.optional()
+		.with_context("unable to query the database for open loans")
+}
+
+fn load_device_key_info<'a>(
+	conn: &mut SqliteConnection,
+	device: &str,
+) -> Result<DeviceKeyInfo<'a>, Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	device_key_info
+		.filter(device_id.eq(device))
+		.get_result::<DeviceKeyInfo<'_>>(conn)
+		.optional()
+		.with_context("unable to query the database for device_key_info existence")?
+		.ok_or(UserError::BadRequest("Invalid device ID.").into())
+}
+
+fn verify_location(conn: &mut SqliteConnection, location: i32) -> Result<(), Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	if !select(exists(locations.filter(id.eq(location))))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for location existence")?
+	{
+		return Err(UserError::BadRequest("Invalid location.").into());
+	}
+
+	Ok(())
+}
+
+/// Moves a device to a new location, if one is provided and it's different.
+///
+/// Returns the diff for the change, if there was one.
+fn move_device(
+	conn: &mut SqliteConnection,
+	device_key_info_result: &DeviceKeyInfo<'_>,
+	new_location_id: Option<i32>,
+) -> Result<Option<DeviceKeyInfoDiff>, Error> {
+	// Uses
+	use schema::device_key_info::dsl::*;
+
+	let Some(new_location_id) = new_location_id
+		.filter(|new_location_id| *new_location_id != device_key_info_result.location_id)
+	else {
+		return Ok(None);
+	};
+
+	update(device_key_info.filter(id.eq(device_key_info_result.id)))
+		.set(location_id.eq(new_location_id))
+		.execute(conn)
+		.with_context("unable to update device_key_info")?;
+
+	Ok(Some(DeviceKeyInfoDiff::Edit(DeviceKeyInfoDiffData {
+		location_id: Some(new_location_id),
+	})))
+}
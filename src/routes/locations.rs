@@ -0,0 +1,258 @@
+// Uses
+use std::{borrow::Cow, net::IpAddr};
+
+use diesel::{
+	dsl::{exists, not},
+	insert_into,
+	select,
+	sql_query,
+	update,
+	Connection,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+};
+
+use super::Routable;
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	auth::AuthedUser,
+	db::{
+		models::{LocationDefinition, LocationDefinitionNew, LocationSummary},
+		schema,
+		util::{fetch_new_rowid_on, location_is_within},
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+};
+
+/// The route for this section.
+pub(super) struct LocationsApi;
+impl Routable for LocationsApi {
+	const PATH: &'static str = "/locations";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> =
+		&|| routes![get_locations, create_location, update_location];
+}
+
+// Constants
+/// The separator between the names of locations in a path. Names can't contain
+/// it, so that paths aren't ambiguous.
+const PATH_SEPARATOR: char = '/';
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LocationInfo {
+	name:      String,
+	/// The location to nest this one under, or nothing for a top-level
+	/// location.
+	#[serde(default)]
+	parent_id: Option<i32>,
+}
+
+/// Lists all locations in path order, with the number of devices in each one
+/// and in each one's subtree.
+#[get("/list")]
+pub async fn get_locations(_user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	conn.run(move |c| {
+		let location_results = sql_query(include_str!(concat!(
+			env!("CARGO_MANIFEST_DIR"),
+			"/src/sql/location_summaries.sql"
+		)))
+		.load::<LocationSummary<'_>>(c)
+		.with_context("unable to load the locations")?;
+
+		Ok(json!({ "locations": location_results }))
+	})
+	.await
+}
+
+/// Creates a new location.
+#[post("/create", data = "<location_info>")]
+pub async fn create_location(
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	location_info: Json<LocationInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::locations::dsl::*;
+
+		let new_name = verify_location_name(location_info.name.as_str())?;
+
+		c.transaction::<_, Error, _>(|tc| {
+			if let Some(new_parent_id) = location_info.parent_id {
+				verify_location(tc, new_parent_id)?;
+			}
+			verify_name_is_free(tc, None, location_info.parent_id, new_name)?;
+
+			insert_into(locations)
+				.values(LocationDefinitionNew {
+					parent_id: location_info.parent_id,
+					name:      Cow::from(new_name),
+				})
+				.execute(tc)
+				.with_context("unable to insert into locations")?;
+			let new_location_id =
+				fetch_new_rowid_on(tc).with_context("unable to get the ID of the new location")?;
+
+			let location_after = load_location_definition(tc, new_location_id)?;
+			record_audit_event(
+				tc,
+				AuditEvent {
+					actor: Some(user_id_value),
+					action: AuditAction::LocationCreate,
+					target: AuditTarget::Location(new_location_id),
+					before: None,
+					after: Some(json!(location_after)),
+					client_ip,
+				},
+			)?;
+
+			Ok(json!({ "locationId": new_location_id }))
+		})
+	})
+	.await
+}
+
+/// Renames a location, or moves it (and everything in it) under a different
+/// parent.
+#[post("/update/<location>", data = "<location_info>")]
+pub async fn update_location(
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	location: i32,
+	location_info: Json<LocationInfo>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::locations::dsl::*;
+
+		let new_name = verify_location_name(location_info.name.as_str())?;
+
+		c.transaction::<_, Error, _>(|tc| {
+			if !select(exists(locations.filter(id.eq(location))))
+				.get_result::<bool>(tc)
+				.with_context("unable to query the database for location existence")?
+			{
+				return Err(UserError::NotFound("Invalid location.").into());
+			}
+			if let Some(new_parent_id) = location_info.parent_id {
+				verify_location(tc, new_parent_id)?;
+				if location_is_within(tc, new_parent_id, location)? {
+					return Err(UserError::BadRequest(
+						"A location can't be moved into itself or one of its descendants.",
+					)
+					.into());
+				}
+			}
+			verify_name_is_free(tc, Some(location), location_info.parent_id, new_name)?;
+
+			let location_before = load_location_definition(tc, location)?;
+			update(locations.filter(id.eq(location)))
+				.set((parent_id.eq(location_info.parent_id), name.eq(new_name)))
+				.execute(tc)
+				.with_context("unable to update locations")?;
+
+			let location_after = load_location_definition(tc, location)?;
+			record_audit_event(
+				tc,
+				AuditEvent {
+					actor: Some(user_id_value),
+					action: AuditAction::LocationUpdate,
+					target: AuditTarget::Location(location),
+					before: Some(json!(location_before)),
+					after: Some(json!(location_after)),
+					client_ip,
+				},
+			)?;
+
+			Ok(json!({ "locationId": location }))
+		})
+	})
+	.await
+}
+
+/// Trims a submitted location name, and ensures that it's usable.
+fn verify_location_name(location_name: &str) -> Result<&str, Error> {
+	let location_name = location_name.trim();
+	if location_name.is_empty() {
+		return Err(UserError::BadRequest("Location names can't be empty.").into());
+	}
+	if location_name.contains(PATH_SEPARATOR) {
+		return Err(UserError::BadRequest("Location names can't contain a '/'.").into());
+	}
+
+	Ok(location_name)
+}
+
+fn verify_location(conn: &mut SqliteConnection, location: i32) -> Result<(), Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	if !select(exists(locations.filter(id.eq(location))))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for location existence")?
+	{
+		return Err(UserError::BadRequest("Invalid parent location.").into());
+	}
+
+	Ok(())
+}
+
+/// Ensures that no other location under the same parent already has the name.
+fn verify_name_is_free(
+	conn: &mut SqliteConnection,
+	location: Option<i32>,
+	parent: Option<i32>,
+	location_name: &str,
+) -> Result<(), Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	let mut sibling_query = locations.filter(name.eq(location_name)).into_boxed();
+	sibling_query = match parent {
+		Some(parent) => sibling_query.filter(parent_id.eq(parent)),
+		None => sibling_query.filter(parent_id.is_null()),
+	};
+	if let Some(location) = location {
+		sibling_query = sibling_query.filter(not(id.eq(location)));
+	}
+
+	if select(exists(sibling_query))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for location name existence")?
+	{
+		return Err(
+			UserError::BadRequest("A location with that name already exists there.").into(),
+		);
+	}
+
+	Ok(())
+}
+
+/// Loads a location's definition, as it's recorded in the audit log.
+fn load_location_definition<'a>(
+	conn: &mut SqliteConnection,
+	location: i32,
+) -> Result<LocationDefinition<'a>, Error> {
+	// Uses
+	use schema::locations::dsl::*;
+
+	locations
+		.filter(id.eq(location))
+		.get_result::<LocationDefinition<'_>>(conn)
+		.with_context("unable to load the location")
+}
@@ -5,15 +5,43 @@ use crate::{
 	auth::LdapAuthenticator,
 	config::{load_complete_config, validate_config, AppConfig, LdapSettings},
 	db::{init as init_db, DbConn},
-	routes::{admin::AdminApi, auth::AuthApi, devices::DevicesApi, svelte_pages::SveltePages},
+	jobs::start_scheduler,
+	live_changes::{start_live_changes, LiveChanges},
+	notifications::start_mailer,
+	routes::{
+		admin::AdminApi,
+		audits::AuditsApi,
+		auth::AuthApi,
+		bookings::BookingsApi,
+		changes::ChangesApi,
+		devices::DevicesApi,
+		labels::LabelsApi,
+		lifecycle::LifecycleApi,
+		loans::LoansApi,
+		locations::LocationsApi,
+		notifications::NotificationsApi,
+		svelte_pages::SveltePages,
+		watches::WatchesApi,
+	},
+	webhooks::start_dispatcher,
 };
 
 // Modules
 mod admin;
+mod audits;
 mod auth;
+mod bookings;
+mod changes;
 mod devices;
 mod file_from_memory;
+mod if_match;
+mod labels;
+mod lifecycle;
+mod loans;
+mod locations;
+mod notifications;
 mod svelte_pages;
+mod watches;
 
 // Constants
 const API_ROOT: &str = "/api";
@@ -28,7 +56,18 @@ pub fn rocket() -> Rocket<Build> {
 		.attach(AdHoc::config::<AppConfig>())
 		.attach(AdHoc::try_on_ignite("Config Validation", validate_config))
 		.attach(DbConn::fairing())
-		.attach(AdHoc::try_on_ignite("Database Setup", init_db));
+		.attach(AdHoc::try_on_ignite("Database Setup", init_db))
+		.attach(AdHoc::on_liftoff("Job Scheduler", |r| {
+			Box::pin(start_scheduler(r))
+		}))
+		.attach(AdHoc::on_liftoff("Webhook Dispatcher", |r| {
+			Box::pin(start_dispatcher(r))
+		}))
+		.manage(LiveChanges::new())
+		.attach(AdHoc::on_liftoff("Live Changes", |r| {
+			Box::pin(start_live_changes(r))
+		}))
+		.attach(AdHoc::on_liftoff("Mailer", |r| Box::pin(start_mailer(r))));
 
 	// Fetch the Svelte path
 	let svelte_path = rocket
@@ -66,10 +105,46 @@ pub fn rocket() -> Rocket<Build> {
 			format!("{API_ROOT}{}", DevicesApi::PATH).as_str(),
 			DevicesApi::ROUTES(),
 		)
+		.mount(
+			format!("{API_ROOT}{}", LabelsApi::PATH).as_str(),
+			LabelsApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", LocationsApi::PATH).as_str(),
+			LocationsApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", LoansApi::PATH).as_str(),
+			LoansApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", BookingsApi::PATH).as_str(),
+			BookingsApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", ChangesApi::PATH).as_str(),
+			ChangesApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", AuditsApi::PATH).as_str(),
+			AuditsApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", LifecycleApi::PATH).as_str(),
+			LifecycleApi::ROUTES(),
+		)
 		.mount(
 			format!("{API_ROOT}{}", AdminApi::PATH).as_str(),
 			AdminApi::ROUTES(),
 		)
+		.mount(
+			format!("{API_ROOT}{}", NotificationsApi::PATH).as_str(),
+			NotificationsApi::ROUTES(),
+		)
+		.mount(
+			format!("{API_ROOT}{}", WatchesApi::PATH).as_str(),
+			WatchesApi::ROUTES(),
+		)
 		.mount(SveltePages::PATH, SveltePages::ROUTES())
 		.mount("/", FileServer::from(svelte_path.as_str()));
 
@@ -0,0 +1,254 @@
+// Uses
+use std::net::IpAddr;
+
+use diesel::{insert_into, update, ExpressionMethods, QueryDsl, RunQueryDsl};
+use lettre::Address;
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+	State,
+};
+
+use super::Routable;
+use crate::{
+	audit_log::{record_audit_event, AuditAction, AuditEvent, AuditTarget},
+	auth::AuthedUser,
+	config::AppConfig,
+	db::{
+		models::{Notification, NotificationPreferences},
+		schema,
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+	notifications::{enqueue_notification, load_notification_preferences, NotificationKind},
+};
+
+// Constants
+/// The number of notifications to return when listing a user's notifications.
+const NOTIFICATION_HISTORY_LIMIT: i64 = 100;
+
+/// The route for this section.
+pub(super) struct NotificationsApi;
+impl Routable for NotificationsApi {
+	const PATH: &'static str = "/notifications";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			get_notifications,
+			get_preferences,
+			set_preferences,
+			set_email,
+			send_test_notification
+		]
+	};
+}
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationPreferencesInfo {
+	loan_overdue:      bool,
+	warranty_expiring: bool,
+	device_changed:    bool,
+}
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EmailInfo {
+	/// The new email address, or `None` to remove it.
+	#[serde(default)]
+	email: Option<String>,
+}
+
+/// Lists the most recent notifications queued for the current user, newest
+/// first.
+#[get("/")]
+pub async fn get_notifications(user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::notifications::dsl::*;
+
+		let notification_results = notifications
+			.filter(user_id.eq(user_id_value))
+			.order_by(created_at.desc())
+			.limit(NOTIFICATION_HISTORY_LIMIT)
+			.load::<Notification<'_>>(c)
+			.with_context("unable to load the notifications")?;
+
+		Ok(json!({ "notifications": notification_results }))
+	})
+	.await
+}
+
+/// Gets the current user's email address and notification preferences.
+#[get("/preferences")]
+pub async fn get_preferences(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	let email_enabled = config.email.enabled;
+	let email_managed = email_managed_by_ldap(config);
+	conn.run(move |c| {
+		// Uses
+		use schema::user_info::dsl::*;
+
+		let email_value = user_info
+			.filter(id.eq(user_id_value))
+			.select(email)
+			.get_result::<Option<String>>(c)
+			.with_context("unable to load the user's email address")?;
+
+		Ok(json!({
+			"emailEnabled": email_enabled,
+			"email": email_value,
+			"emailManaged": email_managed,
+			"preferences": load_notification_preferences(c, user_id_value)?,
+		}))
+	})
+	.await
+}
+
+/// Sets which notifications the current user is sent.
+#[post("/preferences", data = "<preferences_info>")]
+pub async fn set_preferences(
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	preferences_info: Json<NotificationPreferencesInfo>,
+) -> Result<JsonValue, Error> {
+	let new_preferences = NotificationPreferences {
+		user_id:           user.0.id,
+		loan_overdue:      preferences_info.loan_overdue,
+		warranty_expiring: preferences_info.warranty_expiring,
+		device_changed:    preferences_info.device_changed,
+	};
+	conn.run(move |c| {
+		// Uses
+		use schema::notification_preferences::dsl::*;
+
+		let old_preferences = load_notification_preferences(c, new_preferences.user_id)?;
+		insert_into(notification_preferences)
+			.values(&new_preferences)
+			.on_conflict(user_id)
+			.do_update()
+			.set(&new_preferences)
+			.execute(c)
+			.with_context("unable to save the notification preferences")?;
+		record_audit_event(
+			c,
+			AuditEvent {
+				actor: Some(new_preferences.user_id),
+				action: AuditAction::NotificationPreferencesUpdate,
+				target: AuditTarget::User(Some(new_preferences.user_id)),
+				before: Some(json!(old_preferences)),
+				after: Some(json!(new_preferences)),
+				client_ip,
+			},
+		)?;
+
+		Ok(json!({ "preferences": new_preferences }))
+	})
+	.await
+}
+
+/// Sets the current user's email address.
+///
+/// This isn't allowed when email addresses are kept in sync with LDAP.
+#[post("/email", data = "<email_info>")]
+pub async fn set_email(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+	client_ip: Option<IpAddr>,
+	email_info: Json<EmailInfo>,
+) -> Result<JsonValue, Error> {
+	if email_managed_by_ldap(config) {
+		return Err(UserError::BadRequest("Email addresses are managed by LDAP.").into());
+	}
+
+	let new_email = email_info
+		.into_inner()
+		.email
+		.map(|value| value.trim().to_owned())
+		.filter(|value| !value.is_empty());
+	if let Some(new_email) = &new_email {
+		new_email
+			.parse::<Address>()
+			.map_err(|_| UserError::BadRequest("Invalid email address."))?;
+	}
+
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::user_info::dsl::*;
+
+		let old_email = user_info
+			.filter(id.eq(user_id_value))
+			.select(email)
+			.get_result::<Option<String>>(c)
+			.with_context("unable to load the user's email address")?;
+		update(user_info.filter(id.eq(user_id_value)))
+			.set(email.eq(&new_email))
+			.execute(c)
+			.with_context("unable to update the user's email address")?;
+		record_audit_event(
+			c,
+			AuditEvent {
+				actor: Some(user_id_value),
+				action: AuditAction::EmailUpdate,
+				target: AuditTarget::User(Some(user_id_value)),
+				before: Some(json!({ "email": old_email })),
+				after: Some(json!({ "email": new_email })),
+				client_ip,
+			},
+		)?;
+
+		Ok(json!({ "email": new_email }))
+	})
+	.await
+}
+
+/// Queues a test email to the current user.
+#[post("/test")]
+pub async fn send_test_notification(
+	config: &State<AppConfig>,
+	user: &AuthedUser,
+	conn: DbConn,
+) -> Result<JsonValue, Error> {
+	if !config.email.enabled {
+		return Err(UserError::BadRequest("Email notifications are disabled.").into());
+	}
+
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::user_info::dsl::*;
+
+		let email_value = user_info
+			.filter(id.eq(user_id_value))
+			.select(email)
+			.get_result::<Option<String>>(c)
+			.with_context("unable to load the user's email address")?
+			.ok_or(UserError::BadRequest(
+				"You don't have an email address set.",
+			))?;
+
+		enqueue_notification(c, user_id_value, NotificationKind::Test, &json!({}), None)?;
+
+		Ok(json!({ "email": email_value }))
+	})
+	.await
+}
+
+/// Whether users' email addresses come from LDAP, instead of being set in the
+/// app.
+fn email_managed_by_ldap(config: &AppConfig) -> bool {
+	config
+		.ldap
+		.as_ref()
+		.is_some_and(|ldap| ldap.enabled && ldap.user_email_attribute.is_some())
+}
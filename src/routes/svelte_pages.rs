@@ -1,8 +1,25 @@
 // Uses
-use rocket::{fs::NamedFile, get, response::Redirect, routes, Route, State};
+use rocket::{
+	fs::NamedFile,
+	get,
+	http::{
+		uri::{fmt::Path, Segments},
+		RawStr,
+	},
+	response::Redirect,
+	routes,
+	Route,
+	State,
+};
 
 use super::Routable;
-use crate::{auth::AuthedUserForwarding, config::AppConfig};
+use crate::{
+	auth::AuthedUserForwarding,
+	config::AppConfig,
+	db::DbConn,
+	error::{Error, UserError},
+	labels::resolve_scanned_code,
+};
 
 /// The route for this section.
 pub(super) struct SveltePages;
@@ -18,7 +35,9 @@ impl Routable for SveltePages {
 			edit_page,
 			edit_page_redir,
 			edit_page_with_param,
-			edit_page_with_param_redir
+			edit_page_with_param_redir,
+			scan_page,
+			scan_page_redir
 		]
 	};
 }
@@ -69,6 +88,31 @@ pub async fn edit_page_with_param(
 	edit_page(config, user).await
 }
 
+/// The landing page for scanned codes.
+///
+/// This resolves whatever was scanned (a device ID, a component ID, or a full
+/// link to a device) and redirects to the matching device's page.
+#[get("/scan/<payload..>")]
+pub async fn scan_page(
+	_user: &AuthedUserForwarding,
+	conn: DbConn,
+	payload: Segments<'_, Path>,
+) -> Result<Redirect, Error> {
+	let scanned = payload.collect::<Vec<_>>().join("/");
+
+	let found_device = conn
+		.run(move |c| resolve_scanned_code(c, scanned.as_str()))
+		.await?;
+	let Some(found_device) = found_device else {
+		return Err(UserError::NotFound("No device matches the scanned code.").into());
+	};
+
+	Ok(Redirect::to(format!(
+		"/edit/{}",
+		RawStr::new(found_device.as_str()).percent_encode()
+	)))
+}
+
 // Auth redirect catchers - these redirect based on whether the user is logged
 // in or not
 fn redirect_to_login() -> Redirect {
@@ -99,3 +143,8 @@ pub fn edit_page_redir() -> Redirect {
 pub fn edit_page_with_param_redir(_device: String) -> Redirect {
 	redirect_to_login()
 }
+
+#[get("/scan/<_payload..>", rank = 9)]
+pub fn scan_page_redir(_payload: Segments<'_, Path>) -> Redirect {
+	redirect_to_login()
+}
@@ -0,0 +1,617 @@
+// Uses
+use std::{borrow::Cow, collections::HashMap};
+
+use chrono::Utc;
+use diesel::{
+	delete,
+	insert_into,
+	result::OptionalExtension,
+	sql_query,
+	sql_types::Integer,
+	Connection,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use rocket::{
+	get,
+	post,
+	routes,
+	serde::json::{json, Json, Value as JsonValue},
+	Route,
+};
+use serde_json::{from_str as from_json_str, to_string as to_json_string};
+
+use super::{
+	devices::{build_search_query, SubmittedSearchQuery},
+	Routable,
+};
+use crate::{
+	auth::AuthedUser,
+	db::{
+		models::{
+			DeviceChangeDisplay,
+			DeviceInfoByName,
+			SavedSearch,
+			SavedSearchNew,
+			Watch,
+			WatchFeedVisit,
+			WatchNew,
+			DEVICE_CHANGE,
+		},
+		schema,
+		DbConn,
+	},
+	error::{Context, Error, UserError},
+};
+
+// Constants
+/// The most changes to return from the feed at once.
+const FEED_PAGE_SIZE: i64 = 200;
+
+/// The route for this section.
+pub(super) struct WatchesApi;
+impl Routable for WatchesApi {
+	const PATH: &'static str = "/watches";
+	const ROUTES: &'static dyn Fn() -> Vec<Route> = &|| {
+		routes![
+			list_watches,
+			watch_device,
+			watch_location,
+			watch_saved_search,
+			remove_watch,
+			list_saved_searches,
+			save_search,
+			delete_saved_search,
+			get_feed
+		]
+	};
+}
+
+// Type Definitions
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SavedSearchInfo {
+	name:  String,
+	/// The search query, in the same form as it's submitted to the search
+	/// route.
+	query: JsonValue,
+}
+/// The thing a watch is on.
+enum WatchTarget {
+	Device(i32),
+	Location(i32),
+	SavedSearch(i32),
+}
+/// A change to something a user is watching.
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WatchFeedEntry<'a> {
+	pub change:    DeviceChangeDisplay<'a>,
+	pub device_id: String,
+	/// The watches that the change matched.
+	pub watch_ids: Vec<i32>,
+}
+/// A device in a watched location.
+#[derive(QueryableByName, Debug)]
+struct WatchedLocationDevice {
+	#[diesel(sql_type = Integer)]
+	watch_id:           i32,
+	#[diesel(sql_type = Integer)]
+	device_key_info_id: i32,
+}
+
+/// Lists the current user's watches, along with what each one is on.
+#[get("/list")]
+pub async fn list_watches(user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{device_key_info, location_paths, saved_searches, watches};
+
+		let watch_results = watches::table
+			.filter(watches::user_id.eq(user_id_value))
+			.order_by(watches::created_at)
+			.load::<Watch>(c)
+			.with_context("unable to load the watches")?;
+
+		let mut watch_entries = Vec::with_capacity(watch_results.len());
+		for watch in watch_results {
+			let target_name = if let Some(internal_id) = watch.device_key_info_id {
+				device_key_info::table
+					.filter(device_key_info::id.eq(internal_id))
+					.select(device_key_info::device_id)
+					.get_result::<String>(c)
+			} else if let Some(watched_location_id) = watch.location_id {
+				location_paths::table
+					.filter(location_paths::id.eq(watched_location_id))
+					.select(location_paths::path)
+					.get_result::<String>(c)
+			} else {
+				saved_searches::table
+					.filter(saved_searches::id.eq(watch.saved_search_id.unwrap_or_default()))
+					.select(saved_searches::name)
+					.get_result::<String>(c)
+			}
+			.with_context("unable to load what a watch is on")?;
+
+			watch_entries.push(json!({ "watch": watch, "name": target_name }));
+		}
+
+		Ok(json!({ "watches": watch_entries }))
+	})
+	.await
+}
+
+/// Watches a device.
+#[post("/device/<device>")]
+pub async fn watch_device(
+	user: &AuthedUser,
+	conn: DbConn,
+	device: String,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::device_key_info::dsl::*;
+
+		let internal_id = device_key_info
+			.filter(device_id.eq(device.as_str()))
+			.select(id)
+			.get_result::<i32>(c)
+			.optional()
+			.with_context("unable to query the database for device_key_info existence")?
+			.ok_or(UserError::BadRequest("Invalid device ID."))?;
+
+		add_watch(c, user_id_value, &WatchTarget::Device(internal_id))
+	})
+	.await
+}
+
+/// Watches a location, which includes everything in its descendants.
+#[post("/location/<location>")]
+pub async fn watch_location(
+	user: &AuthedUser,
+	conn: DbConn,
+	location: i32,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::locations::dsl::*;
+
+		let location_exists = locations
+			.filter(id.eq(location))
+			.count()
+			.get_result::<i64>(c)
+			.with_context("unable to query the database for location existence")?
+			> 0;
+		if !location_exists {
+			return Err(UserError::BadRequest("Invalid location.").into());
+		}
+
+		add_watch(c, user_id_value, &WatchTarget::Location(location))
+	})
+	.await
+}
+
+/// Watches one of the current user's saved searches.
+#[post("/search/<search>")]
+pub async fn watch_saved_search(
+	user: &AuthedUser,
+	conn: DbConn,
+	search: i32,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		load_saved_search(c, user_id_value, search)?;
+
+		add_watch(c, user_id_value, &WatchTarget::SavedSearch(search))
+	})
+	.await
+}
+
+/// Stops watching something.
+#[post("/remove/<watch>")]
+pub async fn remove_watch(user: &AuthedUser, conn: DbConn, watch: i32) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::watches::dsl::*;
+
+		let removed_count = delete(
+			watches
+				.filter(id.eq(watch))
+				.filter(user_id.eq(user_id_value)),
+		)
+		.execute(c)
+		.with_context("unable to delete from watches")?;
+		if removed_count == 0 {
+			return Err(UserError::NotFound("Invalid watch ID.").into());
+		}
+
+		Ok(json!({ "removed": watch }))
+	})
+	.await
+}
+
+/// Lists the current user's saved searches.
+#[get("/searches")]
+pub async fn list_saved_searches(user: &AuthedUser, conn: DbConn) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::saved_searches::dsl::*;
+
+		let search_results = saved_searches
+			.filter(user_id.eq(user_id_value))
+			.order_by(name)
+			.load::<SavedSearch<'_>>(c)
+			.with_context("unable to load the saved searches")?;
+
+		Ok(json!({ "savedSearches": search_results }))
+	})
+	.await
+}
+
+/// Saves a search under a name, replacing the current user's search with that
+/// name if there is one.
+#[post("/searches/save", data = "<search_info>")]
+pub async fn save_search(
+	user: &AuthedUser,
+	conn: DbConn,
+	search_info: Json<SavedSearchInfo>,
+) -> Result<JsonValue, Error> {
+	let search_info = search_info.into_inner();
+	let search_name = search_info.name.trim().to_owned();
+	if search_name.is_empty() {
+		return Err(UserError::BadRequest("The search needs a name.").into());
+	}
+	let serialised_query =
+		to_json_string(&search_info.query).with_context("unable to serialise the search query")?;
+	from_json_str::<SubmittedSearchQuery>(&serialised_query)
+		.map_err(|_| UserError::BadRequest("Invalid search query."))?
+		.validate()?;
+
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::saved_searches::dsl::*;
+
+		insert_into(saved_searches)
+			.values(SavedSearchNew {
+				user_id:    user_id_value,
+				name:       Cow::from(search_name.as_str()),
+				query:      Cow::from(serialised_query.as_str()),
+				created_at: Utc::now().naive_utc(),
+			})
+			.on_conflict((user_id, name))
+			.do_update()
+			.set(query.eq(serialised_query.as_str()))
+			.execute(c)
+			.with_context("unable to save the search")?;
+
+		let search_result = saved_searches
+			.filter(user_id.eq(user_id_value))
+			.filter(name.eq(search_name.as_str()))
+			.get_result::<SavedSearch<'_>>(c)
+			.with_context("unable to load the saved search")?;
+
+		Ok(json!({ "savedSearch": search_result }))
+	})
+	.await
+}
+
+/// Deletes one of the current user's saved searches, along with any watch on
+/// it.
+#[post("/searches/delete/<search>")]
+pub async fn delete_saved_search(
+	user: &AuthedUser,
+	conn: DbConn,
+	search: i32,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	conn.run(move |c| {
+		// Uses
+		use schema::{saved_searches, watches};
+
+		load_saved_search(c, user_id_value, search)?;
+
+		c.transaction::<_, Error, _>(|tc| {
+			delete(watches::table.filter(watches::saved_search_id.eq(search)))
+				.execute(tc)
+				.with_context("unable to delete from watches")?;
+			delete(saved_searches::table.filter(saved_searches::id.eq(search)))
+				.execute(tc)
+				.with_context("unable to delete from saved_searches")?;
+
+			Ok(json!({ "deleted": search }))
+		})
+	})
+	.await
+}
+
+/// Lists the changes to everything the current user is watching since their
+/// last visit, oldest first.
+///
+/// Each call counts as a visit, so the next one carries on from where this one
+/// left off. `after` starts from a given change instead, and `peek` leaves the
+/// last visit as it is; neither of them count as a visit.
+#[get("/feed?<after>&<peek>")]
+pub async fn get_feed(
+	user: &AuthedUser,
+	conn: DbConn,
+	after: Option<i32>,
+	peek: Option<bool>,
+) -> Result<JsonValue, Error> {
+	let user_id_value = user.0.id;
+	let is_visit = after.is_none() && peek != Some(true);
+	conn.run(move |c| {
+		// Uses
+		use schema::watch_feed_visits::dsl::*;
+
+		let last_visit = watch_feed_visits
+			.filter(user_id.eq(user_id_value))
+			.get_result::<WatchFeedVisit>(c)
+			.optional()
+			.with_context("unable to load the last watch feed visit")?;
+		let after_change_id = after
+			.or_else(|| last_visit.as_ref().map(|visit| visit.last_seen_change_id))
+			.unwrap_or_default();
+
+		let mut feed_entries =
+			load_watch_feed(c, user_id_value, after_change_id, FEED_PAGE_SIZE + 1)?;
+		let has_more = feed_entries.len() > FEED_PAGE_SIZE as usize;
+		feed_entries.truncate(FEED_PAGE_SIZE as usize);
+
+		if is_visit {
+			// Everything up to the latest change has been checked unless the page was
+			// cut off
+			let seen_change_id = if has_more {
+				feed_entries
+					.last()
+					.map_or(after_change_id, |entry| entry.change.id)
+			} else {
+				load_latest_change_id(c)?.max(after_change_id)
+			};
+			let visit = WatchFeedVisit {
+				user_id:             user_id_value,
+				last_seen_change_id: seen_change_id,
+				visited_at:          Utc::now().naive_utc(),
+			};
+			insert_into(watch_feed_visits)
+				.values(&visit)
+				.on_conflict(user_id)
+				.do_update()
+				.set(&visit)
+				.execute(c)
+				.with_context("unable to record the watch feed visit")?;
+		}
+
+		Ok(json!({
+			"changes": feed_entries,
+			"afterChangeId": after_change_id,
+			"hasMore": has_more,
+			"lastVisitedAt": last_visit.map(|visit| visit.visited_at),
+		}))
+	})
+	.await
+}
+
+/// Adds a watch for a user, if they aren't already watching the same thing.
+///
+/// The first watch a user adds starts their feed from the latest change, so
+/// that it isn't filled with history.
+fn add_watch(
+	conn: &mut SqliteConnection,
+	user: i32,
+	target: &WatchTarget,
+) -> Result<JsonValue, Error> {
+	// Uses
+	use schema::{watch_feed_visits, watches};
+
+	let (target_device, target_location, target_search) = match *target {
+		WatchTarget::Device(internal_id) => (Some(internal_id), None, None),
+		WatchTarget::Location(location) => (None, Some(location), None),
+		WatchTarget::SavedSearch(search) => (None, None, Some(search)),
+	};
+
+	conn.transaction::<_, Error, _>(|tc| {
+		insert_into(watches::table)
+			.values(WatchNew {
+				user_id:            user,
+				device_key_info_id: target_device,
+				location_id:        target_location,
+				saved_search_id:    target_search,
+				created_at:         Utc::now().naive_utc(),
+			})
+			.on_conflict_do_nothing()
+			.execute(tc)
+			.with_context("unable to insert into watches")?;
+
+		let latest_change_id = load_latest_change_id(tc)?;
+		insert_into(watch_feed_visits::table)
+			.values(WatchFeedVisit {
+				user_id:             user,
+				last_seen_change_id: latest_change_id,
+				visited_at:          Utc::now().naive_utc(),
+			})
+			.on_conflict_do_nothing()
+			.execute(tc)
+			.with_context("unable to insert into watch_feed_visits")?;
+
+		let mut watch_query = watches::table
+			.filter(watches::user_id.eq(user))
+			.into_boxed();
+		watch_query = match *target {
+			WatchTarget::Device(internal_id) => {
+				watch_query.filter(watches::device_key_info_id.eq(internal_id))
+			}
+			WatchTarget::Location(location) => {
+				watch_query.filter(watches::location_id.eq(location))
+			}
+			WatchTarget::SavedSearch(search) => {
+				watch_query.filter(watches::saved_search_id.eq(search))
+			}
+		};
+		let watch = watch_query
+			.get_result::<Watch>(tc)
+			.with_context("unable to load the watch")?;
+
+		Ok(json!({ "watch": watch }))
+	})
+}
+
+/// Loads one of a user's saved searches.
+fn load_saved_search<'a>(
+	conn: &mut SqliteConnection,
+	user: i32,
+	search: i32,
+) -> Result<SavedSearch<'a>, Error> {
+	// Uses
+	use schema::saved_searches::dsl::*;
+
+	saved_searches
+		.filter(id.eq(search))
+		.filter(user_id.eq(user))
+		.get_result::<SavedSearch<'_>>(conn)
+		.optional()
+		.with_context("unable to load the saved search")?
+		.ok_or_else(|| UserError::NotFound("Invalid saved search ID.").into())
+}
+
+/// Fetches the ID of the most recent change.
+fn load_latest_change_id(conn: &mut SqliteConnection) -> Result<i32, Error> {
+	// Uses
+	use schema::device_changes::dsl::*;
+
+	device_changes
+		.select(id)
+		.order_by(id.desc())
+		.first::<i32>(conn)
+		.optional()
+		.with_context("unable to load the latest device change")
+		.map(Option::unwrap_or_default)
+}
+
+/// Finds the devices a user is watching, along with the watches that cover
+/// each one.
+///
+/// Locations cover the devices currently in them or their descendants, and
+/// saved searches cover the devices they currently find.
+fn load_watched_devices(
+	conn: &mut SqliteConnection,
+	user: i32,
+) -> Result<HashMap<i32, Vec<i32>>, Error> {
+	// Uses
+	use schema::{saved_searches, watches};
+
+	let mut watched_devices = HashMap::<i32, Vec<i32>>::new();
+
+	let device_watches = watches::table
+		.filter(watches::user_id.eq(user))
+		.filter(watches::device_key_info_id.is_not_null())
+		.select((watches::id, watches::device_key_info_id))
+		.load::<(i32, Option<i32>)>(conn)
+		.with_context("unable to load the device watches")?;
+	for (watch_id, internal_id) in device_watches {
+		if let Some(internal_id) = internal_id {
+			watched_devices
+				.entry(internal_id)
+				.or_default()
+				.push(watch_id);
+		}
+	}
+
+	let location_devices = sql_query(include_str!(concat!(
+		env!("CARGO_MANIFEST_DIR"),
+		"/src/sql/watched_location_devices.sql"
+	)))
+	.bind::<Integer, _>(user)
+	.load::<WatchedLocationDevice>(conn)
+	.with_context("unable to load the devices in the watched locations")?;
+	for location_device in location_devices {
+		watched_devices
+			.entry(location_device.device_key_info_id)
+			.or_default()
+			.push(location_device.watch_id);
+	}
+
+	let search_watches = watches::table
+		.inner_join(saved_searches::table)
+		.filter(watches::user_id.eq(user))
+		.select((watches::id, saved_searches::query))
+		.load::<(i32, String)>(conn)
+		.with_context("unable to load the saved search watches")?;
+	for (watch_id, search_query) in search_watches {
+		let search_query = from_json_str::<SubmittedSearchQuery>(&search_query)
+			.with_context("unable to parse a saved search query")?;
+		let found_devices = build_search_query(&search_query)
+			.load::<DeviceInfoByName<'_>>(conn)
+			.with_context("unable to run a saved search")?;
+		for found_device in found_devices {
+			watched_devices
+				.entry(found_device.id)
+				.or_default()
+				.push(watch_id);
+		}
+	}
+
+	// A device can be covered by the same watch more than once, such as by a
+	// location watch while it's in a nested location
+	for watch_ids in watched_devices.values_mut() {
+		watch_ids.sort_unstable();
+		watch_ids.dedup();
+	}
+
+	Ok(watched_devices)
+}
+
+/// Loads up to `limit` changes after `after_change_id` to anything a user is
+/// watching, oldest first.
+///
+/// This is what the feed is built from, and any other way of notifying the user
+/// can use it the same way, keeping track of its own `after_change_id`.
+pub fn load_watch_feed<'a>(
+	conn: &mut SqliteConnection,
+	user: i32,
+	after_change_id: i32,
+	limit: i64,
+) -> Result<Vec<WatchFeedEntry<'a>>, Error> {
+	// Uses
+	use schema::{
+		device_changes::dsl::*,
+		device_key_info::dsl::{device_id, device_key_info},
+		user_info::dsl::user_info,
+	};
+
+	let watched_devices = load_watched_devices(conn, user)?;
+	if watched_devices.is_empty() {
+		return Ok(Vec::new());
+	}
+
+	let watched_ids = watched_devices.keys().copied().collect::<Vec<_>>();
+	let change_results = device_changes
+		.left_join(user_info)
+		.inner_join(device_key_info)
+		.filter(schema::device_changes::dsl::id.gt(after_change_id))
+		.filter(device_key_info_id.eq_any(watched_ids))
+		.order_by(schema::device_changes::dsl::id)
+		.limit(limit)
+		.select((DEVICE_CHANGE(), device_id))
+		.load::<(DeviceChangeDisplay<'_>, String)>(conn)
+		.with_context("unable to load the changes to the watched devices")?;
+
+	Ok(change_results
+		.into_iter()
+		.map(|(change_result, change_device_id)| WatchFeedEntry {
+			watch_ids: watched_devices
+				.get(&change_result.device_key_info_id)
+				.cloned()
+				.unwrap_or_default(),
+			change:    change_result,
+			device_id: change_device_id,
+		})
+		.collect())
+}
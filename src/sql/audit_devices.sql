@@ -0,0 +1,46 @@
+-- Lists the devices relevant to an audit: those recorded as being anywhere
+-- within the audited location, and those that were scanned during the audit.
+-- Binds: the audited location, the audit session, and then both again.
+
+-- @formatter:off
+SELECT
+	dki.id,
+	dki.device_id,
+	dki.location_id,
+	l.path AS location,
+	dki.location_id IN (
+		SELECT
+			ls.location_id
+		FROM location_subtrees AS ls
+		WHERE ls.ancestor_id = ?
+	) AS expected,
+	EXISTS (
+		SELECT
+			ast.id
+		FROM audit_scans AS ast
+		WHERE
+			ast.audit_session_id = ?
+			AND ast.device_key_info_id = dki.id
+	) AS scanned
+FROM device_key_info AS dki
+INNER JOIN location_paths AS l
+	ON l.id = dki.location_id
+WHERE
+	dki.deleted = 0
+	AND (
+		dki.location_id IN (
+			SELECT
+				ls.location_id
+			FROM location_subtrees AS ls
+			WHERE ls.ancestor_id = ?
+		)
+		OR EXISTS (
+			SELECT
+				ast.id
+			FROM audit_scans AS ast
+			WHERE
+				ast.audit_session_id = ?
+				AND ast.device_key_info_id = dki.id
+		)
+	)
+ORDER BY dki.device_id
@@ -0,0 +1,39 @@
+-- Lists device changes across every device, along with the device's current
+-- location and the name of the user who made each change.
+-- Filter conditions, the ordering and any paging are appended to the end of
+-- this.
+
+-- @formatter:off
+SELECT
+	ch.id,
+	ch.device_key_info_id,
+	dki.device_id,
+	dki.location_id,
+	lp.path AS location,
+	ch.timestamp,
+	ch.done_automatically,
+	ch.user_id,
+	u.display_name AS user,
+	ch.event,
+	ch.change,
+	ch.batch_id
+FROM (
+	SELECT
+		dc.*,
+		-- This has to match `WebhookEvent::from(&DeviceDiff)`
+		CASE
+			WHEN json_extract(dc.change, '$.deviceKeyInfo.operation') = 'add' THEN 'create'
+			WHEN json_extract(dc.change, '$.deviceKeyInfo.operation') = 'delete' THEN 'delete'
+			WHEN json_extract(dc.change, '$.deviceKeyInfo.operation') = 'restore' THEN 'restore'
+			WHEN json_extract(dc.change, '$.deviceLoan.operation') = 'checkOut' THEN 'check_out'
+			WHEN json_extract(dc.change, '$.deviceLoan.operation') = 'checkIn' THEN 'check_in'
+			ELSE 'update'
+		END AS event
+	FROM device_changes AS dc
+) AS ch
+INNER JOIN device_key_info AS dki
+	ON dki.id = ch.device_key_info_id
+INNER JOIN location_paths AS lp
+	ON lp.id = dki.location_id
+LEFT JOIN user_info AS u
+	ON u.id = ch.user_id
@@ -6,7 +6,7 @@ SELECT
 	dki.device_id,
 	dki.deleted,
 	dki.location_id,
-	l.name AS location,
+	l.path AS location,
 	(
 		SELECT
 			dc.timestamp
@@ -18,7 +18,7 @@ SELECT
 		LIMIT 1
 	) AS last_updated
 FROM device_key_info AS dki
-INNER JOIN locations AS l
+INNER JOIN location_paths AS l
     ON l.id = dki.location_id
 WHERE
 	(
@@ -0,0 +1,30 @@
+-- Lists every deleted device, along with when it was deleted and by whom,
+-- most recently deleted first.
+
+-- @formatter:off
+SELECT
+	dki.id,
+	dki.device_id,
+	dki.location_id,
+	lp.path AS location,
+	dc.timestamp AS deleted_at,
+	dc.user_id AS deleted_by,
+	u.display_name AS deleted_by_name
+FROM device_key_info AS dki
+INNER JOIN location_paths AS lp
+	ON lp.id = dki.location_id
+-- The most recent deletion, since a device can be deleted and restored many
+-- times
+LEFT JOIN device_changes AS dc
+	ON dc.id = (
+		SELECT
+			MAX(ddc.id)
+		FROM device_changes AS ddc
+		WHERE
+			ddc.device_key_info_id = dki.id
+			AND json_extract(ddc.change, '$.deviceKeyInfo.operation') = 'delete'
+	)
+LEFT JOIN user_info AS u
+	ON u.id = dc.user_id
+WHERE dki.deleted = 1
+ORDER BY dc.id DESC
@@ -0,0 +1,32 @@
+-- Lists every device attached beneath a device, directly or through other
+-- attached devices. Deleted devices (and anything attached beneath them) are
+-- left out.
+
+-- @formatter:off
+WITH RECURSIVE descendants (id) AS (
+	SELECT
+		dki.id
+	FROM device_key_info AS dki
+	WHERE
+		dki.parent_device_id = ?
+		AND dki.deleted = 0
+	UNION
+	SELECT
+		dki.id
+	FROM device_key_info AS dki
+	INNER JOIN descendants AS d
+		ON d.id = dki.parent_device_id
+	WHERE dki.deleted = 0
+)
+SELECT
+	dki.id,
+	dki.device_id,
+	dki.parent_device_id,
+	dki.location_id,
+	l.path AS location
+FROM descendants AS d
+INNER JOIN device_key_info AS dki
+	ON dki.id = d.id
+INNER JOIN location_paths AS l
+	ON l.id = dki.location_id
+ORDER BY dki.device_id
@@ -0,0 +1,27 @@
+-- Lists device loans, with the names of everyone involved filled in.
+-- Filter conditions and the ordering are appended to the end of this.
+
+-- @formatter:off
+SELECT
+	dl.id,
+	dki.device_id,
+	dl.borrower_user_id,
+	COALESCE(bu.display_name, dl.borrower_name) AS borrower,
+	dl.loaned_by,
+	lu.display_name AS loaned_by_name,
+	dl.loaned_at,
+	dl.due_at,
+	dl.notes,
+	dl.returned_at,
+	dl.returned_to,
+	ru.display_name AS returned_to_name,
+	dl.return_notes
+FROM device_loans AS dl
+INNER JOIN device_key_info AS dki
+	ON dki.id = dl.device_key_info_id
+LEFT JOIN user_info AS bu
+	ON bu.id = dl.borrower_user_id
+INNER JOIN user_info AS lu
+	ON lu.id = dl.loaned_by
+LEFT JOIN user_info AS ru
+	ON ru.id = dl.returned_to
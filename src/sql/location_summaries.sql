@@ -0,0 +1,30 @@
+-- Lists every location with its full path, along with the number of devices
+-- directly in it and in its whole subtree. Deleted devices aren't counted.
+
+-- @formatter:off
+SELECT
+	lp.id,
+	lp.parent_id,
+	lp.name,
+	lp.path,
+	lp.depth,
+	(
+		SELECT
+			COUNT(dki.id)
+		FROM device_key_info AS dki
+		WHERE
+			dki.location_id = lp.id
+			AND dki.deleted = 0
+	) AS device_count,
+	(
+		SELECT
+			COUNT(dki.id)
+		FROM location_subtrees AS ls
+		INNER JOIN device_key_info AS dki
+			ON dki.location_id = ls.location_id
+		WHERE
+			ls.ancestor_id = lp.id
+			AND dki.deleted = 0
+	) AS total_device_count
+FROM location_paths AS lp
+ORDER BY lp.path
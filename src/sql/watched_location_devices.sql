@@ -0,0 +1,15 @@
+-- Lists the devices in each location a user is watching, including the ones in
+-- any of its descendants. Deleted devices are included, so that their deletion
+-- still shows up in the user's feed.
+-- Binds: the user.
+
+-- @formatter:off
+SELECT
+	w.id AS watch_id,
+	dki.id AS device_key_info_id
+FROM watches AS w
+INNER JOIN location_subtrees AS ls
+	ON ls.ancestor_id = w.location_id
+INNER JOIN device_key_info AS dki
+	ON dki.location_id = ls.location_id
+WHERE w.user_id = ?
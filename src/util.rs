@@ -1,25 +1,224 @@
 // Uses
-use diesel::{dsl::exists, select, ExpressionMethods, QueryDsl, RunQueryDsl, SqliteConnection};
+use diesel::{
+	dsl::exists,
+	select,
+	BoolExpressionMethods,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+	TextExpressionMethods,
+};
 
 use crate::{
+	config::{CheckDigit, DeviceIdAlphabet, DeviceIdSettings},
 	db::schema,
-	error::{Context, Error},
-	id_gen::{gen_new_id, NumericAscii},
+	error::{Context, Error, InternalError, UserError},
+	id_gen::{damm, gen_new_id, luhn_mod_n, CrockfordBase32, NumericAscii},
 };
 
-/// Generates a new device ID, and ensures it's not already in use.
-pub fn gen_new_device_id(conn: &mut SqliteConnection) -> Result<String, Error> {
-	const LENGTH: usize = 6;
+/// Generates a new device ID in the configured format, and ensures it's not
+/// already in use or reserved.
+pub fn gen_new_device_id(
+	conn: &mut SqliteConnection,
+	settings: &DeviceIdSettings,
+) -> Result<String, Error> {
+	if settings.sequential {
+		return gen_next_sequential_device_id(conn, settings);
+	}
 
-	gen_new_id(NumericAscii, LENGTH, |new_id| {
-		use schema::device_key_info::dsl::*;
+	let device_id_is_used = |new_payload: &str| {
+		device_id_is_taken(conn, build_device_id(settings, new_payload).as_str())
+	};
+	let payload = match settings.alphabet {
+		DeviceIdAlphabet::Numeric => gen_new_id(NumericAscii, settings.length, device_id_is_used),
+		DeviceIdAlphabet::CrockfordBase32 => {
+			gen_new_id(CrockfordBase32, settings.length, device_id_is_used)
+		}
+	}
+	.with_context("almost every device ID in the configured format is already in use")?;
+
+	Ok(build_device_id(settings, payload.as_str()))
+}
+
+/// Checks whether a device ID is already used by a device (including deleted
+/// ones) or reserved.
+pub fn device_id_is_taken(conn: &mut SqliteConnection, device: &str) -> Result<bool, Error> {
+	use schema::{device_key_info, reserved_device_ids};
 
-		select(exists(device_key_info.filter(device_id.eq(new_id))))
-			.get_result::<bool>(conn)
-			.with_context("unable to query the database for a device ID")
+	select(
+		exists(device_key_info::table.filter(device_key_info::device_id.eq(device))).or(exists(
+			reserved_device_ids::table.filter(reserved_device_ids::device_id.eq(device)),
+		)),
+	)
+	.get_result::<bool>(conn)
+	.with_context("unable to query the database for a device ID")
+}
+
+/// Generates the device ID that comes after the highest existing or reserved
+/// one in the configured format.
+fn gen_next_sequential_device_id(
+	conn: &mut SqliteConnection,
+	settings: &DeviceIdSettings,
+) -> Result<String, Error> {
+	use schema::{device_key_info, reserved_device_ids};
+
+	// Since the characters of each alphabet are in ascending order and all IDs in
+	// the format are the same length, sorting them as text sorts them by number
+	// too
+	// Deleted devices are included so that their IDs are never reused
+	let id_pattern = format!(
+		"{}{}",
+		settings.prefix,
+		"_".repeat(settings.total_length() - settings.prefix.len())
+	);
+	let mut existing_ids = device_key_info::table
+		.filter(device_key_info::device_id.like(id_pattern.as_str()))
+		.select(device_key_info::device_id)
+		.load::<String>(conn)
+		.with_context("unable to query the database for existing device IDs")?;
+	existing_ids.extend(
+		reserved_device_ids::table
+			.filter(reserved_device_ids::device_id.like(id_pattern.as_str()))
+			.select(reserved_device_ids::device_id)
+			.load::<String>(conn)
+			.with_context("unable to query the database for reserved device IDs")?,
+	);
+	existing_ids.sort_unstable_by(|a, b| b.cmp(a));
+	let next_number = existing_ids
+		.iter()
+		.find_map(|existing_id| {
+			let (payload, _) = split_device_id(settings, existing_id.as_str())?;
+			device_id_matches_format(settings, existing_id.as_str())
+				.then(|| decode_payload(settings, payload))
+		})
+		.map_or(Some(1), |last_number| last_number.checked_add(1));
+
+	let payload = next_number
+		.and_then(|number| encode_payload(settings, number))
+		.ok_or(InternalError::Assertion(
+			"every device ID in the configured format is already in use",
+		))?;
+
+	Ok(build_device_id(settings, payload.as_str()))
+}
+
+/// Verifies the check digit of a device ID that's being looked up, to catch
+/// typos.
+///
+/// Only IDs that look like they're in the configured format are checked, and
+/// any ID that exactly matches an existing device is accepted regardless, since
+/// devices keep their IDs when the format is changed.
+pub fn verify_device_id(
+	conn: &mut SqliteConnection,
+	settings: &DeviceIdSettings,
+	device: &str,
+) -> Result<(), Error> {
+	use schema::device_key_info::dsl::*;
+
+	let Some((payload, check_suffix)) = split_device_id(settings, device) else {
+		return Ok(());
+	};
+	if check_digit_suffix(settings, payload) == check_suffix {
+		return Ok(());
+	}
+
+	let device_exists = select(exists(device_key_info.filter(device_id.eq(device))))
+		.get_result::<bool>(conn)
+		.with_context("unable to query the database for device existence")?;
+	if !device_exists {
+		return Err(UserError::BadRequest(
+			"The device ID's check digit doesn't match. Please double-check it for typos.",
+		)
+		.into());
+	}
+
+	Ok(())
+}
+
+/// Checks whether a device ID is in the configured format, including having a
+/// valid check digit.
+pub fn device_id_matches_format(settings: &DeviceIdSettings, device: &str) -> bool {
+	split_device_id(settings, device).is_some_and(|(payload, check_suffix)| {
+		check_digit_suffix(settings, payload) == check_suffix
 	})
 }
 
+/// Builds a complete device ID from its generated part, adding the prefix and
+/// check digit.
+fn build_device_id(settings: &DeviceIdSettings, payload: &str) -> String {
+	format!(
+		"{}{payload}{}",
+		settings.prefix,
+		check_digit_suffix(settings, payload)
+	)
+}
+
+/// Splits a device ID into its generated part and its check digit (which may be
+/// empty), if it's in the configured format.
+fn split_device_id<'a>(settings: &DeviceIdSettings, device: &'a str) -> Option<(&'a str, &'a str)> {
+	if device.len() != settings.total_length() {
+		return None;
+	}
+
+	let charset = settings.alphabet.charset();
+	let rest = device.strip_prefix(settings.prefix.as_str())?;
+	if !rest.bytes().all(|c| charset.contains(&c)) {
+		return None;
+	}
+
+	Some(rest.split_at(settings.length))
+}
+
+/// Calculates the check digit for the generated part of a device ID, which is
+/// empty if the format doesn't use one.
+fn check_digit_suffix(settings: &DeviceIdSettings, payload: &str) -> String {
+	let check_digit = match settings.check_digit {
+		CheckDigit::None => return String::new(),
+		CheckDigit::Luhn => luhn_mod_n(settings.alphabet.charset(), payload),
+		CheckDigit::Damm => damm(payload),
+	};
+
+	// The payload always comes from the alphabet, and the config validation
+	// ensures the algorithm supports it
+	char::from(check_digit.expect("the payload must only contain characters from the alphabet"))
+		.to_string()
+}
+
+/// Decodes the generated part of a device ID as a number, in the base of the
+/// alphabet.
+fn decode_payload(settings: &DeviceIdSettings, payload: &str) -> u128 {
+	let charset = settings.alphabet.charset();
+
+	payload.bytes().fold(0, |number, c| {
+		let value = charset
+			.iter()
+			.position(|charset_c| *charset_c == c)
+			.expect("the payload must only contain characters from the alphabet");
+		number * charset.len() as u128 + value as u128
+	})
+}
+
+/// Encodes a number as the generated part of a device ID, padded to the
+/// configured length.
+///
+/// Returns `None` if the number is too large to fit.
+fn encode_payload(settings: &DeviceIdSettings, mut number: u128) -> Option<String> {
+	let charset = settings.alphabet.charset();
+	let base = charset.len() as u128;
+
+	let mut payload = vec![0; settings.length];
+	for c in payload.iter_mut().rev() {
+		*c = charset[(number % base) as usize];
+		number /= base;
+	}
+	if number > 0 {
+		return None;
+	}
+
+	Some(String::from_utf8(payload).expect("the alphabets are all ASCII"))
+}
+
 /// Generates a new component ID, and ensures it's not already in use with
 /// `device_id`.
 pub fn gen_new_component_id(conn: &mut SqliteConnection, device_id: i32) -> Result<String, Error> {
@@ -55,3 +254,53 @@ pub fn gen_new_attachment_id(conn: &mut SqliteConnection, device_id: i32) -> Res
 		.with_context("unable to query the database for a attachment ID")
 	})
 }
+
+#[cfg(test)]
+mod tests {
+	// Uses
+	use super::*;
+
+	fn settings(alphabet: DeviceIdAlphabet, length: usize) -> DeviceIdSettings {
+		DeviceIdSettings {
+			length,
+			alphabet,
+			..DeviceIdSettings::default()
+		}
+	}
+
+	#[test]
+	fn payloads_round_trip() {
+		let numeric = settings(DeviceIdAlphabet::Numeric, 4);
+		for number in [0, 1, 42, 9999] {
+			let payload = encode_payload(&numeric, number).unwrap();
+			assert_eq!(payload.len(), 4);
+			assert_eq!(decode_payload(&numeric, payload.as_str()), number);
+		}
+		assert_eq!(encode_payload(&numeric, 42).unwrap(), "0042");
+
+		let crockford = settings(DeviceIdAlphabet::CrockfordBase32, 3);
+		for number in [0, 31, 32, 32_u128.pow(3) - 1] {
+			let payload = encode_payload(&crockford, number).unwrap();
+			assert_eq!(decode_payload(&crockford, payload.as_str()), number);
+		}
+		assert_eq!(
+			encode_payload(&crockford, 32_u128.pow(3) - 1).unwrap(),
+			"ZZZ"
+		);
+	}
+
+	#[test]
+	fn encoding_too_large_a_number_fails() {
+		assert_eq!(
+			encode_payload(&settings(DeviceIdAlphabet::Numeric, 4), 10_000),
+			None
+		);
+		assert_eq!(
+			encode_payload(
+				&settings(DeviceIdAlphabet::CrockfordBase32, 3),
+				32_u128.pow(3)
+			),
+			None
+		);
+	}
+}
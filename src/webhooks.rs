@@ -0,0 +1,448 @@
+//! Outbound webhooks, which notify other systems of device changes.
+//!
+//! Every change is written to an outbox in the same transaction that logs it,
+//! and a background task delivers the outbox to the configured webhooks,
+//! retrying failed deliveries with backoff.
+
+// Uses
+use std::{borrow::Cow, fmt::Write, time::Duration};
+
+use chrono::{Duration as ChronoDuration, NaiveDateTime, Utc};
+use diesel::{
+	insert_into,
+	update,
+	BoolExpressionMethods,
+	ExpressionMethods,
+	QueryDsl,
+	RunQueryDsl,
+	SqliteConnection,
+};
+use hmac::{Hmac, Mac};
+use reqwest::{header::CONTENT_TYPE, Client};
+use rocket::{
+	serde::json::{json, Value as JsonValue},
+	tokio::{spawn, time::interval},
+	Orbit,
+	Rocket,
+};
+use serde_json::{from_str as from_json_str, to_string as to_json_string};
+use sha2::Sha256;
+
+use crate::{
+	config::{WebhookEndpoint, WebhookSettings},
+	db::{
+		change_log::{DeviceDiff, DeviceKeyInfoDiff, DeviceLoanDiff},
+		models::{WebhookDelivery, WebhookDeliveryNew, WebhookOutboxEntryNew},
+		schema,
+		DbConn,
+		DbPool,
+	},
+	error::{Context, Error},
+};
+
+// Constants
+/// How often the dispatcher checks the outbox for changes to deliver.
+const DISPATCHER_TICK: Duration = Duration::from_secs(10);
+/// The most outbox entries with deliveries due to look at on each tick.
+const DISPATCH_BATCH_SIZE: i64 = 100;
+/// The most times the retry delay is doubled.
+const MAX_BACKOFF_DOUBLINGS: u32 = 16;
+pub const SIGNATURE_HEADER: &str = "X-Pecan-Signature";
+pub const EVENT_HEADER: &str = "X-Pecan-Event";
+pub const DELIVERY_HEADER: &str = "X-Pecan-Delivery";
+
+// Type Definitions
+type HmacSha256 = Hmac<Sha256>;
+
+/// The kind of change a webhook is notified of.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookEvent {
+	Create,
+	Update,
+	Delete,
+	Restore,
+	CheckOut,
+	CheckIn,
+}
+
+impl WebhookEvent {
+	pub const ALL: [Self; 6] = [
+		Self::Create,
+		Self::Update,
+		Self::Delete,
+		Self::Restore,
+		Self::CheckOut,
+		Self::CheckIn,
+	];
+
+	/// The name the event is stored and sent as, which matches the name used
+	/// in the config.
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::Create => "create",
+			Self::Update => "update",
+			Self::Delete => "delete",
+			Self::Restore => "restore",
+			Self::CheckOut => "check_out",
+			Self::CheckIn => "check_in",
+		}
+	}
+
+	pub fn from_name(name: &str) -> Option<Self> {
+		Self::ALL.into_iter().find(|event| event.name() == name)
+	}
+}
+
+/// This is mirrored in `change_history.sql`, which has to be kept in sync.
+impl From<&DeviceDiff<'_>> for WebhookEvent {
+	fn from(diff: &DeviceDiff<'_>) -> Self {
+		match (&diff.device_key_info, &diff.device_loan) {
+			(Some(DeviceKeyInfoDiff::Add(_)), _) => Self::Create,
+			(Some(DeviceKeyInfoDiff::Delete), _) => Self::Delete,
+			(Some(DeviceKeyInfoDiff::Restore), _) => Self::Restore,
+			(_, Some(DeviceLoanDiff::CheckOut { .. })) => Self::CheckOut,
+			(_, Some(DeviceLoanDiff::CheckIn { .. })) => Self::CheckIn,
+			_ => Self::Update,
+		}
+	}
+}
+
+/// A delivery that's due to be attempted.
+#[derive(Debug)]
+struct PendingDelivery {
+	webhook_outbox_id: i32,
+	event:             String,
+	endpoint:          WebhookEndpoint,
+	attempt:           i32,
+	body:              String,
+}
+
+/// Adds a change to the outbox, to be sent to the webhooks.
+///
+/// This should be called in the same transaction as the change is logged in,
+/// so that the outbox only ever contains changes that were actually made.
+pub fn enqueue_webhook_event(
+	conn: &mut SqliteConnection,
+	change_id: i32,
+	webhook_event: WebhookEvent,
+) -> Result<(), Error> {
+	// Uses
+	use schema::webhook_outbox::dsl::*;
+
+	insert_into(webhook_outbox)
+		.values(WebhookOutboxEntryNew {
+			device_change_id: change_id,
+			event:            Cow::from(webhook_event.name()),
+			created_at:       Utc::now().naive_utc(),
+		})
+		.execute(conn)
+		.with_context("unable to insert into webhook_outbox")?;
+
+	Ok(())
+}
+
+/// The time to wait after a delivery's `failed_attempts`th failed attempt
+/// before trying again.
+fn retry_delay(settings: &WebhookSettings, failed_attempts: u32) -> ChronoDuration {
+	let doublings = failed_attempts.saturating_sub(1).min(MAX_BACKOFF_DOUBLINGS);
+	ChronoDuration::seconds(i64::from(settings.retry_delay_seconds) << doublings)
+}
+
+/// Looks through the outbox for deliveries that are due, and marks the
+/// entries that have nothing left to deliver as completed.
+///
+/// Entries that are only waiting on retries are filtered out before the batch
+/// is limited, so that they can't hold up newer changes.
+fn load_pending_deliveries(
+	conn: &mut SqliteConnection,
+	settings: &WebhookSettings,
+	now: NaiveDateTime,
+) -> Result<Vec<PendingDelivery>, Error> {
+	// Uses
+	use schema::{
+		device_changes::dsl::{change, device_changes, done_automatically, timestamp, user_id},
+		device_key_info::dsl::{device_id, device_key_info},
+		webhook_deliveries::dsl::{webhook_deliveries, webhook_outbox_id},
+		webhook_outbox::dsl::*,
+	};
+
+	let outbox_results = webhook_outbox
+		.inner_join(device_changes.inner_join(device_key_info))
+		.filter(completed_at.is_null())
+		.filter(next_attempt_at.is_null().or(next_attempt_at.le(now)))
+		.order_by(schema::webhook_outbox::dsl::id)
+		.limit(DISPATCH_BATCH_SIZE)
+		.select((
+			schema::webhook_outbox::dsl::id,
+			event,
+			device_change_id,
+			device_id,
+			timestamp,
+			done_automatically,
+			user_id,
+			change,
+		))
+		.load::<(
+			i32,
+			String,
+			i32,
+			String,
+			NaiveDateTime,
+			bool,
+			Option<i32>,
+			String,
+		)>(conn)
+		.with_context("unable to load the webhook outbox")?;
+
+	let mut pending_deliveries = Vec::new();
+	for (
+		outbox_id,
+		outbox_event,
+		change_id,
+		change_device_id,
+		change_timestamp,
+		change_done_automatically,
+		change_user_id,
+		change_value,
+	) in outbox_results
+	{
+		let delivery_results = webhook_deliveries
+			.filter(webhook_outbox_id.eq(outbox_id))
+			.load::<WebhookDelivery<'_>>(conn)
+			.with_context("unable to load the webhook deliveries")?;
+
+		// Entries for events that no longer exist are just skipped
+		let receiving_endpoints = WebhookEvent::from_name(outbox_event.as_str())
+			.map(|parsed_event| {
+				settings
+					.endpoints
+					.iter()
+					.filter(|endpoint| endpoint.receives(parsed_event))
+					.collect::<Vec<_>>()
+			})
+			.unwrap_or_default();
+
+		let mut body = None;
+		let mut completed = true;
+		let mut next_attempt_value = None::<NaiveDateTime>;
+		for endpoint in receiving_endpoints {
+			let endpoint_deliveries = delivery_results
+				.iter()
+				.filter(|delivery| delivery.webhook_name == endpoint.name)
+				.collect::<Vec<_>>();
+			if endpoint_deliveries
+				.iter()
+				.any(|delivery| delivery.succeeded)
+			{
+				continue;
+			}
+			let failed_attempts = u32::try_from(endpoint_deliveries.len()).unwrap_or(u32::MAX);
+			if failed_attempts >= settings.max_attempts {
+				continue;
+			}
+			completed = false;
+
+			let due_at = endpoint_deliveries
+				.iter()
+				.map(|delivery| delivery.attempted_at)
+				.max()
+				.map(|last_attempted_at| {
+					last_attempted_at + retry_delay(settings, failed_attempts)
+				});
+			if let Some(due_at) = due_at.filter(|due_at| *due_at > now) {
+				next_attempt_value =
+					Some(next_attempt_value.map_or(due_at, |value| value.min(due_at)));
+				continue;
+			}
+
+			// If this attempt fails, the next one is due after the retry delay
+			let retry_at = now + retry_delay(settings, failed_attempts + 1);
+			next_attempt_value =
+				Some(next_attempt_value.map_or(retry_at, |value| value.min(retry_at)));
+
+			// The body is the same for every webhook, so it's only built once
+			if body.is_none() {
+				let change_json = from_json_str::<JsonValue>(change_value.as_str())
+					.with_context("unable to parse the logged change")?;
+				body = Some(
+					to_json_string(&json!({
+						"deliveryId": outbox_id,
+						"event": outbox_event,
+						"changeId": change_id,
+						"deviceId": change_device_id,
+						"timestamp": change_timestamp,
+						"doneAutomatically": change_done_automatically,
+						"userId": change_user_id,
+						"change": change_json,
+					}))
+					.with_context("unable to serialise the webhook body to JSON")?,
+				);
+			}
+
+			pending_deliveries.push(PendingDelivery {
+				webhook_outbox_id: outbox_id,
+				event:             outbox_event.clone(),
+				endpoint:          endpoint.clone(),
+				attempt:           i32::try_from(failed_attempts + 1).unwrap_or(i32::MAX),
+				body:              body.clone().unwrap_or_default(),
+			});
+		}
+
+		let outbox_entry = webhook_outbox.filter(schema::webhook_outbox::dsl::id.eq(outbox_id));
+		if completed {
+			update(outbox_entry)
+				.set((
+					completed_at.eq(now),
+					next_attempt_at.eq(None::<NaiveDateTime>),
+				))
+				.execute(conn)
+				.with_context("unable to update webhook_outbox")?;
+		} else {
+			update(outbox_entry)
+				.set(next_attempt_at.eq(next_attempt_value))
+				.execute(conn)
+				.with_context("unable to update webhook_outbox")?;
+		}
+	}
+
+	Ok(pending_deliveries)
+}
+
+/// Signs a request body with a webhook's secret, in the format sent in the
+/// signature header.
+pub fn sign_body(secret: &str, body: &str) -> String {
+	let mut mac =
+		HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+	mac.update(body.as_bytes());
+
+	let mut signature = String::from("sha256=");
+	for byte in mac.finalize().into_bytes() {
+		write!(signature, "{byte:02x}").expect("writing to a `String` can't fail");
+	}
+	signature
+}
+
+/// Attempts a delivery, returning the record of the attempt.
+async fn attempt_delivery(
+	client: &Client,
+	delivery: &PendingDelivery,
+) -> WebhookDeliveryNew<'static> {
+	let response = client
+		.post(delivery.endpoint.url.as_str())
+		.header(CONTENT_TYPE, "application/json")
+		.header(
+			SIGNATURE_HEADER,
+			sign_body(delivery.endpoint.secret.as_str(), delivery.body.as_str()),
+		)
+		.header(EVENT_HEADER, delivery.event.as_str())
+		.header(DELIVERY_HEADER, delivery.webhook_outbox_id.to_string())
+		.body(delivery.body.clone())
+		.send()
+		.await;
+
+	let (status_code, error, succeeded) = match response {
+		Ok(response) => {
+			let status = response.status();
+			(
+				Some(i32::from(status.as_u16())),
+				(!status.is_success())
+					.then(|| Cow::from(format!("the webhook responded with {status}"))),
+				status.is_success(),
+			)
+		}
+		Err(e) => (None, Some(Cow::from(e.to_string())), false),
+	};
+
+	WebhookDeliveryNew {
+		webhook_outbox_id: delivery.webhook_outbox_id,
+		webhook_name: Cow::from(delivery.endpoint.name.clone()),
+		attempt: delivery.attempt,
+		attempted_at: Utc::now().naive_utc(),
+		status_code,
+		error,
+		succeeded,
+	}
+}
+
+/// Delivers everything in the outbox that's due.
+async fn dispatch_outbox(
+	conn: &DbConn,
+	client: &Client,
+	settings: &WebhookSettings,
+) -> Result<(), Error> {
+	let loader_settings = settings.clone();
+	let pending_deliveries = conn
+		.run(move |c| load_pending_deliveries(c, &loader_settings, Utc::now().naive_utc()))
+		.await?;
+
+	for delivery in pending_deliveries {
+		let delivery_record = attempt_delivery(client, &delivery).await;
+		conn.run(move |c| {
+			// Uses
+			use schema::{webhook_deliveries, webhook_outbox};
+
+			let delivery_succeeded = delivery_record.succeeded;
+			insert_into(webhook_deliveries::table)
+				.values(delivery_record)
+				.execute(c)
+				.with_context("unable to insert into webhook_deliveries")?;
+
+			// The entry may have nothing left to deliver now, so it's checked again on
+			// the next tick
+			if delivery_succeeded {
+				update(webhook_outbox::table.find(delivery.webhook_outbox_id))
+					.set(webhook_outbox::next_attempt_at.eq(None::<NaiveDateTime>))
+					.execute(c)
+					.with_context("unable to update webhook_outbox")?;
+			}
+
+			Ok::<_, Error>(())
+		})
+		.await?;
+	}
+
+	Ok(())
+}
+
+/// Starts delivering the outbox in the background.
+pub async fn start_dispatcher(rocket: &Rocket<Orbit>) {
+	let Ok(settings) = rocket
+		.figment()
+		.extract_inner::<WebhookSettings>("webhooks")
+	else {
+		eprintln!("unable to load the webhook settings");
+		return;
+	};
+
+	let client = match Client::builder()
+		.timeout(Duration::from_secs(u64::from(settings.timeout_seconds)))
+		.build()
+	{
+		Ok(client) => client,
+		Err(e) => {
+			eprintln!("unable to set up the webhook client: {e}");
+			return;
+		}
+	};
+
+	let Some(pool) = DbPool::get_from(rocket) else {
+		eprintln!("the database connection pool isn't attached");
+		return;
+	};
+
+	spawn(async move {
+		let mut ticker = interval(DISPATCHER_TICK);
+		loop {
+			ticker.tick().await;
+
+			let Some(conn) = pool.get_one().await else {
+				eprintln!("unable to get a database connection for the webhook dispatcher");
+				continue;
+			};
+			if let Err(e) = dispatch_outbox(&conn, &client, &settings).await {
+				eprintln!("unable to deliver the webhook outbox: {e}");
+			}
+		}
+	});
+}